@@ -0,0 +1,140 @@
+//! Compares the vectorised line ending counter with the byte-at-a-time loop it replaced, on
+//! log-like data.
+//!
+//! The data is generated in memory, `LINE_ENDINGS_BENCH_MB` megabytes of it (64 by default).
+//! Set `LINE_ENDINGS_BENCH_FILE` to read a real log from disk instead, so multi-gigabyte
+//! archives can be measured without holding them in memory:
+//!
+//! ```text
+//! LINE_ENDINGS_BENCH_FILE=/var/log/big.log cargo bench
+//! ```
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use std::fs::File;
+use std::hint::black_box;
+use std::io::Read;
+
+use line_endings::analysis::count_line_endings_buffered;
+use line_endings::types::{LineEndingCounts, TextEncoding};
+
+const BUFFER_SIZES: [(usize, &str); 3] = [(4 << 10, "4KB"), (64 << 10, "64KB"), (1 << 20, "1MB")];
+
+/// Log lines of varying length, mostly LF with some CRLF and the odd lone CR
+fn generate_log(megabytes: usize) -> Vec<u8> {
+    let size = megabytes << 20;
+    let mut data = Vec::with_capacity(size + 256);
+    let mut line = 0usize;
+    while data.len() < size {
+        data.extend_from_slice(b"2026-01-01T00:00:00Z INFO request handled path=/api/v1/items id=");
+        data.extend_from_slice(line.to_string().as_bytes());
+        data.extend(std::iter::repeat_n(b' ', line % 7));
+        data.extend_from_slice(match line % 50 {
+            0 => b"\r",
+            n if n % 10 == 0 => b"\r\n",
+            _ => b"\n",
+        });
+        line += 1;
+    }
+    data
+}
+
+/// Generated log data, or a log file that is read from disk on every iteration
+enum Input {
+    Memory(Vec<u8>),
+    File(String),
+}
+
+/// Runs a counter over the benchmark input, either the generated data or the named file
+fn count(
+    input: &Input,
+    counter: fn(&mut dyn Read, usize) -> LineEndingCounts,
+    buffer_size: usize,
+) -> LineEndingCounts {
+    match input {
+        Input::Memory(data) => counter(&mut data.as_slice(), buffer_size),
+        Input::File(path) => {
+            let mut file = File::open(path).expect("Failed to open benchmark file");
+            counter(&mut file, buffer_size)
+        }
+    }
+}
+
+fn vectorised(reader: &mut dyn Read, buffer_size: usize) -> LineEndingCounts {
+    count_line_endings_buffered(reader, TextEncoding::Utf8, buffer_size).unwrap()
+}
+
+/// The loop that counted line endings before `memchr`, looking at every byte in turn. It
+/// is kept here, rather than in the library, as the baseline to measure against.
+fn byte_loop(reader: &mut dyn Read, buffer_size: usize) -> LineEndingCounts {
+    let mut counts = LineEndingCounts::default();
+    let mut buffer = vec![0u8; buffer_size];
+    let mut prev_was_cr = false;
+
+    loop {
+        let n = reader.read(&mut buffer).unwrap();
+        if n == 0 {
+            break;
+        }
+        for &byte in &buffer[..n] {
+            match byte {
+                b'\n' if prev_was_cr => counts.crlf += 1,
+                b'\n' => counts.lf += 1,
+                // The previous CR was not followed by LF
+                _ if prev_was_cr => counts.cr += 1,
+                _ => {}
+            }
+            prev_was_cr = byte == b'\r';
+        }
+    }
+    if prev_was_cr {
+        counts.cr += 1;
+    }
+    counts
+}
+
+fn bench_count_line_endings(c: &mut Criterion) {
+    let (input, bytes) = if let Ok(path) = std::env::var("LINE_ENDINGS_BENCH_FILE") {
+        let bytes = std::fs::metadata(&path)
+            .expect("Failed to read benchmark file")
+            .len();
+        (Input::File(path), bytes)
+    } else {
+        let megabytes = std::env::var("LINE_ENDINGS_BENCH_MB")
+            .ok()
+            .and_then(|mb| mb.parse().ok())
+            .unwrap_or(64);
+        let data = generate_log(megabytes);
+        let bytes = data.len() as u64;
+        (Input::Memory(data), bytes)
+    };
+
+    // Both paths must agree before their speeds are worth comparing
+    assert_eq!(
+        count(&input, vectorised, BUFFER_SIZES[0].0),
+        count(&input, byte_loop, BUFFER_SIZES[0].0)
+    );
+
+    let mut group = c.benchmark_group("count_line_endings");
+    group.throughput(Throughput::Bytes(bytes));
+    group.sample_size(10);
+    for (buffer_size, label) in BUFFER_SIZES {
+        group.bench_with_input(
+            BenchmarkId::new("memchr", label),
+            &buffer_size,
+            |b, &size| {
+                b.iter(|| count(black_box(&input), vectorised, size));
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("byte_loop", label),
+            &buffer_size,
+            |b, &size| {
+                b.iter(|| count(black_box(&input), byte_loop, size));
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_count_line_endings);
+criterion_main!(benches);
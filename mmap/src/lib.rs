@@ -0,0 +1,42 @@
+//! Memory mapping for large files, used by the `mmap` feature of `line_endings`. This crate
+//! holds the only `unsafe` code, so that `line_endings` itself forbids it with every feature
+//! enabled. Callers fall back to streamed reads whenever mapping returns `None`.
+#![deny(unsafe_code)]
+
+use memmap2::Mmap;
+use std::fs::File;
+
+/// Files at least this large are mapped into memory rather than read in buffers
+pub const MMAP_THRESHOLD: u64 = 64 << 20;
+
+/// Maps an open file into memory if it is at least `min_size` bytes. Returns `None` for
+/// smaller files, for files that cannot be mapped such as pipes and some network file
+/// systems, and for files whose size changed while they were being mapped, so the caller
+/// can stream them instead.
+#[must_use]
+pub fn map_large_file(file: &File, min_size: u64) -> Option<Mmap> {
+    let size = file.metadata().ok()?.len();
+    if size < min_size || size == 0 {
+        return None;
+    }
+
+    // SAFETY: The map is read-only and is dropped before the caller returns. Another process
+    // writing to the file while it is mapped changes what is read, as a streamed read would,
+    // but truncating it makes reading the missing pages raise SIGBUS and crash the process.
+    // That risk is why mapping is opt-in, and is only used for analysis, never for rewriting
+    // files.
+    #[allow(unsafe_code)]
+    let map = unsafe { Mmap::map(file) }.ok()?;
+
+    // A file truncated before it was mapped would fault as soon as the missing pages are
+    // read, so only a mapping of the whole file as it is now is used
+    if map.len() as u64 != size || file.metadata().ok()?.len() != size {
+        return None;
+    }
+
+    // The whole file is read once from start to end, so the kernel can read ahead
+    #[cfg(unix)]
+    let _ = map.advise(memmap2::Advice::Sequential);
+
+    Some(map)
+}
@@ -1,15 +1,29 @@
 use anyhow::Result;
+use memchr::memchr2_iter;
+use rayon::prelude::*;
 use std::fs::File;
-use std::io::{BufReader, Read};
+use std::io::{self, BufReader, Read};
 use std::path::Path;
 
-use crate::types::{BomType, ConfigSettings, FileAnalysis};
+use crate::chunks::{CHUNK_SIZE, ChunkReader, PARALLEL_THRESHOLD, line_aligned_chunks};
+use crate::types::{
+    BomType, ConfigSettings, FileAnalysis, FirstLineEndings, LineEnding, LineEndingCounts,
+    TextEncoding,
+};
+use crate::utils::for_each_code_unit;
+
+#[cfg(feature = "mmap")]
+use crate::mmap::{MMAP_THRESHOLD, map_large_file};
+
+/// Bytes read at a time when scanning lines, unless `--buffer-size` changes it
+pub const DEFAULT_SCAN_BUFFER_SIZE: usize = 64 * 1024;
 
 // Define constants for line ending characters
-const BUFFER_SIZE: usize = 4096; // 4KB buffer for more efficient reading
 const BINARY_CHECK_SIZE: usize = 8192; // 8KB for binary detection
-const LF: u8 = b'\n';
-const CR: u8 = b'\r';
+const LF: u32 = 0x0A;
+const CR: u32 = 0x0D;
+const SPACE: u32 = 0x20;
+const TAB: u32 = 0x09;
 
 // Define BOM (Byte Order Marker) constants
 const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
@@ -18,124 +32,543 @@ const UTF16_BE_BOM: &[u8] = &[0xFE, 0xFF];
 const UTF32_LE_BOM: &[u8] = &[0xFF, 0xFE, 0x00, 0x00];
 const UTF32_BE_BOM: &[u8] = &[0x00, 0x00, 0xFE, 0xFF];
 
-/// Analyzes a single file for line endings and BOM
+/// Analyzes a single file for line endings and BOM, opening it once
 pub fn analyze_file(path: impl AsRef<Path>, config: &ConfigSettings) -> FileAnalysis {
-    // Check if file is binary (skip if detected)
-    match is_binary_file(&path) {
-        Ok(true) => {
+    let path = path.as_ref();
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) => {
             return FileAnalysis {
-                path: path.as_ref().to_path_buf(),
-                lf_count: 0,
-                crlf_count: 0,
-                bom_checked: false,
-                bom_type: None,
-                is_binary: true,
-                error: None,
+                error: Some(format!("Failed to check file type: {e}")),
+                ..empty_analysis(path)
             };
         }
+    };
+    let size = file.metadata().map_or(0, |metadata| metadata.len());
+
+    #[cfg(feature = "mmap")]
+    if let Some(map) = map_large_file(&file, MMAP_THRESHOLD) {
+        return analyze_mapped(path, &map, config);
+    }
+    if size >= PARALLEL_THRESHOLD {
+        return analyze_open_file_in_chunks(path, file, size, config, CHUNK_SIZE);
+    }
+
+    analyze_stream(path, file, size, config)
+}
+
+/// Analyzes a file as `analyze_file` does, scanning chunks of about `chunk_size` bytes on
+/// separate threads. `analyze_file` does this for files of `PARALLEL_THRESHOLD` bytes or
+/// more. UTF-16/32 files are scanned on one thread.
+pub fn analyze_file_in_chunks(
+    path: impl AsRef<Path>,
+    config: &ConfigSettings,
+    chunk_size: u64,
+) -> FileAnalysis {
+    let path = path.as_ref();
+    let opened = File::open(path).and_then(|file| Ok((file.metadata()?.len(), file)));
+    match opened {
+        Ok((size, file)) => analyze_open_file_in_chunks(path, file, size, config, chunk_size),
+        Err(e) => FileAnalysis {
+            error: Some(format!("Failed to check file type: {e}")),
+            ..empty_analysis(path)
+        },
+    }
+}
+
+/// Analyzes a file of `size` bytes that is already open, scanning it in chunks
+fn analyze_open_file_in_chunks(
+    path: &Path,
+    mut file: File,
+    size: u64,
+    config: &ConfigSettings,
+    chunk_size: u64,
+) -> FileAnalysis {
+    let head = match sniff_head(&mut file) {
+        Ok(head) => head,
         Err(e) => {
             return FileAnalysis {
-                path: path.as_ref().to_path_buf(),
-                lf_count: 0,
-                crlf_count: 0,
-                bom_checked: false,
-                bom_type: None,
-                is_binary: false,
                 error: Some(format!("Failed to check file type: {e}")),
+                ..empty_analysis(path)
             };
         }
-        Ok(false) => {} // Not binary, continue processing
-    }
-
-    // Only detect BOM if check_bom is true
-    let bom_type: Option<BomType> = if config.check_bom {
-        match detect_bom(&path) {
-            Ok(bom) => bom,
-            Err(e) => {
-                return FileAnalysis {
-                    path: path.as_ref().to_path_buf(),
-                    lf_count: 0,
-                    crlf_count: 0,
-                    bom_checked: false,
-                    bom_type: None,
-                    is_binary: false,
-                    error: Some(format!("Failed to detect BOM: {e}")),
-                };
-            }
+    };
+    let analysis = analyze_head(path, &head, size, config);
+    if analysis.is_binary {
+        return analysis;
+    }
+
+    let scan = if analysis.encoding == TextEncoding::Utf8 {
+        scan_file_in_chunks(&file, size, config.scan_buffer_size, chunk_size)
+    } else {
+        scan_lines(
+            head.as_slice().chain(file),
+            analysis.encoding,
+            config.scan_buffer_size,
+        )
+    };
+    with_line_scan(analysis, scan)
+}
+
+/// Analyzes content already in memory, such as a blob read from the git index, as the
+/// contents of the file at `path`
+pub fn analyze_bytes(path: impl AsRef<Path>, data: &[u8], config: &ConfigSettings) -> FileAnalysis {
+    analyze_stream(path.as_ref(), data, data.len() as u64, config)
+}
+
+/// Analyzes a stream as the contents of the file at `path` in a single pass. The first
+/// buffer read detects the encoding, binary content and any BOM, and is then scanned ahead
+/// of the rest of the stream.
+fn analyze_stream(
+    path: &Path,
+    mut reader: impl Read,
+    size: u64,
+    config: &ConfigSettings,
+) -> FileAnalysis {
+    let head = match sniff_head(&mut reader) {
+        Ok(head) => head,
+        Err(e) => {
+            return FileAnalysis {
+                error: Some(format!("Failed to check file type: {e}")),
+                ..empty_analysis(path)
+            };
         }
+    };
+    let analysis = analyze_head(path, &head, size, config);
+    if analysis.is_binary {
+        return analysis;
+    }
+
+    // Then scan the lines in the file's own code units
+    let stream = head.as_slice().chain(reader);
+    let scan = scan_lines(stream, analysis.encoding, config.scan_buffer_size);
+    with_line_scan(analysis, scan)
+}
+
+/// Analyzes a file mapped into memory, scanning the mapping directly rather than copying it
+/// through a buffer
+#[cfg(feature = "mmap")]
+fn analyze_mapped(path: &Path, data: &[u8], config: &ConfigSettings) -> FileAnalysis {
+    let head = &data[..data.len().min(BINARY_CHECK_SIZE)];
+    let analysis = analyze_head(path, head, data.len() as u64, config);
+    if analysis.is_binary {
+        return analysis;
+    }
+
+    let scan = scan_slice(data, analysis.encoding, config.scan_buffer_size);
+    with_line_scan(analysis, scan)
+}
+
+/// Starts the analysis of a file from its first bytes, which give its encoding, whether it
+/// is binary and any BOM
+fn analyze_head(path: &Path, head: &[u8], size: u64, config: &ConfigSettings) -> FileAnalysis {
+    // Detect the encoding before the binary check, so UTF-16/32 files are not skipped
+    let (encoding, is_binary) = sniff_bytes(head);
+    if is_binary {
+        return FileAnalysis {
+            size,
+            is_binary: true,
+            ..empty_analysis(path)
+        };
+    }
+
+    // Only detect BOM if check_bom is true. Every BOM fits in the first bytes
+    let bom_type = if config.check_bom {
+        bom_from_bytes(head)
     } else {
         None
     };
+    FileAnalysis {
+        size,
+        bom_checked: config.check_bom,
+        bom_type,
+        encoding,
+        ..empty_analysis(path)
+    }
+}
 
-    // Then count line endings
-    match count_line_endings_in_file(&path) {
-        Ok((lf_count, crlf_count)) => FileAnalysis {
-            path: path.as_ref().to_path_buf(),
-            lf_count,
-            crlf_count,
-            bom_checked: config.check_bom,
-            bom_type,
-            is_binary: false,
-            error: None,
+/// Completes an analysis with the result of scanning its lines
+fn with_line_scan(analysis: FileAnalysis, scan: Result<LineScan>) -> FileAnalysis {
+    match scan {
+        Ok(scan) => FileAnalysis {
+            lf_count: scan.counts.lf,
+            crlf_count: scan.counts.crlf,
+            cr_count: scan.counts.cr,
+            trailing_whitespace_lines: scan.trailing_whitespace_lines,
+            final_newline: scan.final_newline,
+            first_lines: scan.first,
+            ..analysis
         },
         Err(e) => FileAnalysis {
-            path: path.as_ref().to_path_buf(),
-            lf_count: 0,
-            crlf_count: 0,
-            bom_checked: config.check_bom,
-            bom_type,
-            is_binary: false,
             error: Some(e.to_string()),
+            ..analysis
         },
     }
 }
 
-/// Opens a file and counts the line endings
+/// The analysis of a file before anything is known about it
+fn empty_analysis(path: &Path) -> FileAnalysis {
+    FileAnalysis {
+        path: path.to_path_buf(),
+        size: 0,
+        lf_count: 0,
+        crlf_count: 0,
+        cr_count: 0,
+        trailing_whitespace_lines: 0,
+        final_newline: None,
+        first_lines: FirstLineEndings::default(),
+        bom_checked: false,
+        bom_type: None,
+        encoding: TextEncoding::Utf8,
+        is_binary: false,
+        error: None,
+    }
+}
+
+/// Opens a file, detects its encoding and counts the line endings
 ///
 /// # Errors
 ///
 /// Returns an error if the file cannot be opened or read.
-pub fn count_line_endings_in_file(path: impl AsRef<Path>) -> Result<(usize, usize)> {
-    let file = File::open(&path)?;
-    let reader = BufReader::with_capacity(BUFFER_SIZE, file);
-    let (lf_count, crlf_count) = count_line_endings(reader)?;
+pub fn count_line_endings_in_file(path: impl AsRef<Path>) -> Result<LineEndingCounts> {
+    let (encoding, _) = sniff_file(&path)?;
+    count_line_endings_in_file_with_encoding(path, encoding, DEFAULT_SCAN_BUFFER_SIZE)
+}
+
+/// Opens a file and counts the line endings, reading it as the given encoding
+/// `buffer_size` bytes at a time
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be opened or read.
+pub fn count_line_endings_in_file_with_encoding(
+    path: impl AsRef<Path>,
+    encoding: TextEncoding,
+    buffer_size: usize,
+) -> Result<LineEndingCounts> {
+    Ok(scan_file(path, encoding, buffer_size)?.counts)
+}
 
-    Ok((lf_count, crlf_count))
+/// Counts LF, CRLF and lone CR line endings in a reader of single-byte text
+///
+/// # Errors
+///
+/// Returns an error if reading from the reader fails.
+pub fn count_line_endings<R: Read>(reader: BufReader<R>) -> Result<LineEndingCounts> {
+    count_line_endings_with_encoding(reader, TextEncoding::Utf8)
 }
 
-/// Counts LF and Crlf line endings in a reader
+/// Counts LF, CRLF and lone CR line endings in a reader, comparing whole code units
+/// so that `0D 00 0A 00` is a single CRLF in UTF-16 LE
 ///
 /// # Errors
 ///
 /// Returns an error if reading from the reader fails.
-pub fn count_line_endings<R: Read>(mut reader: BufReader<R>) -> Result<(usize, usize)> {
-    let mut buffer = [0u8; BUFFER_SIZE];
-    let mut lf_count = 0;
-    let mut crlf_count = 0;
-    let mut prev_was_cr = false;
+pub fn count_line_endings_with_encoding<R: Read>(
+    reader: BufReader<R>,
+    encoding: TextEncoding,
+) -> Result<LineEndingCounts> {
+    Ok(scan_lines(reader, encoding, DEFAULT_SCAN_BUFFER_SIZE)?.counts)
+}
+
+/// Counts line endings reading `buffer_size` bytes at a time. Single-byte text is searched
+/// for CR and LF with vectorised `memchr`, and wider encodings are decoded unit by unit.
+///
+/// # Errors
+///
+/// Returns an error if reading from the reader fails.
+pub fn count_line_endings_buffered<R: Read>(
+    reader: R,
+    encoding: TextEncoding,
+    buffer_size: usize,
+) -> Result<LineEndingCounts> {
+    Ok(scan_lines(reader, encoding, buffer_size)?.counts)
+}
+
+/// Counts line endings by decoding and checking every code unit, the scalar path that
+/// UTF-16/32 text takes. Single-byte text can take it too, to check the two agree in tests.
+///
+/// # Errors
+///
+/// Returns an error if reading from the reader fails.
+pub fn count_line_endings_scalar<R: Read>(
+    reader: R,
+    encoding: TextEncoding,
+    buffer_size: usize,
+) -> Result<LineEndingCounts> {
+    Ok(scan_units(reader, encoding, buffer_size)?.counts)
+}
+
+/// Finds the line of the first LF, CRLF and lone CR terminator in a file, and of the first
+/// line with trailing whitespace, read as the given encoding `buffer_size` bytes at a time.
+/// Every kind of terminator ends a line, so this matches how editors number lines.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be opened or read.
+pub fn find_first_line_endings(
+    path: impl AsRef<Path>,
+    encoding: TextEncoding,
+    buffer_size: usize,
+) -> Result<FirstLineEndings> {
+    Ok(scan_file(path, encoding, buffer_size)?.first)
+}
+
+/// Everything learned about the lines of a file in one pass
+struct LineScan {
+    counts: LineEndingCounts,
+    first: FirstLineEndings,
+    trailing_whitespace_lines: usize,
+    final_newline: Option<bool>,
+}
+
+fn scan_file(
+    path: impl AsRef<Path>,
+    encoding: TextEncoding,
+    buffer_size: usize,
+) -> Result<LineScan> {
+    let file = File::open(&path)?;
+
+    #[cfg(feature = "mmap")]
+    if let Some(map) = map_large_file(&file, MMAP_THRESHOLD) {
+        return scan_slice(&map, encoding, buffer_size);
+    }
+
+    let size = file.metadata()?.len();
+    if encoding == TextEncoding::Utf8 && size >= PARALLEL_THRESHOLD {
+        return scan_file_in_chunks(&file, size, buffer_size, CHUNK_SIZE);
+    }
+
+    scan_lines(file, encoding, buffer_size)
+}
+
+/// Scans single-byte text in chunks on separate threads, each reading its own part of the
+/// open file
+fn scan_file_in_chunks(
+    file: &File,
+    size: u64,
+    buffer_size: usize,
+    chunk_size: u64,
+) -> Result<LineScan> {
+    let chunks = line_aligned_chunks(&mut &*file, 0, size, chunk_size)?;
+    let scans = chunks
+        .par_iter()
+        .map(|range| scan_bytes(ChunkReader::new(file, range), buffer_size))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(join_line_scans(scans))
+}
+
+/// Joins the scans of consecutive chunks that each start at the start of a line, numbering
+/// the lines of each chunk after those of the chunks before it
+fn join_line_scans(scans: Vec<LineScan>) -> LineScan {
+    let mut joined = LineScan {
+        counts: LineEndingCounts::default(),
+        first: FirstLineEndings::default(),
+        trailing_whitespace_lines: 0,
+        final_newline: None,
+    };
+
+    for scan in scans {
+        let lines_before = joined.counts.lf + joined.counts.crlf + joined.counts.cr;
+        let after = |line: Option<usize>| line.map(|line| line + lines_before);
+        let first = &mut joined.first;
+        first.lf = first.lf.or(after(scan.first.lf));
+        first.crlf = first.crlf.or(after(scan.first.crlf));
+        first.cr = first.cr.or(after(scan.first.cr));
+        first.trailing_whitespace = first
+            .trailing_whitespace
+            .or(after(scan.first.trailing_whitespace));
+
+        joined.counts.lf += scan.counts.lf;
+        joined.counts.crlf += scan.counts.crlf;
+        joined.counts.cr += scan.counts.cr;
+        joined.trailing_whitespace_lines += scan.trailing_whitespace_lines;
+        // Only an empty chunk has no last unit, and then the previous chunk's stands
+        joined.final_newline = scan.final_newline.or(joined.final_newline);
+    }
+    joined
+}
+
+/// Scans content that is already in memory, without copying single-byte text. UTF-16/32
+/// text is decoded through a buffer of `buffer_size` bytes.
+#[cfg(feature = "mmap")]
+fn scan_slice(data: &[u8], encoding: TextEncoding, buffer_size: usize) -> Result<LineScan> {
+    if encoding != TextEncoding::Utf8 {
+        return scan_units(data, encoding, buffer_size);
+    }
+    let scan_range = |range: &std::ops::Range<u64>| -> Result<LineScan> {
+        let start = usize::try_from(range.start)?;
+        let end = usize::try_from(range.end)?;
+        let mut scanner = LineScanner::default();
+        scanner.push_bytes(&data[start..end]);
+        Ok(scanner.finish())
+    };
+
+    let size = data.len() as u64;
+    if size < PARALLEL_THRESHOLD {
+        return scan_range(&(0..size));
+    }
+    let chunks = line_aligned_chunks(&mut io::Cursor::new(data), 0, size, CHUNK_SIZE)?;
+    let scans = chunks
+        .par_iter()
+        .map(scan_range)
+        .collect::<Result<Vec<_>>>()?;
+    Ok(join_line_scans(scans))
+}
+
+/// Scans lines, taking the vectorised path for single-byte text
+fn scan_lines<R: Read>(reader: R, encoding: TextEncoding, buffer_size: usize) -> Result<LineScan> {
+    if encoding == TextEncoding::Utf8 {
+        scan_bytes(reader, buffer_size)
+    } else {
+        scan_units(reader, encoding, buffer_size)
+    }
+}
+
+/// Scans single-byte text, jumping between terminators with `memchr` rather than looking
+/// at every byte
+fn scan_bytes<R: Read>(mut reader: R, buffer_size: usize) -> Result<LineScan> {
+    let mut scanner = LineScanner::default();
+    let mut buffer = vec![0u8; buffer_size];
 
     loop {
-        let n = reader.read(&mut buffer)?;
-        if n == 0 {
-            break;
+        let n = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        scanner.push_bytes(&buffer[..n]);
+    }
+
+    Ok(scanner.finish())
+}
+
+/// Scans text one decoded code unit at a time
+fn scan_units<R: Read>(
+    mut reader: R,
+    encoding: TextEncoding,
+    buffer_size: usize,
+) -> Result<LineScan> {
+    let mut scanner = LineScanner::default();
+
+    for_each_code_unit(
+        &mut reader,
+        encoding.code_unit_width(),
+        buffer_size,
+        |unit| {
+            scanner.push(encoding.decode_unit(unit));
+            Ok(())
+        },
+    )?;
+
+    // Any incomplete trailing code unit cannot be a terminator and is ignored
+    Ok(scanner.finish())
+}
+
+/// Collects line statistics from decoded code units, on top of `TerminatorScanner`
+#[derive(Default)]
+struct LineScanner {
+    terminators: TerminatorScanner,
+    counts: LineEndingCounts,
+    first: FirstLineEndings,
+    /// The last code unit of the current line, before any terminator, is a space or tab
+    last_was_blank: bool,
+    trailing_whitespace_lines: usize,
+    last_unit: Option<u32>,
+}
+
+impl LineScanner {
+    fn push(&mut self, unit: u32) {
+        if let Some(ending) = self.terminators.push(unit) {
+            self.end_line(Some(ending));
+        }
+        if unit != CR && unit != LF {
+            self.last_was_blank = unit == SPACE || unit == TAB;
+        }
+        self.last_unit = Some(unit);
+    }
+
+    /// Pushes single-byte text. Only the terminators, and the bytes either side of them,
+    /// affect the scan, so the runs of other bytes between them are skipped over.
+    fn push_bytes(&mut self, bytes: &[u8]) {
+        let mut start = 0;
+        for terminator in memchr2_iter(b'\n', b'\r', bytes) {
+            self.push_run(&bytes[start..terminator]);
+            self.push(u32::from(bytes[terminator]));
+            start = terminator + 1;
+        }
+        self.push_run(&bytes[start..]);
+    }
+
+    /// Pushes a run of bytes without terminators, which acts like its first and last bytes
+    fn push_run(&mut self, run: &[u8]) {
+        if let [first, .., last] = run {
+            self.push(u32::from(*first));
+            self.push(u32::from(*last));
+        } else if let [only] = run {
+            self.push(u32::from(*only));
+        }
+    }
+
+    /// Ends the current line, with `None` for a last line that has no terminator
+    fn end_line(&mut self, ending: Option<LineEnding>) {
+        let line = self.counts.lf + self.counts.crlf + self.counts.cr + 1;
+
+        if self.last_was_blank {
+            self.trailing_whitespace_lines += 1;
+            self.first.trailing_whitespace.get_or_insert(line);
         }
-        for &b in &buffer[..n] {
-            match b {
-                CR => prev_was_cr = true,
-                LF => {
-                    if prev_was_cr {
-                        crlf_count += 1;
-                    } else {
-                        lf_count += 1;
-                    }
-                    prev_was_cr = false;
-                }
-                _ => prev_was_cr = false,
-            }
+        self.last_was_blank = false;
+
+        if let Some(ending) = ending {
+            self.counts.add(ending);
+            let first = match ending {
+                LineEnding::Lf => &mut self.first.lf,
+                LineEnding::Crlf => &mut self.first.crlf,
+                LineEnding::Cr => &mut self.first.cr,
+            };
+            first.get_or_insert(line);
         }
     }
 
-    Ok((lf_count, crlf_count))
+    fn finish(mut self) -> LineScan {
+        let last = self.terminators.finish();
+        self.end_line(last);
+
+        LineScan {
+            counts: self.counts,
+            first: self.first,
+            trailing_whitespace_lines: self.trailing_whitespace_lines,
+            final_newline: self.last_unit.map(|unit| unit == CR || unit == LF),
+        }
+    }
+}
+
+/// Line terminator state machine, fed one decoded code unit at a time.
+/// A CR is only known to be lone once the following unit arrives, so each terminator
+/// is returned from the call that completes it.
+#[derive(Default)]
+struct TerminatorScanner {
+    prev_was_cr: bool,
+}
+
+impl TerminatorScanner {
+    fn push(&mut self, unit: u32) -> Option<LineEnding> {
+        let was_cr = std::mem::replace(&mut self.prev_was_cr, unit == CR);
+        match unit {
+            LF if was_cr => Some(LineEnding::Crlf),
+            LF => Some(LineEnding::Lf),
+            // The previous CR was not followed by LF
+            _ if was_cr => Some(LineEnding::Cr),
+            _ => None,
+        }
+    }
+
+    fn finish(&self) -> Option<LineEnding> {
+        // A CR as the very last code unit is a lone terminator
+        self.prev_was_cr.then_some(LineEnding::Cr)
+    }
 }
 
 /// Detects BOM (Byte Order Marker) in a file.
@@ -151,20 +584,81 @@ pub fn detect_bom(file_path: impl AsRef<Path>) -> Result<Option<BomType>> {
     // Read up to 4 bytes from the beginning of the file
     let bytes_read = file.read(&mut buffer)?;
 
+    Ok(bom_from_bytes(&buffer[..bytes_read]))
+}
+
+/// Detects a BOM at the start of a byte slice
+#[must_use]
+pub fn bom_from_bytes(buffer: &[u8]) -> Option<BomType> {
     // Check longer BOMs first to avoid false matches (UTF-32 LE starts with UTF-16 LE bytes)
-    if bytes_read >= 4 && buffer[0..4] == UTF32_LE_BOM[..] {
-        return Ok(Some(BomType::Utf32Le));
-    } else if bytes_read >= 4 && buffer[0..4] == UTF32_BE_BOM[..] {
-        return Ok(Some(BomType::Utf32Be));
-    } else if bytes_read >= 3 && buffer[0..3] == UTF8_BOM[..] {
-        return Ok(Some(BomType::Utf8));
-    } else if bytes_read >= 2 && buffer[0..2] == UTF16_LE_BOM[..] {
-        return Ok(Some(BomType::Utf16Le));
-    } else if bytes_read >= 2 && buffer[0..2] == UTF16_BE_BOM[..] {
-        return Ok(Some(BomType::Utf16Be));
+    if buffer.starts_with(UTF32_LE_BOM) {
+        Some(BomType::Utf32Le)
+    } else if buffer.starts_with(UTF32_BE_BOM) {
+        Some(BomType::Utf32Be)
+    } else if buffer.starts_with(UTF8_BOM) {
+        Some(BomType::Utf8)
+    } else if buffer.starts_with(UTF16_LE_BOM) {
+        Some(BomType::Utf16Le)
+    } else if buffer.starts_with(UTF16_BE_BOM) {
+        Some(BomType::Utf16Be)
+    } else {
+        None
+    }
+}
+
+/// Detects the text encoding of a sample from the start of a file.
+/// A BOM is trusted when present; otherwise UTF-16/32 is recognised from the
+/// pattern of null bytes that ASCII-range characters leave in each code unit.
+#[must_use]
+pub fn detect_encoding(buffer: &[u8]) -> TextEncoding {
+    if let Some(bom) = bom_from_bytes(buffer) {
+        return TextEncoding::from(bom);
+    }
+    detect_wide_encoding_without_bom(buffer).unwrap_or(TextEncoding::Utf8)
+}
+
+/// Recognises BOM-less UTF-32 and UTF-16, returning `None` for byte-oriented text
+fn detect_wide_encoding_without_bom(buffer: &[u8]) -> Option<TextEncoding> {
+    // UTF-32: every unit is a valid scalar value and most are in the ASCII range
+    if buffer.len() >= 4 && buffer.len().is_multiple_of(4) {
+        let units = buffer.len() / 4;
+        let is_utf32 = |hi: usize, mid: usize, lo: usize| {
+            let valid = buffer.chunks_exact(4).all(|u| u[hi] == 0 && u[mid] <= 0x10);
+            let ascii = buffer
+                .chunks_exact(4)
+                .filter(|u| u[mid] == 0 && u[lo] == 0)
+                .count();
+            valid && ascii * 2 >= units
+        };
+        if is_utf32(3, 2, 1) && buffer[0] != 0 {
+            return Some(TextEncoding::Utf32Le);
+        }
+        if is_utf32(0, 1, 2) && buffer[3] != 0 {
+            return Some(TextEncoding::Utf32Be);
+        }
     }
 
-    Ok(None)
+    // UTF-16: ASCII-range characters have one null byte per unit, always on the same side
+    let units = buffer.len() / 2;
+    if units == 0 {
+        return None;
+    }
+    let low_byte_only = buffer
+        .chunks_exact(2)
+        .filter(|u| u[0] != 0 && u[1] == 0)
+        .count();
+    let high_byte_only = buffer
+        .chunks_exact(2)
+        .filter(|u| u[0] == 0 && u[1] != 0)
+        .count();
+
+    if low_byte_only * 2 >= units && high_byte_only * 10 < units {
+        Some(TextEncoding::Utf16Le)
+    } else if high_byte_only * 2 >= units && low_byte_only * 10 < units {
+        Some(TextEncoding::Utf16Be)
+    } else {
+        None
+    }
 }
 
 /// Detects if a file is binary by checking for null bytes and non-printable characters
@@ -173,31 +667,90 @@ pub fn detect_bom(file_path: impl AsRef<Path>) -> Result<Option<BomType>> {
 ///
 /// Returns an error if the file cannot be opened or read.
 pub fn is_binary_file(path: impl AsRef<Path>) -> Result<bool> {
+    let (_, is_binary) = sniff_file(path)?;
+    Ok(is_binary)
+}
+
+/// Reads the start of a file to detect its encoding and whether it is binary
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be opened or read.
+pub fn sniff_file(path: impl AsRef<Path>) -> Result<(TextEncoding, bool)> {
     let mut file = File::open(path)?;
-    let mut buffer = vec![0u8; BINARY_CHECK_SIZE];
 
-    let bytes_read = file.read(&mut buffer)?;
-    if bytes_read == 0 {
-        return Ok(false); // Empty file is not binary
+    #[cfg(feature = "mmap")]
+    if let Some(map) = map_large_file(&file, MMAP_THRESHOLD) {
+        return Ok(sniff_bytes(&map[..BINARY_CHECK_SIZE.min(map.len())]));
     }
 
-    let buffer = &buffer[..bytes_read];
+    let (_, encoding, is_binary) = sniff_stream(&mut file)?;
+    Ok((encoding, is_binary))
+}
+
+/// Reads the start of a stream to detect its encoding and whether it is binary. Returns the
+/// bytes read too, which come before the rest of the stream.
+///
+/// # Errors
+///
+/// Returns an error if reading fails.
+pub fn sniff_stream(reader: &mut impl Read) -> io::Result<(Vec<u8>, TextEncoding, bool)> {
+    let buffer = sniff_head(reader)?;
+    let (encoding, is_binary) = sniff_bytes(&buffer);
+    Ok((buffer, encoding, is_binary))
+}
+
+/// Reads the bytes at the start of a stream that are used to detect its encoding
+fn sniff_head(reader: &mut impl Read) -> io::Result<Vec<u8>> {
+    let mut buffer = Vec::with_capacity(BINARY_CHECK_SIZE);
+    reader
+        .take(BINARY_CHECK_SIZE as u64)
+        .read_to_end(&mut buffer)?;
+    Ok(buffer)
+}
+
+/// Detects the encoding of the start of some content, and whether it is binary
+fn sniff_bytes(head: &[u8]) -> (TextEncoding, bool) {
+    let encoding = detect_encoding(head);
+    (encoding, is_binary_data(head, encoding))
+}
 
-    // Check for null bytes (strong indicator of binary)
-    if buffer.contains(&0) {
-        return Ok(true);
+/// Checks a sample of file content for null characters and non-printable characters,
+/// judged per code unit of the given encoding
+#[must_use]
+pub fn is_binary_data(buffer: &[u8], encoding: TextEncoding) -> bool {
+    if buffer.is_empty() {
+        return false; // Empty file is not binary
     }
 
-    // Count non-printable characters (excluding common whitespace)
-    let non_printable_count = buffer.iter().filter(|&&b| !is_text_byte(b)).count();
+    let width = encoding.code_unit_width();
+    let mut units = 0usize;
+    let mut non_printable_count = 0usize;
+
+    for unit in buffer.chunks_exact(width) {
+        let unit = encoding.decode_unit(unit);
+
+        // Check for null characters (strong indicator of binary)
+        if unit == 0 {
+            return true;
+        }
+        if !is_text_unit(unit) {
+            non_printable_count += 1;
+        }
+        units += 1;
+    }
 
     // If more than 30% non-printable, consider it binary
-    let threshold = bytes_read * 30 / 100;
-    Ok(non_printable_count > threshold)
+    let threshold = units * 30 / 100;
+    non_printable_count > threshold
 }
 
-/// Checks if a byte is a typical text character
-fn is_text_byte(b: u8) -> bool {
+/// Checks if a code unit is a typical text character
+fn is_text_unit(unit: u32) -> bool {
     // Printable ASCII (32-126), or common whitespace
-    (32..=126).contains(&b) || b == b'\t' || b == b'\n' || b == b'\r' || b >= 128 // Allow UTF-8
+    (32..=126).contains(&unit)
+        || unit == u32::from(b'\t')
+        || unit == LF
+        || unit == CR
+        || (128..=0x0010_FFFF).contains(&unit) // Allow UTF-8 bytes and non-ASCII characters
 }
@@ -0,0 +1,152 @@
+use crate::types::{
+    ConfigSettings, FileAnalysis, FilePolicy, FirstLineEndings, LineEnding, Violation,
+};
+
+/// Compares an analysed file against a policy and returns every violation found.
+/// Binary files and files that failed analysis have nothing to check and never violate.
+/// Mixed and lone CR line endings are only violations when the policy sets a line ending.
+#[must_use]
+pub fn check_file(result: &FileAnalysis, policy: &FilePolicy) -> Vec<Violation> {
+    let mut violations = Vec::new();
+
+    if result.is_binary || result.error.is_some() {
+        return violations;
+    }
+
+    if policy.line_ending.is_some() && result.has_mixed_line_endings() {
+        violations.push(Violation::MixedLineEndings);
+    }
+
+    if let Some(ending) = policy.line_ending
+        && result.has_line_endings_other_than(ending)
+    {
+        violations.push(Violation::WrongLineEnding(ending));
+    }
+
+    if policy.forbid_bom
+        && let Some(bom) = result.bom_type
+        && bom.is_removable()
+    {
+        violations.push(Violation::UnexpectedBom(bom));
+    }
+
+    if result.cr_count > 0
+        && policy
+            .line_ending
+            .is_some_and(|ending| ending != LineEnding::Cr)
+    {
+        violations.push(Violation::LoneCr);
+    }
+
+    if let Some(encoding) = policy.encoding
+        && result.encoding != encoding
+    {
+        violations.push(Violation::WrongEncoding(encoding));
+    }
+
+    if policy.require_bom && result.bom_checked && result.bom_type.is_none() {
+        violations.push(Violation::MissingBom);
+    }
+
+    match (policy.final_newline, result.final_newline) {
+        (Some(true), Some(false)) => violations.push(Violation::MissingFinalNewline),
+        (Some(false), Some(true)) => violations.push(Violation::UnexpectedFinalNewline),
+        _ => {}
+    }
+
+    if policy.trim_trailing_whitespace && result.trailing_whitespace_lines > 0 {
+        violations.push(Violation::TrailingWhitespace);
+    }
+
+    if let Some(limit) = policy.max_size
+        && result.size > limit
+    {
+        violations.push(Violation::FileTooLarge(limit));
+    }
+
+    violations
+}
+
+/// Returns the 1-based line where a violation first occurs.
+/// For mixed endings this is the first line that differs from the file's first ending,
+/// and final newline problems are on the last line.
+#[must_use]
+pub fn first_offending_line(
+    violation: &Violation,
+    result: &FileAnalysis,
+    first: &FirstLineEndings,
+) -> Option<usize> {
+    let all = [LineEnding::Lf, LineEnding::Crlf, LineEnding::Cr];
+
+    match violation {
+        Violation::MixedLineEndings => {
+            let mut lines: Vec<usize> = all.iter().filter_map(|e| first.line_of(*e)).collect();
+            lines.sort_unstable();
+            lines.get(1).copied()
+        }
+        Violation::WrongLineEnding(expected) => all
+            .iter()
+            .filter(|e| *e != expected)
+            .filter_map(|e| first.line_of(*e))
+            .min(),
+        Violation::LoneCr => first.cr,
+        Violation::TrailingWhitespace => first.trailing_whitespace,
+        Violation::UnexpectedBom(_) | Violation::MissingBom | Violation::WrongEncoding(_) => {
+            Some(1)
+        }
+        // The unterminated last line follows every terminator
+        Violation::MissingFinalNewline => {
+            Some(result.lf_count + result.crlf_count + result.cr_count + 1)
+        }
+        Violation::UnexpectedFinalNewline => {
+            Some(result.lf_count + result.crlf_count + result.cr_count)
+        }
+        // The size belongs to the whole file, not a line
+        Violation::FileTooLarge(_) => None,
+    }
+}
+
+/// Checks every analysed file against the configured policy and prints each file that
+/// fails, without modifying anything. Returns the number of files with violations.
+#[must_use]
+pub fn check_files(config: &ConfigSettings, results: &[FileAnalysis]) -> usize {
+    println!();
+
+    let mut failed_files = 0usize;
+    let mut checked_files = 0usize;
+    let mut not_text_files = 0usize;
+
+    for result in results {
+        if result.is_binary || result.error.is_some() {
+            continue;
+        }
+        let Some(policy) = config.policy_for(&result.path) else {
+            not_text_files += 1;
+            continue;
+        };
+        checked_files += 1;
+
+        let violations = check_file(result, &policy);
+        if !violations.is_empty() {
+            let descriptions: Vec<String> = violations.iter().map(ToString::to_string).collect();
+            println!(
+                "\"{}\"\tcheck failed: {}",
+                result.path.display(),
+                descriptions.join(", ")
+            );
+            failed_files += 1;
+        }
+    }
+
+    if not_text_files > 0 {
+        println!("Skipped {not_text_files} file(s) marked binary or -text in .gitattributes");
+    }
+
+    if failed_files == 0 {
+        println!("Check passed for {checked_files} file(s)");
+    } else {
+        println!("Check failed for {failed_files} of {checked_files} file(s)");
+    }
+
+    failed_files
+}
@@ -0,0 +1,162 @@
+//! Splitting large files of single-byte text into chunks that can be scanned or converted
+//! in parallel. Every chunk ends just after a line terminator, so a CRLF pair is never split
+//! between two chunks and each chunk starts at the start of a line.
+
+use memchr::memchr2;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::ops::Range;
+
+/// Files at least this large are split into chunks that are processed in parallel
+pub const PARALLEL_THRESHOLD: u64 = 128 << 20;
+
+/// Size of the chunks that large files are split into
+pub const CHUNK_SIZE: u64 = 32 << 20;
+
+/// Most chunks, counted in bytes of `chunk_size`, that are converted in memory at once
+pub const CHUNKS_IN_FLIGHT: u64 = 8;
+
+// Bytes read at a time when looking for the end of a line
+const SEARCH_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Splits the bytes from `start` to `end` into ranges of about `chunk_size` bytes, moving
+/// each split forward to just after the next LF, CRLF or lone CR. Lines longer than a chunk
+/// are never split, so they make a longer chunk.
+///
+/// # Errors
+///
+/// Returns an error if seeking or reading fails.
+pub fn line_aligned_chunks<R: Read + Seek>(
+    reader: &mut R,
+    start: u64,
+    end: u64,
+    chunk_size: u64,
+) -> io::Result<Vec<Range<u64>>> {
+    let chunk_size = chunk_size.max(1);
+    let mut chunks = Vec::new();
+    let mut chunk_start = start;
+    let mut search_from = start.saturating_add(chunk_size);
+
+    while search_from < end {
+        let search_end = search_from.saturating_add(chunk_size).min(end);
+        match next_line_start(reader, search_from, search_end)? {
+            Some(split) if split < end => {
+                chunks.push(chunk_start..split);
+                chunk_start = split;
+                search_from = split.saturating_add(chunk_size);
+            }
+            // Only the last line ending of the content is left
+            Some(_) => break,
+            // No line ends here, so keep looking further along
+            None => search_from = search_end,
+        }
+    }
+
+    chunks.push(chunk_start..end);
+    Ok(chunks)
+}
+
+/// Finds the first line terminator that starts from `from` up to `to`, returning the
+/// offset just after it. The LF of a CRLF whose CR is just before `to` is included.
+fn next_line_start<R: Read + Seek>(reader: &mut R, from: u64, to: u64) -> io::Result<Option<u64>> {
+    reader.seek(SeekFrom::Start(from))?;
+    let mut buffer = vec![0u8; SEARCH_BUFFER_SIZE];
+    let mut offset = from;
+
+    while offset < to {
+        let n = match reader.read(&mut buffer) {
+            Ok(0) => return Ok(None),
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        let Some(index) = memchr2(b'\n', b'\r', &buffer[..n]) else {
+            offset += n as u64;
+            continue;
+        };
+
+        let terminator = offset + index as u64;
+        if terminator >= to {
+            return Ok(None);
+        }
+        if buffer[index] == b'\n' {
+            return Ok(Some(terminator + 1));
+        }
+
+        // A CR is one terminator with an LF that follows it
+        let next = if let Some(&byte) = buffer[..n].get(index + 1) {
+            Some(byte)
+        } else {
+            let mut byte = [0u8];
+            (reader.read(&mut byte)? == 1).then_some(byte[0])
+        };
+        return Ok(Some(terminator + 1 + u64::from(next == Some(b'\n'))));
+    }
+    Ok(None)
+}
+
+/// Groups consecutive chunks into batches of at most `max_bytes` bytes, so that only one
+/// batch is held in memory at a time. A chunk larger than `max_bytes` is a batch of its own.
+#[must_use]
+pub fn batch_chunks(chunks: &[Range<u64>], max_bytes: u64) -> Vec<&[Range<u64>]> {
+    let mut batches = Vec::new();
+    let mut batch_start = 0;
+    let mut batch_bytes = 0u64;
+
+    for (index, range) in chunks.iter().enumerate() {
+        let len = range.end - range.start;
+        if index > batch_start && batch_bytes + len > max_bytes {
+            batches.push(&chunks[batch_start..index]);
+            batch_start = index;
+            batch_bytes = 0;
+        }
+        batch_bytes += len;
+    }
+    if batch_start < chunks.len() {
+        batches.push(&chunks[batch_start..]);
+    }
+    batches
+}
+
+/// Reads one chunk of an open file with positional reads, so that chunks of the same file
+/// can be read on separate threads without opening it again or sharing its cursor
+pub struct ChunkReader<'a> {
+    file: &'a File,
+    offset: u64,
+    end: u64,
+}
+
+impl<'a> ChunkReader<'a> {
+    /// Creates a reader of the bytes of `file` in `range`
+    #[must_use]
+    pub fn new(file: &'a File, range: &Range<u64>) -> Self {
+        Self {
+            file,
+            offset: range.start,
+            end: range.end,
+        }
+    }
+}
+
+impl Read for ChunkReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = usize::try_from(self.end.saturating_sub(self.offset)).unwrap_or(usize::MAX);
+        let len = buf.len().min(remaining);
+        if len == 0 {
+            return Ok(0);
+        }
+        let n = read_at(self.file, &mut buf[..len], self.offset)?;
+        self.offset += n as u64;
+        Ok(n)
+    }
+}
+
+#[cfg(unix)]
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    std::os::unix::fs::FileExt::read_at(file, buf, offset)
+}
+
+#[cfg(windows)]
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    std::os::windows::fs::FileExt::seek_read(file, buf, offset)
+}
@@ -1,38 +1,405 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
 use pico_args::Arguments;
 
-use crate::types::{ConfigSettings, LineEndingTarget};
+use crate::analysis::DEFAULT_SCAN_BUFFER_SIZE;
+use crate::editorconfig::EditorConfig;
+use crate::gitattributes::GitAttributes;
+use crate::project_config::{PROJECT_CONFIG, ProjectConfig, parse_size};
+use crate::types::{ConfigSettings, FileSource, HookSettings, LineEndingTarget, OutputFormat};
+
+// Limits for --buffer-size
+const MIN_BUFFER_SIZE: u64 = 1 << 10;
+const MAX_BUFFER_SIZE: u64 = 1 << 30;
 
 /// Parses command line arguments and returns configuration settings.
 ///
 /// # Errors
 ///
 /// Returns an error if invalid arguments are provided or conflicting options are specified.
-pub fn parse_args(mut args: Arguments) -> Result<ConfigSettings> {
+pub fn parse_args(args: Arguments) -> Result<ConfigSettings> {
+    parse_settings(args, Command::Analyze)
+}
+
+/// The command that options are parsed for
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Command {
+    Analyze,
+    Hook,
+    Filter,
+}
+
+/// Parses the arguments of `hook run`. These are the usual options followed by file names,
+/// which are taken literally. Without file names the staged files are checked.
+///
+/// # Errors
+///
+/// Returns an error if invalid arguments are provided or conflicting options are specified.
+pub fn parse_hook_args(mut args: Arguments) -> Result<HookSettings> {
+    let restage = args.contains("--restage");
+    let autofix = restage || args.contains("--autofix");
+
+    let mut config = parse_settings(args, Command::Hook)?;
+    if config.format != OutputFormat::Text {
+        return Err(anyhow::anyhow!("hook run only writes text output"));
+    }
+
+    // The rules in .gitattributes, .editorconfig and .line-endings.toml are always checked, so
+    // fixing should apply them too
+    config.fix = autofix
+        && (config.project.is_some()
+            || config.gitattributes.is_some()
+            || config.editorconfig.is_some());
+
+    Ok(HookSettings {
+        config,
+        autofix,
+        restage,
+    })
+}
+
+/// Parses the arguments of `filter`. These are the usual options, which set the line endings
+/// and BOMs that files are checked out with. Git supplies the files.
+///
+/// # Errors
+///
+/// Returns an error if invalid arguments are provided or conflicting options are specified.
+pub fn parse_filter_args(args: Arguments) -> Result<ConfigSettings> {
+    let mut config = parse_settings(args, Command::Filter)?;
+    if config.check {
+        return Err(anyhow::anyhow!("Cannot use --check with filter"));
+    }
+
+    // BOMs are always removed when cleaning, so they must be detected
+    config.check_bom = true;
+    Ok(config)
+}
+
+/// Parses the options shared by the main command, `hook run` and `filter`. Hooks always
+/// check, and take their file names literally.
+#[allow(clippy::too_many_lines)] // one flat list of every option reads better than split up
+fn parse_settings(mut args: Arguments, command: Command) -> Result<ConfigSettings> {
     // Parse flags
     let case_sensitive = args.contains(["-c", "--case-sensitive"]);
     let set_linux = args.contains(["-l", "--linux-line-endings"]);
     let set_windows = args.contains(["-w", "--windows-line-endings"]);
+    let set_mac = args.contains("--mac-line-endings");
     let check_bom = args.contains(["-b", "--bom"]);
     let remove_bom = args.contains(["-m", "--remove-bom"]);
     let recursive = args.contains(["-r", "--recursive"]);
     let no_trash = args.contains(["-n", "--no-trash"]);
+    let check_flag = args.contains("--check");
+    let check = check_flag || command == Command::Hook;
+    let use_gitattributes = args.contains("--gitattributes");
+    let use_editorconfig = args.contains("--editorconfig");
+    let fix = args.contains("--fix");
+    let no_config = args.contains("--no-config");
+    let no_ignore = args.contains("--no-ignore");
+    let null_separated = args.contains(["-0", "--null"]);
+    let git_tracked = args.contains("--git-tracked");
+    let git_staged = args.contains("--git-staged");
+    let stdin = args.contains("--stdin");
+    let diff = args.contains("--diff");
+    // Both are read, as `--diff` implies `--dry-run` but may be given with it
+    let dry_run_flag = args.contains("--dry-run");
+    let dry_run = diff || dry_run_flag;
 
     let folder: Option<String> = args.opt_value_from_str(["-f", "--folder"])?;
+    let format: OutputFormat = args.opt_value_from_str("--format")?.unwrap_or_default();
+    let config_path: Option<String> = args.opt_value_from_str("--config")?;
+    let exclude_patterns: Vec<String> = args.values_from_str("--exclude")?;
+    let files_from: Option<String> = args.opt_value_from_str("--files-from")?;
+    let git_changed: Option<String> = args.opt_value_from_str("--git-changed")?;
+    let stdin_path: Option<String> = args.opt_value_from_str("--stdin-path")?;
+    let buffer_size: Option<String> = args.opt_value_from_str("--buffer-size")?;
+
+    let line_ending_target = line_ending_target_from_flags(set_linux, set_windows, set_mac)?;
+    let scan_buffer_size = parse_buffer_size(buffer_size.as_deref())?;
 
-    // Convert boolean flags to LineEndingTarget enum
-    let line_ending_target = match (set_linux, set_windows) {
-        (true, true) => {
+    if fix && check_flag {
+        return Err(anyhow::anyhow!("Cannot use --fix together with --check"));
+    }
+    if no_config && config_path.is_some() {
+        return Err(anyhow::anyhow!(
+            "Cannot use --config together with --no-config"
+        ));
+    }
+
+    let fixing = !check && (line_ending_target != LineEndingTarget::None || remove_bom || fix);
+
+    // Machine readable output would be interleaved with progress text from the fixes
+    if format != OutputFormat::Text && fixing {
+        return Err(anyhow::anyhow!(
+            "--format {format} can only be used for analysis or with --check, not when fixing files"
+        ));
+    }
+
+    let mut file_paths = collect_file_paths(args)?;
+    let source = source_from_flags(git_tracked, git_staged, git_changed, stdin, stdin_path)?;
+    let file_source = select_file_source(files_from, null_separated, source, &file_paths, command)?;
+    check_source_allows_fixing(&file_source, fixing)?;
+    check_dry_run_allowed(dry_run, &file_source, fixing, command)?;
+
+    // Attribute patterns are relative to the repository containing the search folder
+    let gitattributes = if use_gitattributes {
+        let start = folder.as_deref().unwrap_or(".");
+        Some(GitAttributes::discover(start).with_context(|| "Failed to load .gitattributes")?)
+    } else {
+        None
+    };
+
+    let exclude = build_exclude(&exclude_patterns, folder.as_deref())?;
+    let project = load_project_config(config_path.as_deref(), no_config, folder.as_deref())?;
+
+    if fix && project.is_none() && gitattributes.is_none() && !use_editorconfig {
+        return Err(anyhow::anyhow!(
+            "--fix applies the rules from --gitattributes, --editorconfig or {PROJECT_CONFIG}, but none were found"
+        ));
+    }
+
+    // Without a file pattern, search everything below the folder for the included files
+    let defaults_to_include_globs = file_source == FileSource::Patterns
+        && file_paths.is_empty()
+        && project
+            .as_ref()
+            .is_some_and(ProjectConfig::has_include_globs);
+    if defaults_to_include_globs {
+        file_paths.push("*".to_string());
+    }
+
+    // BOMs are needed to remove or require them
+    let check_bom = check_bom
+        || remove_bom
+        || use_editorconfig
+        || project.as_ref().is_some_and(ProjectConfig::has_bom_rules);
+
+    Ok(ConfigSettings {
+        case_sensitive,
+        line_ending_target,
+        check_bom,
+        remove_bom,
+        recursive: recursive || defaults_to_include_globs,
+        no_trash,
+        check,
+        format,
+        gitattributes,
+        editorconfig: use_editorconfig.then(EditorConfig::new),
+        project,
+        fix,
+        dry_run,
+        diff,
+        exclude,
+        use_ignore_files: !no_ignore,
+        file_source,
+        scan_buffer_size,
+        supplied_paths: file_paths,
+        folder,
+    })
+}
+
+/// Picks where the files to process come from, rejecting options that don't go together.
+/// Hooks take file names literally, and check the staged files when there are none. Filters
+/// are given their files by git.
+fn select_file_source(
+    files_from: Option<String>,
+    null_separated: bool,
+    source: Option<FileSource>,
+    file_paths: &[String],
+    command: Command,
+) -> Result<FileSource> {
+    if command == Command::Filter
+        && (files_from.is_some() || source.is_some() || !file_paths.is_empty())
+    {
+        return Err(anyhow::anyhow!(
+            "filter is given its files by git, so it cannot take file names or select files"
+        ));
+    }
+    let hook = command == Command::Hook;
+
+    if null_separated && files_from.is_none() {
+        return Err(anyhow::anyhow!(
+            "-0/--null can only be used with --files-from"
+        ));
+    }
+
+    match (files_from, source) {
+        (Some(_), Some(source)) => Err(anyhow::anyhow!(
+            "Cannot use --files-from together with {}",
+            option_name(&source)
+        )),
+        (Some(_), None) if !file_paths.is_empty() => Err(anyhow::anyhow!(
+            "Cannot use file patterns together with --files-from"
+        )),
+        (None, Some(source)) if !file_paths.is_empty() => Err(anyhow::anyhow!(
+            "Cannot use file patterns together with {}",
+            option_name(&source)
+        )),
+        (Some(path), None) => Ok(FileSource::List {
+            path,
+            null_separated,
+        }),
+        (None, Some(source)) => Ok(source),
+        (None, None) if hook && file_paths.is_empty() => Ok(FileSource::GitStaged),
+        (None, None) if hook => Ok(FileSource::Files(file_paths.to_vec())),
+        (None, None) => Ok(FileSource::Patterns),
+    }
+}
+
+/// Converts the git selection and `--stdin` options to a `FileSource`, allowing at most one
+fn source_from_flags(
+    git_tracked: bool,
+    git_staged: bool,
+    git_changed: Option<String>,
+    stdin: bool,
+    stdin_path: Option<String>,
+) -> Result<Option<FileSource>> {
+    if stdin_path.is_some() && !stdin {
+        return Err(anyhow::anyhow!(
+            "--stdin-path can only be used with --stdin"
+        ));
+    }
+
+    let mut sources = Vec::new();
+    if git_tracked {
+        sources.push(FileSource::GitTracked);
+    }
+    if git_staged {
+        sources.push(FileSource::GitStaged);
+    }
+    if let Some(reference) = git_changed {
+        sources.push(FileSource::GitChanged(reference));
+    }
+    if stdin {
+        sources.push(FileSource::Stdin { path: stdin_path });
+    }
+
+    if sources.len() > 1 {
+        return Err(anyhow::anyhow!(
+            "Only one of --git-tracked, --git-staged, --git-changed and --stdin can be used"
+        ));
+    }
+    Ok(sources.pop())
+}
+
+/// Name of the option that selected a file source, for error messages
+fn option_name(source: &FileSource) -> &'static str {
+    match source {
+        FileSource::Patterns | FileSource::Files(_) => "file patterns",
+        FileSource::List { .. } => "--files-from",
+        FileSource::GitTracked => "--git-tracked",
+        FileSource::GitStaged => "--git-staged",
+        FileSource::GitChanged(_) => "--git-changed",
+        FileSource::Stdin { .. } => "--stdin",
+    }
+}
+
+/// Rejects fixes for staged content, which would rewrite the working tree rather than what
+/// was checked, and requires them for `--stdin`, which only converts
+fn check_source_allows_fixing(file_source: &FileSource, fixing: bool) -> Result<()> {
+    match file_source {
+        FileSource::GitStaged if fixing => Err(anyhow::anyhow!(
+            "--git-staged checks the index and cannot be used when fixing files"
+        )),
+        FileSource::Stdin { .. } if !fixing => Err(anyhow::anyhow!(
+            "--stdin converts content, so it needs a fix option such as -l, -w or -m and cannot be used with --check"
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Requires a fix to preview for `--dry-run` and `--diff`, which only apply to the main
+/// command reading files
+fn check_dry_run_allowed(
+    dry_run: bool,
+    file_source: &FileSource,
+    fixing: bool,
+    command: Command,
+) -> Result<()> {
+    if !dry_run {
+        return Ok(());
+    }
+    if command != Command::Analyze || !fixing {
+        return Err(anyhow::anyhow!(
+            "--dry-run and --diff preview fixes, so they need a fix option such as -l, -w, -m or --fix and cannot be used with --check, hook or filter"
+        ));
+    }
+    if matches!(file_source, FileSource::Stdin { .. }) {
+        return Err(anyhow::anyhow!(
+            "--stdin writes to stdout, so it cannot be used with --dry-run or --diff"
+        ));
+    }
+    Ok(())
+}
+
+/// Parses `--buffer-size`, such as `256KB` or `4MB`, which must be from 1 KB to 1 GB
+fn parse_buffer_size(text: Option<&str>) -> Result<usize> {
+    let Some(text) = text else {
+        return Ok(DEFAULT_SCAN_BUFFER_SIZE);
+    };
+    let size = parse_size(text).with_context(|| "Invalid --buffer-size")?;
+    if !(MIN_BUFFER_SIZE..=MAX_BUFFER_SIZE).contains(&size) {
+        return Err(anyhow::anyhow!(
+            "--buffer-size must be from 1 KB to 1 GB, not {text}"
+        ));
+    }
+    usize::try_from(size).with_context(|| "--buffer-size is too large for this platform")
+}
+
+/// Loads the named project configuration file, or the nearest `.line-endings.toml` above
+/// the search folder unless `--no-config` was given
+fn load_project_config(
+    config_path: Option<&str>,
+    no_config: bool,
+    folder: Option<&str>,
+) -> Result<Option<ProjectConfig>> {
+    if let Some(path) = config_path {
+        return ProjectConfig::load(path).map(Some);
+    }
+    if no_config {
+        return Ok(None);
+    }
+    ProjectConfig::discover(folder.unwrap_or("."))
+        .with_context(|| format!("Failed to load {PROJECT_CONFIG}"))
+}
+
+/// Converts the line ending flags to a `LineEndingTarget`, rejecting conflicting flags
+fn line_ending_target_from_flags(
+    set_linux: bool,
+    set_windows: bool,
+    set_mac: bool,
+) -> Result<LineEndingTarget> {
+    let target = match (set_linux, set_windows, set_mac) {
+        (true, true, _) => {
             return Err(anyhow::anyhow!(
                 "Cannot set both Linux and Windows line endings at the same time"
             ));
         }
-        (true, false) => LineEndingTarget::Linux,
-        (false, true) => LineEndingTarget::Windows,
-        (false, false) => LineEndingTarget::None,
+        (true, false, true) | (false, true, true) => {
+            return Err(anyhow::anyhow!(
+                "Cannot set Mac line endings together with Linux or Windows line endings"
+            ));
+        }
+        (true, false, false) => LineEndingTarget::Linux,
+        (false, true, false) => LineEndingTarget::Windows,
+        (false, false, true) => LineEndingTarget::Mac,
+        (false, false, false) => LineEndingTarget::None,
     };
+    Ok(target)
+}
+
+/// Compiles the `--exclude` globs, which are relative to the search folder
+fn build_exclude(patterns: &[String], folder: Option<&str>) -> Result<Gitignore> {
+    let mut builder = GitignoreBuilder::new(folder.unwrap_or("."));
+    for pattern in patterns {
+        builder
+            .add_line(None, pattern)
+            .with_context(|| format!("Invalid --exclude pattern '{pattern}'"))?;
+    }
+    Ok(builder.build()?)
+}
 
-    // Get all file paths from command line
+/// Collects the file patterns left after the options, rejecting unrecognized switches
+fn collect_file_paths(mut args: Arguments) -> Result<Vec<String>> {
     let mut file_paths = Vec::new();
     let mut unrecognized_switches = Vec::new();
 
@@ -60,14 +427,5 @@ pub fn parse_args(mut args: Arguments) -> Result<ConfigSettings> {
         )));
     }
 
-    Ok(ConfigSettings {
-        case_sensitive,
-        line_ending_target,
-        check_bom: check_bom || remove_bom, // need to check BOM if removing it
-        remove_bom,
-        recursive,
-        no_trash,
-        supplied_paths: file_paths,
-        folder,
-    })
+    Ok(file_paths)
 }
@@ -0,0 +1,543 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use crate::types::{LineEnding, TextEncoding};
+use crate::utils::normalize_path;
+
+const EDITORCONFIG: &str = ".editorconfig";
+
+/// Value of the `charset` property
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Charset {
+    Latin1,
+    Utf8,
+    Utf8Bom, // UTF-8 that must start with a BOM
+    Utf16Be,
+    Utf16Le,
+}
+
+impl Charset {
+    /// Encoding the file's code units are in. Latin-1 is single-byte like UTF-8.
+    #[must_use]
+    pub fn encoding(self) -> TextEncoding {
+        match self {
+            Charset::Latin1 | Charset::Utf8 | Charset::Utf8Bom => TextEncoding::Utf8,
+            Charset::Utf16Be => TextEncoding::Utf16Be,
+            Charset::Utf16Le => TextEncoding::Utf16Le,
+        }
+    }
+}
+
+/// The properties this tool enforces, resolved for one file. `None` means not set.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct EditorConfigProperties {
+    pub end_of_line: Option<LineEnding>,
+    pub charset: Option<Charset>,
+    pub insert_final_newline: Option<bool>,
+    pub trim_trailing_whitespace: Option<bool>,
+}
+
+/// One `[glob]` section and its `key = value` pairs, with keys lowercased
+struct Section {
+    glob: Glob,
+    properties: Vec<(String, String)>,
+}
+
+/// A parsed `.editorconfig` file
+#[derive(Default)]
+struct EditorConfigFile {
+    /// `root = true` in the preamble stops the search for files in parent directories
+    is_root: bool,
+    sections: Vec<Section>,
+}
+
+/// Resolves `.editorconfig` properties for paths. For each file, the `.editorconfig` in
+/// its directory and every parent is read, up to one marked `root = true`, with closer files
+/// and later sections taking precedence. Parsed files are cached by directory.
+#[derive(Default)]
+pub struct EditorConfig {
+    files: Mutex<HashMap<PathBuf, Arc<EditorConfigFile>>>,
+}
+
+impl EditorConfig {
+    #[must_use]
+    pub fn new() -> Self {
+        EditorConfig::default()
+    }
+
+    /// Resolves the properties that apply to a path
+    #[must_use]
+    pub fn properties_for(&self, path: impl AsRef<Path>) -> EditorConfigProperties {
+        let Ok(path) = std::path::absolute(path.as_ref()) else {
+            return EditorConfigProperties::default();
+        };
+        // Without `..` components, so the ancestors are the file's real parent directories
+        let path = normalize_path(&path);
+
+        // Nearest directory first, stopping after a root file
+        let mut applicable = Vec::new();
+        for directory in path.ancestors().skip(1) {
+            let file = self.file_for_directory(directory);
+            let is_root = file.is_root;
+            applicable.push((directory, file));
+            if is_root {
+                break;
+            }
+        }
+
+        // Apply the furthest file first, so closer files override it
+        let mut values: HashMap<String, String> = HashMap::new();
+        for (directory, file) in applicable.iter().rev() {
+            let Some(relative_path) = relative_with_slashes(&path, directory) else {
+                continue;
+            };
+            for section in &file.sections {
+                if section.glob.matches(&relative_path) {
+                    for (key, value) in &section.properties {
+                        values.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+        }
+
+        properties_from_values(&values)
+    }
+
+    /// Parsed `.editorconfig` of a directory, read and cached on first use.
+    /// A missing or unreadable file has no sections.
+    fn file_for_directory(&self, directory: &Path) -> Arc<EditorConfigFile> {
+        let cached = self
+            .files
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(directory)
+            .cloned();
+        if let Some(file) = cached {
+            return file;
+        }
+
+        let parsed = std::fs::read_to_string(directory.join(EDITORCONFIG))
+            .map(|contents| parse_editorconfig(&contents))
+            .unwrap_or_default();
+
+        self.files
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(directory.to_path_buf())
+            .or_insert_with(|| Arc::new(parsed))
+            .clone()
+    }
+}
+
+/// Path relative to a directory with `/` separators
+pub(crate) fn relative_with_slashes(path: &Path, directory: &Path) -> Option<String> {
+    let relative = path.strip_prefix(directory).ok()?;
+    let parts: Vec<String> = relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    Some(parts.join("/"))
+}
+
+/// Turns raw property values into typed properties. Unknown values and `unset` leave a
+/// property unset, as editors do.
+fn properties_from_values(values: &HashMap<String, String>) -> EditorConfigProperties {
+    let get = |key: &str| values.get(key).map(String::as_str);
+
+    let boolean = |key: &str| match get(key) {
+        Some("true") => Some(true),
+        Some("false") => Some(false),
+        _ => None,
+    };
+
+    EditorConfigProperties {
+        end_of_line: match get("end_of_line") {
+            Some("lf") => Some(LineEnding::Lf),
+            Some("crlf") => Some(LineEnding::Crlf),
+            Some("cr") => Some(LineEnding::Cr),
+            _ => None,
+        },
+        charset: match get("charset") {
+            Some("latin1") => Some(Charset::Latin1),
+            Some("utf-8") => Some(Charset::Utf8),
+            Some("utf-8-bom") => Some(Charset::Utf8Bom),
+            Some("utf-16be") => Some(Charset::Utf16Be),
+            Some("utf-16le") => Some(Charset::Utf16Le),
+            _ => None,
+        },
+        insert_final_newline: boolean("insert_final_newline"),
+        trim_trailing_whitespace: boolean("trim_trailing_whitespace"),
+    }
+}
+
+/// Parses the INI-style contents of an `.editorconfig` file. Keys and the values of
+/// known properties are case-insensitive, so both are lowercased.
+fn parse_editorconfig(contents: &str) -> EditorConfigFile {
+    let mut file = EditorConfigFile::default();
+    let mut current: Option<Section> = None;
+
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+
+        if let Some(header) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            if let Some(section) = current.take() {
+                file.sections.push(section);
+            }
+            current = Some(Section {
+                glob: Glob::new(header),
+                properties: Vec::new(),
+            });
+            continue;
+        }
+
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let key = key.trim().to_lowercase();
+        let value = value.trim().to_lowercase();
+
+        match current.as_mut() {
+            Some(section) => section.properties.push((key, value)),
+            None => {
+                if key == "root" {
+                    file.is_root = value == "true";
+                }
+            }
+        }
+    }
+
+    if let Some(section) = current {
+        file.sections.push(section);
+    }
+    file
+}
+
+/// One element of a section glob
+#[derive(Debug, Clone)]
+enum Token {
+    Literal(char),
+    AnyChar,        // `?`, any character except `/`
+    Star,           // `*`, any run of characters except `/`
+    DoubleStar,     // `**`, any run of characters
+    AnyDirectories, // `**/`, nothing or any run of characters ending in `/`
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+    Alternatives(Vec<Vec<Token>>), // `{a,b}`
+    NumberRange(i64, i64),         // `{1..10}`
+}
+
+/// A section glob, with the `.editorconfig` syntax: `*`, `**`, `?`, `[...]`, `[!...]`,
+/// `{a,b}` and `{num..num}`. Also used for the globs in `.line-endings.toml`.
+#[derive(Debug, Clone)]
+pub(crate) struct Glob {
+    tokens: Vec<Token>,
+}
+
+impl Glob {
+    /// Builds a glob from a section header or pattern. A glob without `/` matches the file
+    /// name at any depth, otherwise it is relative to the directory of the file defining it.
+    pub(crate) fn new(header: &str) -> Glob {
+        let anchored = if header.contains('/') {
+            header.strip_prefix('/').unwrap_or(header).to_string()
+        } else {
+            format!("**/{header}")
+        };
+        let chars: Vec<char> = anchored.chars().collect();
+        Glob {
+            tokens: parse_tokens(&chars, &mut 0, false),
+        }
+    }
+
+    pub(crate) fn matches(&self, path: &str) -> bool {
+        let chars: Vec<char> = path.chars().collect();
+        match_tokens(&self.tokens, &chars)
+    }
+}
+
+/// Parses glob characters from `*position`. Inside braces parsing stops at an unnested
+/// `,` or `}`, which is left for the caller.
+fn parse_tokens(chars: &[char], position: &mut usize, in_braces: bool) -> Vec<Token> {
+    let mut tokens = Vec::new();
+
+    while *position < chars.len() {
+        let c = chars[*position];
+        match c {
+            ',' | '}' if in_braces => break,
+            '\\' if *position + 1 < chars.len() => {
+                tokens.push(Token::Literal(chars[*position + 1]));
+                *position += 2;
+            }
+            '?' => {
+                tokens.push(Token::AnyChar);
+                *position += 1;
+            }
+            '*' => {
+                if chars.get(*position + 1) == Some(&'*') {
+                    if chars.get(*position + 2) == Some(&'/') {
+                        tokens.push(Token::AnyDirectories);
+                        *position += 3;
+                    } else {
+                        tokens.push(Token::DoubleStar);
+                        *position += 2;
+                    }
+                } else {
+                    tokens.push(Token::Star);
+                    *position += 1;
+                }
+            }
+            '[' | '{' => {
+                let parsed = if c == '[' {
+                    parse_class(chars, *position)
+                } else {
+                    parse_braces(chars, *position)
+                };
+                if let Some((token, next)) = parsed {
+                    tokens.push(token);
+                    *position = next;
+                } else {
+                    // Unterminated, so it is a literal character
+                    tokens.push(Token::Literal(c));
+                    *position += 1;
+                }
+            }
+            _ => {
+                tokens.push(Token::Literal(c));
+                *position += 1;
+            }
+        }
+    }
+
+    tokens
+}
+
+/// Parses `[...]` starting at `start`, returning the token and the position after `]`
+fn parse_class(chars: &[char], start: usize) -> Option<(Token, usize)> {
+    let mut position = start + 1;
+    let negated = matches!(chars.get(position), Some('!' | '^'));
+    if negated {
+        position += 1;
+    }
+
+    let mut ranges = Vec::new();
+    let mut first = true;
+    while let Some(&c) = chars.get(position) {
+        if c == ']' && !first {
+            return Some((Token::Class { negated, ranges }, position + 1));
+        }
+        if c == '/' {
+            return None; // a class never spans a path separator
+        }
+        if chars.get(position + 1) == Some(&'-')
+            && let Some(&end) = chars.get(position + 2)
+            && end != ']'
+        {
+            ranges.push((c, end));
+            position += 3;
+        } else {
+            ranges.push((c, c));
+            position += 1;
+        }
+        first = false;
+    }
+    None
+}
+
+/// Parses `{...}` starting at `start`, as a number range or comma separated alternatives.
+/// Braces without a comma are literal, as in other `.editorconfig` implementations.
+fn parse_braces(chars: &[char], start: usize) -> Option<(Token, usize)> {
+    // Find the matching close brace
+    let mut depth = 0usize;
+    let mut end = None;
+    let mut index = start;
+    while index < chars.len() {
+        match chars[index] {
+            '\\' => index += 1,
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    end = Some(index);
+                    break;
+                }
+            }
+            _ => {}
+        }
+        index += 1;
+    }
+    let end = end?;
+
+    let inner: String = chars[start + 1..end].iter().collect();
+    if let Some((low, high)) = inner.split_once("..")
+        && let (Ok(low), Ok(high)) = (low.parse::<i64>(), high.parse::<i64>())
+    {
+        return Some((Token::NumberRange(low.min(high), low.max(high)), end + 1));
+    }
+
+    let mut alternatives = Vec::new();
+    let mut position = start + 1;
+    loop {
+        alternatives.push(parse_tokens(chars, &mut position, true));
+        match chars.get(position) {
+            Some(',') => position += 1,
+            _ => break, // the closing brace
+        }
+    }
+
+    if alternatives.len() < 2 {
+        return None;
+    }
+    Some((Token::Alternatives(alternatives), end + 1))
+}
+
+/// Matches tokens against the whole of `text`
+fn match_tokens(tokens: &[Token], text: &[char]) -> bool {
+    let mut starts = vec![false; text.len() + 1];
+    starts[0] = true;
+    match_positions(tokens, text, &starts)[text.len()]
+}
+
+/// Returns the positions in `text` where `tokens` can stop matching when they start at any
+/// of the positions set in `starts`. Each token moves the whole set of positions along at
+/// once, rather than backtracking, so matching takes polynomial time for any pattern.
+fn match_positions(tokens: &[Token], text: &[char], starts: &[bool]) -> Vec<bool> {
+    let mut positions = starts.to_vec();
+    for token in tokens {
+        let mut next = vec![false; text.len() + 1];
+        if let Token::Alternatives(alternatives) = token {
+            for alternative in alternatives {
+                let ends = match_positions(alternative, text, &positions);
+                for (next, end) in next.iter_mut().zip(ends) {
+                    *next |= end;
+                }
+            }
+        } else {
+            for start in (0..=text.len()).filter(|&start| positions[start]) {
+                advance_token(token, text, start, &mut next);
+            }
+        }
+        if !next.contains(&true) {
+            return next;
+        }
+        positions = next;
+    }
+    positions
+}
+
+/// Sets a run of positions that a wildcard can stop at. A set position means an earlier
+/// start has already set the rest of the run.
+fn set_run(run: &mut [bool]) {
+    for position in run {
+        if std::mem::replace(position, true) {
+            break;
+        }
+    }
+}
+
+/// Sets the positions in `next` where `token` can stop matching when it starts at `start`
+fn advance_token(token: &Token, text: &[char], start: usize, next: &mut [bool]) {
+    let rest = &text[start..];
+    match token {
+        Token::Literal(c) => {
+            if rest.first() == Some(c) {
+                next[start + 1] = true;
+            }
+        }
+        Token::AnyChar => {
+            if rest.first().is_some_and(|c| *c != '/') {
+                next[start + 1] = true;
+            }
+        }
+        Token::Star => {
+            let limit = rest.iter().position(|c| *c == '/').unwrap_or(rest.len());
+            set_run(&mut next[start..=start + limit]);
+        }
+        Token::DoubleStar => {
+            set_run(&mut next[start..]);
+        }
+        Token::AnyDirectories => {
+            next[start] = true;
+            for (index, c) in rest.iter().enumerate() {
+                if *c == '/' {
+                    next[start + index + 1] = true;
+                }
+            }
+        }
+        Token::Class { negated, ranges } => {
+            if let Some(c) = rest.first() {
+                let in_class = ranges.iter().any(|(low, high)| (*low..=*high).contains(c));
+                if *c != '/' && in_class != *negated {
+                    next[start + 1] = true;
+                }
+            }
+        }
+        // Alternatives are matched from every start at once by `match_positions`
+        Token::Alternatives(_) => {}
+        Token::NumberRange(low, high) => {
+            // Try every run of digits, with an optional sign, that starts the text
+            let negative = rest.first() == Some(&'-');
+            let sign = usize::from(negative);
+            let mut number = 0i64;
+            for (length, c) in rest[sign..].iter().enumerate() {
+                let Some(digit) = c.to_digit(10) else {
+                    break;
+                };
+                let Some(value) = number
+                    .checked_mul(10)
+                    .and_then(|n| n.checked_add(i64::from(digit)))
+                else {
+                    break;
+                };
+                number = value;
+                let signed = if negative { -number } else { number };
+                if (*low..=*high).contains(&signed) {
+                    next[start + sign + length + 1] = true;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_syntax_matches() {
+        let cases = [
+            ("*.rs", "src/main.rs", true),
+            ("/*.rs", "src/main.rs", false),
+            ("src/**/*.rs", "src/a/b/lib.rs", true),
+            ("src/**/*.rs", "src/lib.rs", true),
+            ("file?.txt", "file1.txt", true),
+            ("[!a-c]x", "dx", true),
+            ("[!a-c]x", "bx", false),
+            ("*.{rs,toml}", "Cargo.toml", true),
+            ("{a,b{c,d}}.txt", "bd.txt", true),
+            ("log{-2..10}.txt", "log-1.txt", true),
+            ("log{-2..10}.txt", "log11.txt", false),
+        ];
+        for (pattern, path, expected) in cases {
+            assert_eq!(
+                Glob::new(pattern).matches(path),
+                expected,
+                "{pattern} {path}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_glob_wildcards_do_not_backtrack_exponentially() {
+        // Backtracking over each `*` would take far too long to finish
+        let path = "a".repeat(200);
+        assert!(!Glob::new(&format!("/{}b", "*a".repeat(30))).matches(&path));
+        assert!(!Glob::new(&format!("/{}b", "**a".repeat(30))).matches(&path));
+        assert!(!Glob::new(&format!("/{}b", "{a,*}".repeat(30))).matches(&path));
+        assert!(Glob::new(&format!("/{}", "{a,*}".repeat(30))).matches(&path));
+    }
+}
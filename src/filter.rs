@@ -0,0 +1,180 @@
+use anyhow::{Context, Result};
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use crate::processing::{clean_content, smudge_content};
+use crate::types::ConfigSettings;
+
+// A pkt-line is a 4 digit hex length, counting itself, then up to this much data
+const MAX_PACKET_DATA: usize = 65516;
+const LENGTH_SIZE: usize = 4;
+
+/// One packet read from git
+#[derive(Debug, PartialEq, Eq)]
+enum Packet {
+    Data(Vec<u8>),
+    /// `0000`, which ends a list or the content
+    Flush,
+    /// Git closed the pipe
+    Eof,
+}
+
+/// Reads one pkt-line
+fn read_packet(reader: &mut impl Read) -> Result<Packet> {
+    let mut length = [0u8; LENGTH_SIZE];
+    match reader.read_exact(&mut length) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(Packet::Eof),
+        Err(e) => return Err(e.into()),
+    }
+
+    let length = std::str::from_utf8(&length)
+        .ok()
+        .and_then(|hex| usize::from_str_radix(hex, 16).ok())
+        .ok_or_else(|| anyhow::anyhow!("Invalid pkt-line length {length:?}"))?;
+    if length == 0 {
+        return Ok(Packet::Flush);
+    }
+    if length <= LENGTH_SIZE || length - LENGTH_SIZE > MAX_PACKET_DATA {
+        return Err(anyhow::anyhow!("Invalid pkt-line length {length}"));
+    }
+
+    let mut data = vec![0u8; length - LENGTH_SIZE];
+    reader
+        .read_exact(&mut data)
+        .with_context(|| "Git closed the pipe part way through a packet")?;
+    Ok(Packet::Data(data))
+}
+
+/// Reads `key=value` text packets up to a flush, without their trailing newlines. Returns
+/// `None` if git closed the pipe before the first packet.
+fn read_text_list(reader: &mut impl Read) -> Result<Option<Vec<String>>> {
+    let mut lines = Vec::new();
+    loop {
+        match read_packet(reader)? {
+            Packet::Data(data) => {
+                let line = String::from_utf8(data)
+                    .map_err(|e| anyhow::anyhow!("Packet from git is not valid UTF-8: {e}"))?;
+                lines.push(line.trim_end_matches('\n').to_string());
+            }
+            Packet::Flush => return Ok(Some(lines)),
+            Packet::Eof if lines.is_empty() => return Ok(None),
+            Packet::Eof => {
+                return Err(anyhow::anyhow!(
+                    "Git closed the pipe part way through a list"
+                ));
+            }
+        }
+    }
+}
+
+/// Reads content packets up to a flush
+fn read_content(reader: &mut impl Read) -> Result<Vec<u8>> {
+    let mut content = Vec::new();
+    loop {
+        match read_packet(reader)? {
+            Packet::Data(data) => content.extend_from_slice(&data),
+            Packet::Flush => return Ok(content),
+            Packet::Eof => {
+                return Err(anyhow::anyhow!(
+                    "Git closed the pipe part way through the content"
+                ));
+            }
+        }
+    }
+}
+
+fn write_packet(writer: &mut impl Write, data: &[u8]) -> io::Result<()> {
+    write!(writer, "{:04x}", data.len() + LENGTH_SIZE)?;
+    writer.write_all(data)
+}
+
+fn write_flush(writer: &mut impl Write) -> io::Result<()> {
+    writer.write_all(b"0000")
+}
+
+/// Writes text packets, each with a trailing newline, then a flush
+fn write_text_list(writer: &mut impl Write, lines: &[&str]) -> io::Result<()> {
+    for line in lines {
+        write_packet(writer, format!("{line}\n").as_bytes())?;
+    }
+    write_flush(writer)
+}
+
+/// Writes content split into packets, then a flush
+fn write_content(writer: &mut impl Write, content: &[u8]) -> io::Result<()> {
+    for chunk in content.chunks(MAX_PACKET_DATA) {
+        write_packet(writer, chunk)?;
+    }
+    write_flush(writer)
+}
+
+/// Value of `key=value` in a list of lines
+fn value_of<'a>(lines: &'a [String], key: &str) -> Option<&'a str> {
+    lines.iter().find_map(|line| {
+        line.strip_prefix(key)
+            .and_then(|rest| rest.strip_prefix('='))
+    })
+}
+
+/// Runs the git long-running filter process protocol, version 2, until git closes the pipe.
+/// Content is cleaned to LF line endings without a UTF-8 BOM, and smudged to the line ending
+/// and BOM that the options and rule files set for each path.
+///
+/// # Errors
+///
+/// Returns an error if git does not follow the protocol or the pipe fails. Problems with a
+/// single file are reported to git instead.
+pub fn run_filter_process(
+    config: &ConfigSettings,
+    mut reader: impl Read,
+    mut writer: impl Write,
+) -> Result<()> {
+    // Handshake: git announces itself and its versions, and the filter picks one
+    let welcome = read_text_list(&mut reader)?.unwrap_or_default();
+    if welcome.first().map(String::as_str) != Some("git-filter-client") {
+        return Err(anyhow::anyhow!(
+            "Expected git-filter-client, this command should be run by git"
+        ));
+    }
+    if !welcome.iter().any(|line| line == "version=2") {
+        return Err(anyhow::anyhow!(
+            "Git does not support filter protocol version 2"
+        ));
+    }
+    write_text_list(&mut writer, &["git-filter-server", "version=2"])?;
+    writer.flush()?;
+
+    // Agree on the capabilities that both sides support
+    let offered = read_text_list(&mut reader)?.unwrap_or_default();
+    let capabilities: Vec<&str> = ["capability=clean", "capability=smudge"]
+        .into_iter()
+        .filter(|capability| offered.iter().any(|line| line == capability))
+        .collect();
+    write_text_list(&mut writer, &capabilities)?;
+    writer.flush()?;
+
+    while let Some(headers) = read_text_list(&mut reader)? {
+        let content = read_content(&mut reader)?;
+        let path = Path::new(value_of(&headers, "pathname").unwrap_or_default());
+
+        let converted = match value_of(&headers, "command") {
+            Some("clean") => clean_content(path, &content, config).ok(),
+            Some("smudge") => smudge_content(path, &content, config).ok(),
+            _ => None,
+        };
+
+        match converted {
+            Some(converted) => {
+                write_text_list(&mut writer, &["status=success"])?;
+                write_content(&mut writer, &converted)?;
+                // An empty list keeps the status as success
+                write_flush(&mut writer)?;
+            }
+            None => write_text_list(&mut writer, &["status=error"])?,
+        }
+        writer.flush()?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,240 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::analysis::analyze_bytes;
+use crate::types::{ConfigSettings, FileAnalysis};
+
+// Modes of index entries that are not regular files: symlinks and submodules
+const SYMLINK_MODE: &str = "120000";
+const SUBMODULE_MODE: &str = "160000";
+
+/// A file selected from the git repository. Paths are relative to the directory git was
+/// run in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitFile {
+    pub path: String,
+    /// Object id of the staged content, when the index is analysed instead of the file
+    pub blob: Option<String>,
+}
+
+/// Runs git in `directory` and returns its standard output
+fn run_git(directory: &str, args: &[&str]) -> Result<Vec<u8>> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(directory)
+        .args(args)
+        .output()
+        .with_context(|| "Failed to run git")?;
+
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(output.stdout)
+}
+
+/// Splits NUL separated git output into paths
+fn split_paths(output: &[u8]) -> Result<Vec<String>> {
+    output
+        .split(|&byte| byte == 0)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            String::from_utf8(entry.to_vec())
+                .map_err(|e| anyhow::anyhow!("Path from git is not valid UTF-8: {e}"))
+        })
+        .collect()
+}
+
+/// Files tracked in the index at or below `directory`
+///
+/// # Errors
+///
+/// Returns an error if git fails, for example outside a repository.
+pub fn tracked_files(directory: &str) -> Result<Vec<GitFile>> {
+    let output = run_git(directory, &["ls-files", "-z"])?;
+    Ok(split_paths(&output)?
+        .into_iter()
+        .map(|path| GitFile { path, blob: None })
+        .collect())
+}
+
+/// Files added, copied, modified or renamed in the working tree compared with where it
+/// branched from `reference`, plus untracked files that are not ignored, at or below
+/// `directory`. Deleted files are left out.
+///
+/// # Errors
+///
+/// Returns an error if git fails or `reference` is not a commit.
+pub fn changed_files(directory: &str, reference: &str) -> Result<Vec<GitFile>> {
+    // Found with merge-base rather than `git diff --merge-base`, which needs git 2.30
+    let output = run_git(directory, &["merge-base", reference, "HEAD"])
+        .with_context(|| format!("Failed to find where HEAD branched from {reference}"))?;
+    let base = String::from_utf8_lossy(&output).trim().to_string();
+
+    let output = run_git(
+        directory,
+        &[
+            "diff",
+            "--name-only",
+            "-z",
+            "--relative",
+            "--diff-filter=ACMR",
+            &base,
+            "--",
+        ],
+    )?;
+    let mut paths = split_paths(&output)?;
+
+    // New files that have not been added are changes too
+    let output = run_git(
+        directory,
+        &["ls-files", "-z", "--others", "--exclude-standard"],
+    )?;
+    paths.extend(split_paths(&output)?);
+
+    Ok(paths
+        .into_iter()
+        .map(|path| GitFile { path, blob: None })
+        .collect())
+}
+
+/// Files with staged additions, copies, modifications or renames at or below `directory`,
+/// with the object id of their staged content. Symlinks and submodules are left out.
+///
+/// # Errors
+///
+/// Returns an error if git fails or its output cannot be parsed.
+pub fn staged_files(directory: &str) -> Result<Vec<GitFile>> {
+    let output = run_git(
+        directory,
+        &[
+            "diff",
+            "--cached",
+            "--raw",
+            "-z",
+            "--no-abbrev",
+            "--relative",
+            "--diff-filter=ACMR",
+        ],
+    )?;
+    parse_raw_diff(&output)
+}
+
+/// Parses `git diff --raw -z` output. Each entry is
+/// `:<old mode> <new mode> <old id> <new id> <status>` then the path, with a second path
+/// for renames and copies.
+fn parse_raw_diff(output: &[u8]) -> Result<Vec<GitFile>> {
+    let fields = split_paths(output)?;
+    let mut files = Vec::new();
+    let mut fields = fields.into_iter();
+
+    while let Some(header) = fields.next() {
+        let parts: Vec<&str> = header.trim_start_matches(':').split(' ').collect();
+        let [_, new_mode, _, new_id, status] = parts[..] else {
+            return Err(anyhow::anyhow!("Unexpected git diff output: {header}"));
+        };
+
+        // Renames and copies list the old path first
+        if status.starts_with(['R', 'C']) {
+            fields.next();
+        }
+        let path = fields
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("Missing path in git diff output"))?;
+
+        if new_mode != SYMLINK_MODE && new_mode != SUBMODULE_MODE {
+            files.push(GitFile {
+                path,
+                blob: Some(new_id.to_string()),
+            });
+        }
+    }
+
+    Ok(files)
+}
+
+/// Reads the content of a blob from the repository
+///
+/// # Errors
+///
+/// Returns an error if git fails or there is no such blob.
+pub fn read_blob(directory: &str, blob: &str) -> Result<Vec<u8>> {
+    run_git(directory, &["cat-file", "blob", blob])
+}
+
+/// Path of the pre-commit hook for the repository containing `directory`, following
+/// `core.hooksPath` when it is set
+///
+/// # Errors
+///
+/// Returns an error if git fails, for example outside a repository.
+pub fn pre_commit_hook_path(directory: &str) -> Result<PathBuf> {
+    let output = run_git(directory, &["rev-parse", "--git-path", "hooks/pre-commit"])?;
+    let path = String::from_utf8(output)
+        .map_err(|e| anyhow::anyhow!("Hook path from git is not valid UTF-8: {e}"))?;
+    Ok(Path::new(directory).join(path.trim_end()))
+}
+
+/// Path of a file found below `directory`, relative to `directory` again so that git run
+/// there finds it. Files are named with the folder they were searched for in, as
+/// `folder/path`, and other paths are returned unchanged.
+#[must_use]
+pub fn path_in_directory<'a>(directory: &str, path: &'a str) -> &'a str {
+    if directory == "." {
+        return path;
+    }
+    path.strip_prefix(directory.trim_end_matches('/'))
+        .and_then(|rest| rest.strip_prefix('/'))
+        .unwrap_or(path)
+}
+
+/// Returns true if the working tree copy of a file differs from its staged content, running
+/// git in `directory` with `path` relative to it
+///
+/// # Errors
+///
+/// Returns an error if git fails.
+pub fn has_unstaged_changes(directory: &str, path: &str) -> Result<bool> {
+    let status = Command::new("git")
+        .arg("-C")
+        .arg(directory)
+        .args(["diff", "--quiet", "--", path])
+        .status()
+        .with_context(|| "Failed to run git")?;
+
+    // git diff --quiet exits with 1 when there are differences
+    match status.code() {
+        Some(0) => Ok(false),
+        Some(1) => Ok(true),
+        _ => Err(anyhow::anyhow!("git diff failed for {path}")),
+    }
+}
+
+/// Adds the working tree copies of files to the index, running git in `directory` with the
+/// paths relative to it
+///
+/// # Errors
+///
+/// Returns an error if git fails.
+pub fn stage_files(directory: &str, paths: &[&str]) -> Result<()> {
+    let mut args = vec!["add", "--"];
+    args.extend(paths);
+    run_git(directory, &args).map(|_| ())
+}
+
+/// Analyzes the staged content of a file, read from its blob rather than the working tree
+#[must_use]
+pub fn analyze_staged_file(path: &str, blob: &str, config: &ConfigSettings) -> FileAnalysis {
+    let directory = config.folder.as_deref().unwrap_or(".");
+    match read_blob(directory, blob) {
+        Ok(data) => analyze_bytes(path, &data, config),
+        Err(e) => FileAnalysis {
+            error: Some(format!("Failed to read staged content: {e}")),
+            ..analyze_bytes(path, &[], config)
+        },
+    }
+}
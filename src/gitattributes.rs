@@ -0,0 +1,412 @@
+use anyhow::{Context, Result};
+use glob::{MatchOptions, Pattern};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use crate::types::LineEnding;
+use crate::utils::normalize_path;
+
+const GITATTRIBUTES: &str = ".gitattributes";
+
+// Macros can call other macros, this stops a macro that refers to itself
+const MAX_MACRO_DEPTH: usize = 16;
+
+// Same matching as git: `*` stops at `/`, `**` spans directories, leading dots are not special
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+/// State of one attribute for a path, as written in a `.gitattributes` line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AttributeState {
+    Set,           // `attr`
+    Unset,         // `-attr`
+    Value(String), // `attr=value`
+    Unspecified,   // `!attr`, back to the state it has when no line matches
+}
+
+/// What the attributes of a path say about its line endings
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum EolAttribute {
+    /// Marked `binary` or `-text`, so line endings must not be checked or changed
+    NotText,
+    /// Text with a fixed line ending, from `eol=lf`, `eol=crlf` or the legacy `crlf=input`
+    Ending(LineEnding),
+    /// No line ending is set for the path
+    Unspecified,
+}
+
+type Assignments = Vec<(String, AttributeState)>;
+
+/// One pattern line from an attributes file
+struct Rule {
+    pattern: Pattern,
+    /// A pattern without `/` matches the file name at any depth below `base`
+    basename_only: bool,
+    /// Directory of the attributes file, relative to the repository root with `/` separators
+    base: String,
+    assignments: Assignments,
+}
+
+impl Rule {
+    fn matches(&self, relative_path: &str) -> bool {
+        let within_base = if self.base.is_empty() {
+            Some(relative_path)
+        } else {
+            relative_path
+                .strip_prefix(self.base.as_str())
+                .and_then(|rest| rest.strip_prefix('/'))
+        };
+
+        let Some(within_base) = within_base else {
+            return false;
+        };
+
+        if self.basename_only {
+            let file_name = within_base.rsplit('/').next().unwrap_or(within_base);
+            self.pattern.matches_with(file_name, MATCH_OPTIONS)
+        } else {
+            self.pattern.matches_with(within_base, MATCH_OPTIONS)
+        }
+    }
+}
+
+/// Resolves git attributes for paths in a repository, from `.gitattributes` files in
+/// every directory and `$GIT_DIR/info/attributes`, with the same precedence as git.
+/// Nested `.gitattributes` files are read the first time a path below them is resolved.
+pub struct GitAttributes {
+    root: PathBuf,
+    macros: HashMap<String, Assignments>,
+    /// Rules from `$GIT_DIR/info/attributes`, which override every `.gitattributes` file
+    info_rules: Vec<Rule>,
+    /// Rules of each directory's `.gitattributes`, keyed by the directory relative to the root
+    directory_rules: Mutex<HashMap<String, Arc<Vec<Rule>>>>,
+}
+
+impl GitAttributes {
+    /// Finds the repository containing `start` by looking for `.git` in it and its parents,
+    /// and loads the root attributes files. Outside a repository `start` is used as the root.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the current directory cannot be determined or an attributes
+    /// file in the root exists but cannot be read.
+    pub fn discover(start: impl AsRef<Path>) -> Result<Self> {
+        let start = normalize_path(&std::path::absolute(start.as_ref())?);
+        let root = start
+            .ancestors()
+            .find(|dir| dir.join(".git").exists())
+            .unwrap_or(&start)
+            .to_path_buf();
+
+        // Macros are only allowed at the top level: the root file and info/attributes
+        let mut macros: HashMap<String, Assignments> = HashMap::new();
+        macros.insert(
+            String::from("binary"),
+            vec![
+                (String::from("diff"), AttributeState::Unset),
+                (String::from("merge"), AttributeState::Unset),
+                (String::from("text"), AttributeState::Unset),
+            ],
+        );
+
+        let root_rules = read_attributes_file(&root.join(GITATTRIBUTES), "", Some(&mut macros))?;
+
+        let info_rules = match git_dir(&root) {
+            Some(git_dir) => {
+                read_attributes_file(&git_dir.join("info/attributes"), "", Some(&mut macros))?
+            }
+            None => Vec::new(),
+        };
+
+        let mut directory_rules = HashMap::new();
+        directory_rules.insert(String::new(), Arc::new(root_rules));
+
+        Ok(GitAttributes {
+            root,
+            macros,
+            info_rules,
+            directory_rules: Mutex::new(directory_rules),
+        })
+    }
+
+    /// Root directory that attribute patterns are relative to
+    #[must_use]
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Resolves every attribute that is set, unset or given a value for a path.
+    /// Paths outside the repository have no attributes.
+    #[must_use]
+    pub fn attributes_for(&self, path: impl AsRef<Path>) -> HashMap<String, AttributeState> {
+        let mut attributes = HashMap::new();
+
+        let Some(relative_path) = self.relative_path(path.as_ref()) else {
+            return attributes;
+        };
+
+        // Lowest precedence first: the root file, each nested file down to the path's
+        // directory, then info/attributes. Later lines override earlier ones.
+        let mut directories = vec![String::new()];
+        let mut components: Vec<&str> = relative_path.split('/').collect();
+        components.pop(); // the file name
+        for depth in 1..=components.len() {
+            directories.push(components[..depth].join("/"));
+        }
+
+        for directory in &directories {
+            let rules = self.rules_for_directory(directory);
+            self.apply_matching(&rules, &relative_path, &mut attributes);
+        }
+        self.apply_matching(&self.info_rules, &relative_path, &mut attributes);
+
+        attributes.retain(|_, state| *state != AttributeState::Unspecified);
+        attributes
+    }
+
+    /// Resolves the `text`, `eol` and legacy `crlf` attributes of a path to its line ending
+    #[must_use]
+    pub fn eol_for(&self, path: impl AsRef<Path>) -> EolAttribute {
+        let attributes = self.attributes_for(path);
+        let text = attributes.get("text");
+        let crlf = attributes.get("crlf");
+
+        if text == Some(&AttributeState::Unset)
+            || (text.is_none() && crlf == Some(&AttributeState::Unset))
+        {
+            return EolAttribute::NotText;
+        }
+
+        match attributes.get("eol") {
+            Some(AttributeState::Value(value)) if value == "lf" => {
+                return EolAttribute::Ending(LineEnding::Lf);
+            }
+            Some(AttributeState::Value(value)) if value == "crlf" => {
+                return EolAttribute::Ending(LineEnding::Crlf);
+            }
+            _ => {}
+        }
+
+        if crlf == Some(&AttributeState::Value(String::from("input"))) {
+            return EolAttribute::Ending(LineEnding::Lf);
+        }
+
+        EolAttribute::Unspecified
+    }
+
+    /// Applies the assignments of every rule that matches, expanding macros
+    fn apply_matching(
+        &self,
+        rules: &[Rule],
+        relative_path: &str,
+        attributes: &mut HashMap<String, AttributeState>,
+    ) {
+        for rule in rules.iter().filter(|rule| rule.matches(relative_path)) {
+            self.apply_assignments(&rule.assignments, attributes, 0);
+        }
+    }
+
+    fn apply_assignments(
+        &self,
+        assignments: &Assignments,
+        attributes: &mut HashMap<String, AttributeState>,
+        depth: usize,
+    ) {
+        for (name, state) in assignments {
+            // Setting a macro sets every attribute it stands for
+            if *state == AttributeState::Set
+                && depth < MAX_MACRO_DEPTH
+                && let Some(expansion) = self.macros.get(name)
+            {
+                self.apply_assignments(expansion, attributes, depth + 1);
+            }
+            attributes.insert(name.clone(), state.clone());
+        }
+    }
+
+    /// Rules of the `.gitattributes` in a directory, read and cached on first use.
+    /// A nested file that cannot be read is treated as empty.
+    fn rules_for_directory(&self, directory: &str) -> Arc<Vec<Rule>> {
+        let cached = self
+            .directory_rules
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(directory)
+            .cloned();
+        if let Some(rules) = cached {
+            return rules;
+        }
+
+        let file = self.root.join(directory).join(GITATTRIBUTES);
+        let rules = Arc::new(read_attributes_file(&file, directory, None).unwrap_or_default());
+
+        self.directory_rules
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(directory.to_string())
+            .or_insert(rules)
+            .clone()
+    }
+
+    /// Path relative to the root with `/` separators, or `None` outside the root
+    fn relative_path(&self, path: &Path) -> Option<String> {
+        let absolute = normalize_path(&std::path::absolute(path).ok()?);
+        let relative = absolute.strip_prefix(&self.root).ok()?;
+        let parts: Vec<String> = relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy().into_owned())
+            .collect();
+
+        if parts.is_empty() {
+            None
+        } else {
+            Some(parts.join("/"))
+        }
+    }
+}
+
+/// Finds the git directory of a repository root. `.git` is usually a directory, but in
+/// worktrees and submodules it is a file containing `gitdir: <path>`.
+fn git_dir(root: &Path) -> Option<PathBuf> {
+    let dot_git = root.join(".git");
+    if dot_git.is_dir() {
+        return Some(dot_git);
+    }
+
+    let contents = std::fs::read_to_string(&dot_git).ok()?;
+    let target = contents.trim().strip_prefix("gitdir:")?.trim();
+    Some(root.join(target))
+}
+
+/// Reads an attributes file into rules. A missing file has no rules. Macro definitions
+/// are collected into `macros` when given, and ignored otherwise, as git does for nested files.
+fn read_attributes_file(
+    path: &Path,
+    base: &str,
+    macros: Option<&mut HashMap<String, Assignments>>,
+) -> Result<Vec<Rule>> {
+    match std::fs::read_to_string(path) {
+        Ok(contents) => Ok(parse_attributes(&contents, base, macros)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
+
+/// Parses the lines of an attributes file
+fn parse_attributes(
+    contents: &str,
+    base: &str,
+    mut macros: Option<&mut HashMap<String, Assignments>>,
+) -> Vec<Rule> {
+    let mut rules = Vec::new();
+
+    for line in contents.lines() {
+        let line = line.trim_start();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (pattern, rest) = split_pattern(line);
+        let assignments: Assignments = rest.split_whitespace().map(parse_assignment).collect();
+
+        if let Some(name) = pattern.strip_prefix("[attr]") {
+            if let Some(macros) = macros.as_deref_mut() {
+                macros.insert(name.to_string(), assignments);
+            }
+            continue;
+        }
+
+        if let Some(rule) = build_rule(&pattern, base, assignments) {
+            rules.push(rule);
+        }
+    }
+
+    rules
+}
+
+/// Turns a pattern into a rule, or `None` for patterns that can never match a file:
+/// negated patterns are not allowed in attributes files, and a trailing `/` only matches directories
+fn build_rule(pattern: &str, base: &str, assignments: Assignments) -> Option<Rule> {
+    if pattern.is_empty() || pattern.starts_with('!') || pattern.ends_with('/') {
+        return None;
+    }
+
+    // A leading `/` anchors the pattern to the directory, as any other `/` does
+    let basename_only = !pattern.contains('/');
+    let pattern = pattern.strip_prefix('/').unwrap_or(pattern);
+
+    Some(Rule {
+        pattern: Pattern::new(&escape_for_glob(pattern)).ok()?,
+        basename_only,
+        base: base.to_string(),
+        assignments,
+    })
+}
+
+/// Converts git's backslash escapes to the bracket form the glob crate understands
+fn escape_for_glob(pattern: &str) -> String {
+    let mut converted = String::with_capacity(pattern.len());
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some(escaped)) => {
+                chars.next();
+                converted.push('[');
+                converted.push(escaped);
+                converted.push(']');
+            }
+            _ => converted.push(c),
+        }
+    }
+    converted
+}
+
+/// Splits a line into its pattern and the attributes after it. Patterns may be quoted
+/// C-style so they can contain spaces.
+fn split_pattern(line: &str) -> (String, &str) {
+    if let Some(quoted) = line.strip_prefix('"') {
+        let mut pattern = String::new();
+        let mut chars = quoted.char_indices();
+        while let Some((index, c)) = chars.next() {
+            match c {
+                '"' => return (pattern, &quoted[index + 1..]),
+                '\\' => match chars.next() {
+                    Some((_, 't')) => pattern.push('\t'),
+                    Some((_, 'n')) => pattern.push('\n'),
+                    // Keep escaped glob characters escaped
+                    Some((_, escaped @ ('*' | '?' | '['))) => {
+                        pattern.push('\\');
+                        pattern.push(escaped);
+                    }
+                    Some((_, escaped)) => pattern.push(escaped),
+                    None => {}
+                },
+                _ => pattern.push(c),
+            }
+        }
+        // Unterminated quote, use the rest of the line as the pattern
+        return (pattern, "");
+    }
+
+    match line.find(char::is_whitespace) {
+        Some(end) => (line[..end].to_string(), &line[end..]),
+        None => (line.to_string(), ""),
+    }
+}
+
+/// Parses one attribute token: `attr`, `-attr`, `!attr` or `attr=value`
+fn parse_assignment(token: &str) -> (String, AttributeState) {
+    if let Some(name) = token.strip_prefix('-') {
+        (name.to_string(), AttributeState::Unset)
+    } else if let Some(name) = token.strip_prefix('!') {
+        (name.to_string(), AttributeState::Unspecified)
+    } else if let Some((name, value)) = token.split_once('=') {
+        (name.to_string(), AttributeState::Value(value.to_string()))
+    } else {
+        (token.to_string(), AttributeState::Set)
+    }
+}
@@ -1,6 +1,10 @@
 pub const HELP: &str = "\
 USAGE:
     line_endings [options] file_pattern...
+    line_endings hook install [--force] [options]
+    line_endings hook run [options] [file...]
+    line_endings filter [options]
+    line_endings --stdin [--stdin-path <PATH>] [options]
 
 OPTIONS:
     -h, --help                   Prints help information
@@ -8,12 +12,73 @@ OPTIONS:
     -c, --case-sensitive         Case-sensitive glob matching
     -b, --bom                    Check for Byte Order Mark (BOM) in files
     -r, --recursive              Recursively search subdirectories
+        --exclude <GLOB>         Skip files and directories matching a .gitignore style glob,
+                                 relative to the folder (can be repeated)
+        --no-ignore              Also search .git and files ignored by .gitignore, .ignore and
+                                 the global git excludes file
+        --files-from <FILE>      Process the files listed in FILE, one per line, instead of
+                                 file patterns. Use - to read the list from stdin
+    -0, --null                   Paths in the --files-from list are separated by NUL characters,
+                                 as written by git ls-files -z, find -print0 or fd -0
+        --git-tracked            Process the files tracked by git in the folder
+        --git-staged             Check the staged content of files added or changed in the git
+                                 index, rather than the working tree copy
+        --git-changed <REF>      Process the files changed since the working tree branched
+                                 from REF, such as main or origin/main, and untracked files
+                                 that are not ignored
+        --stdin                  Convert the content read from stdin and write it to stdout,
+                                 applying the line ending and BOM fix options
+        --stdin-path <PATH>      Apply the rule file settings for PATH to the stdin content
     -n, --no-trash               Keep .bak backup files (default: moved to trash after operations)
+        --format <FORMAT>        Output format: text (default), json, ndjson, sarif, junit
+                                 or checkstyle. Every format lists violations only with
+                                 --check
+        --config <FILE>          Read include/exclude globs and rules from this file instead
+                                 of the nearest .line-endings.toml above the folder
+        --no-config              Ignore any .line-endings.toml
+        --buffer-size <SIZE>     Bytes read at a time when scanning lines, from 1KB to 1GB,
+                                 such as 256KB or 4MB (default: 64KB)
 
 FIXES:
     -w, --windows-line-endings   Rewrite with Windows line endings (CRLF)
     -l, --linux-line-endings     Rewrite with Linux line endings (LF)
-    -m, --remove-bom             Remove BOM from files that have one";
+        --mac-line-endings       Rewrite with classic Mac line endings (CR)
+    -m, --remove-bom             Remove UTF-8 BOMs (UTF-16/32 BOMs are kept)
+        --gitattributes          Use the eol set for each file in .gitattributes, skipping
+                                 files marked binary or -text. Files are only rewritten
+                                 with --fix or -l/-w/--mac-line-endings
+        --editorconfig           Use end_of_line, charset, insert_final_newline and
+                                 trim_trailing_whitespace from .editorconfig for each file.
+                                 Files are only fixed with --fix or another fix option
+        --fix                    Fix files to match the rules in .line-endings.toml and, when
+                                 given, --gitattributes and --editorconfig
+        --dry-run                Report the files the fix options would change, the line endings
+                                 that would be rewritten and the BOMs that would be removed or
+                                 added, without modifying anything
+        --diff                   Dry run, also showing each change as a unified diff with
+                                 carriage returns as \\r and BOMs as <BOM>
+
+CHECKING:
+        --check                  Report files not matching the fix options, exiting with
+                                 status 1, without modifying anything. Mixed and lone CR
+                                 line endings fail only when a line ending is expected
+
+HOOKS:
+    hook install                 Write a git pre-commit hook that runs hook run with the
+                                 options given after it
+        --force                  Replace an existing pre-commit hook not written by this tool
+    hook run                     Check the named files, or the staged content when no files
+                                 are named, exiting with status 1 if any fail
+        --autofix                Fix the files that fail in the working tree, still exiting
+                                 with status 1 so the changes can be reviewed
+        --restage                Fix the files that fail and add them back to the index, so
+                                 the commit goes ahead
+
+FILTER:
+    filter                       Run as a git long-running filter process. Clean stores text
+                                 with LF line endings and no UTF-8 BOM; smudge checks files
+                                 out with the line ending and BOM the options and rule files
+                                 set for each path";
 
 /// Show help message
 pub fn show_help() {
@@ -0,0 +1,81 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::git;
+
+/// Comment identifying hooks written by `hook install`, which can be replaced without `--force`
+pub const HOOK_MARKER: &str = "# Installed by line_endings hook install";
+
+/// Builds a pre-commit hook script that runs `hook run` with the given options
+#[must_use]
+pub fn pre_commit_script(executable: &Path, options: &[String]) -> String {
+    let mut command = vec![
+        shell_quote(&executable.to_string_lossy()),
+        "hook".to_string(),
+        "run".to_string(),
+    ];
+    command.extend(options.iter().map(|option| shell_quote(option)));
+
+    format!("#!/bin/sh\n{HOOK_MARKER}\nexec {}\n", command.join(" "))
+}
+
+/// Writes a pre-commit hook for the repository containing the current directory. An existing
+/// hook is only replaced if it was written by this tool or `force` is set. Returns the path
+/// of the hook.
+///
+/// # Errors
+///
+/// Returns an error if this is not a git repository, another hook is in the way, or the hook
+/// cannot be written.
+pub fn install_pre_commit_hook(options: &[String], force: bool) -> Result<PathBuf> {
+    let path = git::pre_commit_hook_path(".")?;
+
+    if !force
+        && let Ok(existing) = fs::read_to_string(&path)
+        && !existing.contains(HOOK_MARKER)
+    {
+        return Err(anyhow::anyhow!(
+            "A pre-commit hook already exists at {}, use --force to replace it",
+            path.display()
+        ));
+    }
+
+    let executable =
+        std::env::current_exe().with_context(|| "Failed to find the path of this program")?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    fs::write(&path, pre_commit_script(&executable, options))
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    make_executable(&path)?;
+
+    Ok(path)
+}
+
+/// Quotes a word for a POSIX shell, leaving simple words alone
+fn shell_quote(word: &str) -> String {
+    let simple = !word.is_empty()
+        && word
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=:@+,".contains(c));
+    if simple {
+        word.to_string()
+    } else {
+        format!("'{}'", word.replace('\'', r"'\''"))
+    }
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755))
+        .with_context(|| format!("Failed to make {} executable", path.display()))
+}
+
+// Git for Windows runs hooks through its own shell, whatever the file permissions
+#[cfg(not(unix))]
+fn make_executable(_path: &Path) -> Result<()> {
+    Ok(())
+}
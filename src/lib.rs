@@ -1,8 +1,20 @@
 #![forbid(unsafe_code)]
-// Library crate for line_endings to expose modules for testing
+// Library crate for line_endings, used by the binary and to expose modules for testing
 pub mod analysis;
+pub mod check;
+pub mod chunks;
 pub mod config;
+pub mod editorconfig;
+pub mod filter;
+pub mod git;
+pub mod gitattributes;
+pub mod hook;
+#[cfg(feature = "mmap")]
+pub mod mmap;
+pub mod preview;
 pub mod processing;
+pub mod project_config;
+pub mod report;
 pub mod types;
 #[cfg(test)]
 pub mod unit_tests;
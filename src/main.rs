@@ -3,33 +3,51 @@
 use anyhow::{Context, Result};
 use pico_args::Arguments;
 use rayon::prelude::*;
+use std::ffi::OsString;
+use std::io::{self, BufReader, BufWriter};
+use std::path::Path;
+use std::process::ExitCode;
+use std::sync::mpsc;
 use std::time::Instant;
 
-mod analysis;
-mod config;
 mod help;
-mod processing;
-mod types;
-mod utils;
 
-use analysis::analyze_file;
-use config::parse_args;
 use help::show_help;
-use processing::{remove_bom_from_files, rewrite_files, trash_backup_files};
-use types::{FileAnalysis, LineEndingTarget};
-use utils::get_paths_matching_glob;
+use line_endings::analysis::analyze_file;
+use line_endings::check::{check_file, check_files};
+use line_endings::config::{parse_args, parse_filter_args, parse_hook_args};
+use line_endings::filter::run_filter_process;
+use line_endings::git::{self, analyze_staged_file};
+use line_endings::hook::install_pre_commit_hook;
+use line_endings::preview::preview_fixes;
+use line_endings::processing::{convert_stream, copy_without_bom, fix_files, trash_backup_files};
+use line_endings::report::{
+    checkstyle_report, junit_report, print_json_report, print_ndjson_file, print_ndjson_summary,
+    print_sarif_report,
+};
+use line_endings::types::{
+    AnalysisSummary, ConfigSettings, FileAnalysis, FileSource, HookSettings, LineEndingTarget,
+    OutputFormat, TextEncoding,
+};
+use line_endings::utils::stream_input_paths;
 
 /// Formats and prints analysis results for a successfully analyzed file
 fn print_file_analysis(result: &FileAnalysis) {
     let file_name = result.path.display();
-    let line_endings = if result.lf_count == 0 && result.crlf_count == 0 {
-        String::from("None")
-    } else if result.lf_count > 0 && result.crlf_count == 0 {
-        format!("LF {}", result.lf_count)
-    } else if result.lf_count == 0 && result.crlf_count > 0 {
-        format!("CRLF {}", result.crlf_count)
-    } else {
-        format!("Mixed LF {}, CRLF {}", result.lf_count, result.crlf_count)
+    let counts: Vec<String> = [
+        ("LF", result.lf_count),
+        ("CRLF", result.crlf_count),
+        ("CR", result.cr_count),
+    ]
+    .iter()
+    .filter(|(_, count)| *count > 0)
+    .map(|(name, count)| format!("{name} {count}"))
+    .collect();
+
+    let line_endings = match counts.len() {
+        0 => String::from("None"),
+        1 => counts[0].clone(),
+        _ => format!("Mixed {}", counts.join(", ")),
     };
 
     let bom_info = if result.bom_checked {
@@ -41,32 +59,32 @@ fn print_file_analysis(result: &FileAnalysis) {
         String::new()
     };
 
-    println!("\"{file_name}\"\t{line_endings}{bom_info}");
-}
-
-fn main() -> Result<()> {
-    let mut p_args = Arguments::from_env();
-
-    if p_args.contains(["-h", "--help"]) {
-        show_help();
-        return Ok(());
-    }
-
-    let config = parse_args(p_args)?;
+    let encoding_info = if result.encoding == TextEncoding::Utf8 {
+        String::new()
+    } else {
+        format!(", encoding: {}", result.encoding)
+    };
 
-    let start_time = Instant::now();
+    println!("\"{file_name}\"\t{line_endings}{bom_info}{encoding_info}");
+}
 
-    // expand glob patterns and get file paths
-    let expanded_paths =
-        get_paths_matching_glob(&config).with_context(|| "Failed to expand glob patterns")?;
+/// Prints the active configuration, only showing non-default options
+fn print_config(config: &ConfigSettings) {
+    let mut config_parts = Vec::new();
 
-    if expanded_paths.is_empty() {
-        return Err(anyhow::anyhow!("No input files found"));
+    match &config.file_source {
+        FileSource::Patterns | FileSource::Files(_) | FileSource::Stdin { .. } => {}
+        FileSource::List { path, .. } => {
+            let source = if path == "-" { "stdin" } else { path };
+            config_parts.push(format!("Files from: {source}"));
+        }
+        FileSource::GitTracked => config_parts.push("Files from: git tracked files".to_string()),
+        FileSource::GitStaged => config_parts.push("Files from: git staged files".to_string()),
+        FileSource::GitChanged(reference) => {
+            config_parts.push(format!("Files from: git files changed since {reference}"));
+        }
     }
 
-    // Build configuration display, only showing non-default/active options
-    let mut config_parts = Vec::new();
-
     // Always show folder if not current directory
     if let Some(folder) = &config.folder
         && folder != "."
@@ -85,122 +103,426 @@ fn main() -> Result<()> {
         config_parts.push("Check BOM: true".to_string());
     }
     if config.remove_bom {
-        config_parts.push("Remove BOM: true".to_string());
+        if config.check {
+            config_parts.push("Forbid BOM: true".to_string());
+        } else {
+            config_parts.push("Remove BOM: true".to_string());
+        }
     }
     if config.no_trash {
         config_parts.push("Trash backups: disabled".to_string());
     }
 
-    // Only show line ending alteration if one is set
+    if config.check {
+        config_parts.push("Check only: true".to_string());
+    }
+    if config.dry_run {
+        config_parts.push("Dry run: true".to_string());
+    }
+
+    // Only show line ending alteration if one is set. In check mode it is the expected ending
+    let target_label = if config.check {
+        "Expected line endings"
+    } else {
+        "Line ending alteration"
+    };
     match config.line_ending_target {
         LineEndingTarget::Linux => {
-            config_parts.push("Line ending alteration: Linux (LF)".to_string());
+            config_parts.push(format!("{target_label}: Linux (LF)"));
         }
         LineEndingTarget::Windows => {
-            config_parts.push("Line ending alteration: Windows (CRLF)".to_string());
+            config_parts.push(format!("{target_label}: Windows (CRLF)"));
+        }
+        LineEndingTarget::Mac => {
+            config_parts.push(format!("{target_label}: Classic Mac (CR)"));
         }
         LineEndingTarget::None => {} // Don't show anything for no alteration
     }
+    if config.editorconfig.is_some() {
+        config_parts.push(format!("{target_label}: per file from .editorconfig"));
+    }
+    if let Some(project) = &config.project {
+        config_parts.push(format!(
+            "{target_label}: per file from {}",
+            project.path().display()
+        ));
+    }
+    if let Some(attributes) = &config.gitattributes {
+        config_parts.push(format!(
+            "{target_label}: per file from .gitattributes in {}",
+            attributes.root().display()
+        ));
+    }
 
     // Display configuration if there are any non-default options
     if !config_parts.is_empty() {
         println!("{}", config_parts.join(", "));
     }
+}
+
+fn main() -> Result<ExitCode> {
+    let mut p_args = Arguments::from_env();
+
+    if p_args.contains(["-h", "--help"]) {
+        show_help();
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    // `hook` and `filter` are subcommands, anything else is a file pattern
+    let first_arg = std::env::args_os().nth(1);
+    if first_arg.as_ref().is_some_and(|arg| arg == "hook") {
+        p_args.subcommand()?;
+        return run_hook_command(p_args);
+    }
+    if first_arg.as_ref().is_some_and(|arg| arg == "filter") {
+        p_args.subcommand()?;
+        let config = parse_filter_args(p_args)?;
+        let input = BufReader::new(io::stdin().lock());
+        let output = BufWriter::new(io::stdout().lock());
+        run_filter_process(&config, input, output)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let config = parse_args(p_args)?;
+
+    // Stdout carries the converted content, so nothing else is printed
+    if let FileSource::Stdin { path } = &config.file_source {
+        convert_stdin(&config, path.as_deref())?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let text_output = config.format == OutputFormat::Text;
+
+    let start_time = Instant::now();
+
+    if text_output {
+        print_config(&config);
+    }
 
-    // Process all files in parallel using rayon
     let analysis_start = Instant::now();
-    let results: Vec<_> = expanded_paths
-        .par_iter()
-        .map(|path| analyze_file(path, &config))
-        .collect();
-    let analysis_duration = analysis_start.elapsed();
+    let results = analyze_input_files(&config)?;
+    if results.is_empty() {
+        return Err(anyhow::anyhow!("No input files found"));
+    }
 
     // Print any errors and categorize them
-    let mut has_errors = 0;
-    let mut binary_files = 0;
-    let mut analyzed_files = 0;
-    let mut total_lf = 0usize;
-    let mut total_crlf = 0usize;
-    let mut mixed_files = 0usize;
+    let mut summary = AnalysisSummary {
+        analysis_duration: analysis_start.elapsed(),
+        ..AnalysisSummary::default()
+    };
 
     for result in &results {
         if result.is_binary {
-            binary_files += 1;
+            summary.binary_files += 1;
         } else if let Some(error) = &result.error {
-            let filename = result.path.display();
-            println!("\nFile: {filename}\terror: {error}");
-            has_errors += 1;
+            if text_output {
+                let filename = result.path.display();
+                println!("\nFile: {filename}\terror: {error}");
+            }
+            summary.error_files += 1;
         } else {
-            print_file_analysis(result);
-
-            analyzed_files += 1;
-            total_lf += result.lf_count;
-            total_crlf += result.crlf_count;
-            if result.has_mixed_line_endings() {
-                mixed_files += 1;
+            if text_output {
+                print_file_analysis(result);
             }
+            summary.add_analyzed(result);
         }
     }
 
+    if !text_output {
+        return finish_machine_readable_report(&config, &results, summary, start_time);
+    }
+
     // Report binary files separately
-    if binary_files > 0 {
-        println!("\nSkipped {binary_files} binary file(s)");
+    if summary.binary_files > 0 {
+        println!("\nSkipped {} binary file(s)", summary.binary_files);
     }
 
     // bail if there are any real errors (not binary files)
-    if has_errors > 0 {
-        return Err(anyhow::anyhow!("  Files with errors: {has_errors}"));
+    if summary.error_files > 0 {
+        return Err(anyhow::anyhow!(
+            "  Files with errors: {}",
+            summary.error_files
+        ));
     }
 
-    // optionally rewrite files if requested
-    if config.has_rewrite_option() {
-        rewrite_files(&config, &results)?;
+    // In check mode, report violations instead of fixing anything
+    if config.check {
+        summary.failed_files = check_files(&config, &results);
+        summary.total_duration = start_time.elapsed();
+        print_summary(&summary);
+
+        return Ok(check_exit_code(&summary));
     }
 
-    // Remove BOMs if requested (can happen alongside line ending changes)
-    if config.remove_bom {
-        remove_bom_from_files(&config, &results)?;
+    // In a dry run, report what the fixes would change instead of making them
+    if config.dry_run {
+        preview_fixes(&config, &results)?;
+        summary.total_duration = start_time.elapsed();
+        print_summary(&summary);
+
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    apply_fixes(&config, &results)?;
+
+    // Print summary statistics
+    summary.total_duration = start_time.elapsed();
+    print_summary(&summary);
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Converts stdin to stdout, using the rules for `path` if given or else the fix options
+fn convert_stdin(config: &ConfigSettings, path: Option<&str>) -> Result<()> {
+    let policy = match path {
+        Some(path) => config.policy_for(Path::new(path)),
+        None => Some(config.policy()),
+    };
+    let input = io::stdin().lock();
+    let output = BufWriter::new(io::stdout().lock());
+
+    // Files marked binary or -text in .gitattributes pass through unchanged
+    match policy {
+        Some(policy) => convert_stream(input, output, &policy),
+        None => copy_without_bom(input, output, 0),
     }
+    .with_context(|| "Failed to convert stdin")
+}
+
+/// Finds the input files and analyses them, returning the results in report order
+fn analyze_input_files(config: &ConfigSettings) -> Result<Vec<FileAnalysis>> {
+    // Walk for files (or read the file list) on other threads, analysing each one with rayon as soon as it is found
+    let (sender, receiver) = mpsc::channel();
+    let (walk_result, mut analysed) = std::thread::scope(|scope| {
+        let walker = scope.spawn(|| stream_input_paths(config, sender));
+
+        let analysed: Vec<_> = receiver
+            .into_iter()
+            .par_bridge()
+            .map(|matched| {
+                // Staged files are read from the index rather than the working tree
+                let result = match &matched.index_blob {
+                    Some(blob) => analyze_staged_file(&matched.path, blob, config),
+                    None => analyze_file(&matched.path, config),
+                };
+                // NDJSON streams each file as soon as it has been analysed
+                if config.format == OutputFormat::Ndjson {
+                    print_ndjson_file(&result, config);
+                }
+                (matched.sort_key(), result)
+            })
+            .collect();
+
+        (walker.join(), analysed)
+    });
+    walk_result
+        .map_err(|_| anyhow::anyhow!("File search thread panicked"))?
+        .with_context(|| "Failed to find input files")?;
+
+    // Report files in the order of the patterns, sorted within each pattern
+    analysed.sort_by(|(a, _), (b, _)| a.cmp(b));
+    Ok(analysed.into_iter().map(|(_, result)| result).collect())
+}
+
+/// Applies the requested fixes to the analysed files, then moves the backups to the trash
+fn apply_fixes(config: &ConfigSettings, results: &[FileAnalysis]) -> Result<()> {
+    // Line endings, BOMs, and the final newline and whitespace rules from .editorconfig and
+    // .line-endings.toml are fixed together, reading and writing each file once
+    fix_files(config, results)?;
 
     // Move backup files to trash unless --no-trash was specified
     if !config.no_trash && (config.has_rewrite_option() || config.remove_bom) {
-        trash_backup_files(&results)?;
+        trash_backup_files(results)?;
     }
 
-    // Print summary statistics
-    let total_duration = start_time.elapsed();
-    print_summary(
-        analyzed_files,
-        binary_files,
-        mixed_files,
-        total_lf,
-        total_crlf,
-        analysis_duration,
-        total_duration,
-    );
-
     Ok(())
 }
 
-fn print_summary(
-    analyzed_files: usize,
-    binary_files: usize,
-    mixed_files: usize,
-    total_lf: usize,
-    total_crlf: usize,
-    analysis_duration: std::time::Duration,
-    total_duration: std::time::Duration,
-) {
+/// Runs `hook install` or `hook run`
+fn run_hook_command(mut args: Arguments) -> Result<ExitCode> {
+    match args.subcommand()?.as_deref() {
+        Some("install") => {
+            let force = args.contains("--force");
+            let options = args
+                .finish()
+                .into_iter()
+                .map(|option| {
+                    option.into_string().map_err(|option| {
+                        anyhow::anyhow!("Option is not valid UTF-8: {}", option.display())
+                    })
+                })
+                .collect::<Result<Vec<String>>>()?;
+
+            // Check the options now rather than on the first commit
+            let settings = parse_hook_args(Arguments::from_vec(
+                options.iter().map(OsString::from).collect(),
+            ))?;
+            if !settings.config.supplied_paths.is_empty() {
+                return Err(anyhow::anyhow!(
+                    "hook install takes options, not file names"
+                ));
+            }
+
+            let path = install_pre_commit_hook(&options, force)?;
+            println!("Installed pre-commit hook at {}", path.display());
+            Ok(ExitCode::SUCCESS)
+        }
+        Some("run") => run_hook(&parse_hook_args(args)?),
+        _ => Err(anyhow::anyhow!("Expected 'hook install' or 'hook run'")),
+    }
+}
+
+/// Checks files before a commit, failing if any break the rules. With `--autofix` the failing
+/// files are fixed in the working tree, and with `--restage` they are added back to the index.
+fn run_hook(settings: &HookSettings) -> Result<ExitCode> {
+    let config = &settings.config;
+    let results = analyze_input_files(config)?;
+
+    let mut error_files = 0usize;
+    for result in &results {
+        if let Some(error) = &result.error {
+            println!("\nFile: {}\terror: {error}", result.path.display());
+            error_files += 1;
+        }
+    }
+    if error_files > 0 {
+        return Err(anyhow::anyhow!("  Files with errors: {error_files}"));
+    }
+
+    // Nothing to check, for example when a commit only deletes files
+    if results.is_empty() || check_files(config, &results) == 0 {
+        return Ok(ExitCode::SUCCESS);
+    }
+    if !settings.autofix {
+        return Ok(ExitCode::FAILURE);
+    }
+
+    // The working tree copy is fixed, which may differ from the staged content that was checked.
+    // Git is run in the folder, like when the staged files were listed.
+    let directory = config.folder.as_deref().unwrap_or(".");
+    let mut to_fix = Vec::new();
+    let mut not_fixed = 0usize;
+    for result in results.iter().filter(|result| fails_check(config, result)) {
+        let path = result.path.to_string_lossy();
+        // Restaging a file with unstaged changes would commit them too
+        if settings.restage
+            && git::has_unstaged_changes(directory, git::path_in_directory(directory, &path))?
+        {
+            println!("\"{path}\"\tnot fixed: has unstaged changes");
+            not_fixed += 1;
+        } else {
+            to_fix.push(analyze_file(path.as_ref(), config));
+        }
+    }
+    if !to_fix.is_empty() {
+        apply_fixes(config, &to_fix)?;
+    }
+
+    // Some violations, such as mixed line endings without a target, can't be fixed
+    let mut fixed = Vec::new();
+    for result in &to_fix {
+        let path = result.path.to_string_lossy().into_owned();
+        if fails_check(config, &analyze_file(&path, config)) {
+            println!("\"{path}\"\tcould not be fixed");
+            not_fixed += 1;
+        } else {
+            fixed.push(path);
+        }
+    }
+
+    if settings.restage {
+        if !fixed.is_empty() {
+            let paths: Vec<&str> = fixed
+                .iter()
+                .map(|path| git::path_in_directory(directory, path))
+                .collect();
+            git::stage_files(directory, &paths)?;
+            println!("Fixed and re-staged {} file(s)", fixed.len());
+        }
+    } else {
+        println!(
+            "Fixed {} file(s), review and stage the changes",
+            fixed.len()
+        );
+    }
+
+    if settings.restage && not_fixed == 0 {
+        Ok(ExitCode::SUCCESS)
+    } else {
+        Ok(ExitCode::FAILURE)
+    }
+}
+
+/// Returns true if a text file breaks its policy
+fn fails_check(config: &ConfigSettings, result: &FileAnalysis) -> bool {
+    !result.is_binary
+        && result.error.is_none()
+        && config
+            .policy_for(&result.path)
+            .is_some_and(|policy| !check_file(result, &policy).is_empty())
+}
+
+/// Completes a machine-readable run. Fixes are rejected for these formats in `parse_args`,
+/// so this only counts check failures, prints the summary and picks the exit code.
+fn finish_machine_readable_report(
+    config: &ConfigSettings,
+    results: &[FileAnalysis],
+    mut summary: AnalysisSummary,
+    start_time: Instant,
+) -> Result<ExitCode> {
+    if config.check {
+        summary.failed_files = results
+            .iter()
+            .filter(|result| fails_check(config, result))
+            .count();
+    }
+    summary.total_duration = start_time.elapsed();
+
+    match config.format {
+        OutputFormat::Json => print_json_report(results, &summary, config),
+        OutputFormat::Ndjson => print_ndjson_summary(&summary, config),
+        OutputFormat::Sarif => print_sarif_report(results, config),
+        OutputFormat::Junit => print!("{}", junit_report(results, &summary, config)),
+        OutputFormat::Checkstyle => print!("{}", checkstyle_report(results, config)),
+        OutputFormat::Text => {}
+    }
+
+    if summary.error_files > 0 {
+        return Err(anyhow::anyhow!(
+            "  Files with errors: {}",
+            summary.error_files
+        ));
+    }
+
+    Ok(check_exit_code(&summary))
+}
+
+/// Exit code for a completed run, failing if check mode found violations
+fn check_exit_code(summary: &AnalysisSummary) -> ExitCode {
+    if summary.failed_files > 0 {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+fn print_summary(summary: &AnalysisSummary) {
     println!("\n--- Summary ---");
-    println!("Total files processed: {analyzed_files}");
-    if binary_files > 0 {
-        println!("Binary files skipped: {binary_files}");
+    println!("Total files processed: {}", summary.analyzed_files);
+    if summary.binary_files > 0 {
+        println!("Binary files skipped: {}", summary.binary_files);
     }
-    if mixed_files > 0 {
-        println!("Files with mixed line endings: {mixed_files}");
+    if summary.mixed_files > 0 {
+        println!("Files with mixed line endings: {}", summary.mixed_files);
     }
-    println!("Total LF line endings: {total_lf}");
-    println!("Total CRLF line endings: {total_crlf}");
-    println!("Analysis time: {:.3}s", analysis_duration.as_secs_f64());
-    println!("Total time: {:.3}s", total_duration.as_secs_f64());
+    println!("Total LF line endings: {}", summary.total_lf);
+    println!("Total CRLF line endings: {}", summary.total_crlf);
+    if summary.total_cr > 0 {
+        println!("Total CR line endings: {}", summary.total_cr);
+    }
+    println!(
+        "Analysis time: {:.3}s",
+        summary.analysis_duration.as_secs_f64()
+    );
+    println!("Total time: {:.3}s", summary.total_duration.as_secs_f64());
 }
@@ -0,0 +1,4 @@
+//! Memory mapping for large files, enabled by the `mmap` feature. The mapping itself lives
+//! in the `line_endings_mmap` crate, so that this crate has no `unsafe` code.
+
+pub use line_endings_mmap::{MMAP_THRESHOLD, map_large_file};
@@ -0,0 +1,273 @@
+use anyhow::Result;
+use rayon::prelude::*;
+use similar::{Algorithm, DiffTag, capture_diff_slices, group_diff_ops};
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::chunks::PARALLEL_THRESHOLD;
+use crate::processing::{apply_fix, plan_fix};
+use crate::types::{ConfigSettings, FileAnalysis, PlannedFix, TextEncoding};
+
+// Unchanged lines shown around each change in a diff
+const CONTEXT_LINES: usize = 3;
+const LF: u32 = 0x0A;
+const CR: u32 = 0x0D;
+const BOM_CHAR: char = '\u{FEFF}';
+
+/// Describes a planned fix for the dry run report, such as
+/// `rewrite 3 line ending(s) as LF, remove UTF-8 BOM`
+#[must_use]
+pub fn describe_plan(result: &FileAnalysis, plan: &PlannedFix) -> String {
+    let mut changes = Vec::new();
+
+    if let Some(ending) = plan.line_ending {
+        changes.push(format!(
+            "rewrite {} line ending(s) as {ending}",
+            plan.terminators
+        ));
+    }
+    if let Some(bom) = plan.remove_bom {
+        changes.push(format!("remove {bom} BOM"));
+    }
+    if let Some(bom) = plan.kept_bom {
+        changes.push(format!("keep {bom} BOM that {bom} text needs"));
+    }
+    if plan.add_bom {
+        changes.push("add UTF-8 BOM".to_string());
+    }
+    if let Some(fixes) = plan.whitespace {
+        if fixes.trim_trailing_whitespace && result.trailing_whitespace_lines > 0 {
+            changes.push(format!(
+                "trim trailing whitespace from {} line(s)",
+                result.trailing_whitespace_lines
+            ));
+        }
+        match (fixes.final_newline, result.final_newline) {
+            (Some(true), Some(false)) => changes.push("add final newline".to_string()),
+            (Some(false), Some(true)) => changes.push("remove final newline".to_string()),
+            _ => {}
+        }
+    }
+
+    changes.join(", ")
+}
+
+/// Splits content into lines, each keeping its LF, CRLF or lone CR terminator
+fn split_lines(data: &[u8], encoding: TextEncoding) -> Vec<&[u8]> {
+    let width = encoding.code_unit_width();
+    let unit_at = |offset: usize| {
+        data.get(offset..offset + width)
+            .map(|unit| encoding.decode_unit(unit))
+    };
+
+    let mut lines = Vec::new();
+    let mut start = 0;
+    let mut offset = 0;
+    while let Some(unit) = unit_at(offset) {
+        offset += width;
+        if unit == LF || (unit == CR && unit_at(offset) != Some(LF)) {
+            lines.push(&data[start..offset]);
+            start = offset;
+        }
+    }
+    if start < data.len() {
+        lines.push(&data[start..]);
+    }
+    lines
+}
+
+/// Decodes a line for display, without its LF. Carriage returns are shown as `\r` and a BOM
+/// as `<BOM>`, so changes to them are visible. Returns the text and whether the line ended
+/// with LF.
+fn render_line(line: &[u8], encoding: TextEncoding) -> (String, bool) {
+    let width = encoding.code_unit_width();
+    let units: Vec<u32> = line
+        .chunks_exact(width)
+        .map(|unit| encoding.decode_unit(unit))
+        .collect();
+    let ends_with_lf = units.last() == Some(&LF);
+    let units = if ends_with_lf {
+        &units[..units.len() - 1]
+    } else {
+        &units[..]
+    };
+
+    let text: String = match encoding {
+        TextEncoding::Utf8 => {
+            let bytes: Vec<u8> = units.iter().map(|&unit| unit.to_le_bytes()[0]).collect();
+            String::from_utf8_lossy(&bytes).into_owned()
+        }
+        TextEncoding::Utf16Le | TextEncoding::Utf16Be => char::decode_utf16(
+            units
+                .iter()
+                .map(|&unit| u16::try_from(unit).unwrap_or(u16::MAX)),
+        )
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect(),
+        TextEncoding::Utf32Le | TextEncoding::Utf32Be => units
+            .iter()
+            .map(|&unit| char::from_u32(unit).unwrap_or(char::REPLACEMENT_CHARACTER))
+            .collect(),
+    };
+
+    let mut rendered = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\r' => rendered.push_str("\\r"),
+            BOM_CHAR => rendered.push_str("<BOM>"),
+            _ => rendered.push(c),
+        }
+    }
+    (rendered, ends_with_lf)
+}
+
+/// Formats one side of a hunk header, which counts lines from 1 and gives the line before an
+/// empty range
+fn hunk_range(start: usize, len: usize) -> String {
+    match len {
+        0 => format!("{start},0"),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{len}", start + 1),
+    }
+}
+
+/// Appends the lines of one side of a hunk with their `-`, `+` or space prefix. Lines that
+/// do not end with LF are followed by a `\` marker line saying why.
+fn push_lines(diff: &mut String, prefix: char, lines: &[&[u8]], encoding: TextEncoding) {
+    let width = encoding.code_unit_width();
+    for line in lines {
+        let (text, ends_with_lf) = render_line(line, encoding);
+        let _ = writeln!(diff, "{prefix}{text}");
+        if !ends_with_lf {
+            let last_unit = line
+                .len()
+                .checked_sub(width)
+                .map(|start| encoding.decode_unit(&line[start..]));
+            if last_unit == Some(CR) {
+                diff.push_str("\\ Line ends with a lone CR\n");
+            } else {
+                diff.push_str("\\ No newline at end of file\n");
+            }
+        }
+    }
+}
+
+/// Renders the changes between two versions of a file as a unified diff, comparing the raw
+/// bytes of each line so that line ending changes show up
+#[must_use]
+pub fn unified_diff(path: &Path, old: &[u8], new: &[u8], encoding: TextEncoding) -> String {
+    let old_lines = split_lines(old, encoding);
+    let new_lines = split_lines(new, encoding);
+    let ops = capture_diff_slices(Algorithm::Myers, &old_lines, &new_lines);
+
+    let mut diff = format!("--- {0}\n+++ {0}\n", path.display());
+    for hunk in group_diff_ops(ops, CONTEXT_LINES) {
+        let (Some(first), Some(last)) = (hunk.first(), hunk.last()) else {
+            continue;
+        };
+        let old_range = first.old_range().start..last.old_range().end;
+        let new_range = first.new_range().start..last.new_range().end;
+        let _ = writeln!(
+            diff,
+            "@@ -{} +{} @@",
+            hunk_range(old_range.start, old_range.len()),
+            hunk_range(new_range.start, new_range.len())
+        );
+
+        for op in &hunk {
+            let (tag, old_range, new_range) = op.as_tag_tuple();
+            let removed = &old_lines[old_range];
+            let added = &new_lines[new_range];
+            match tag {
+                DiffTag::Equal => push_lines(&mut diff, ' ', removed, encoding),
+                DiffTag::Delete | DiffTag::Insert | DiffTag::Replace => {
+                    push_lines(&mut diff, '-', removed, encoding);
+                    push_lines(&mut diff, '+', added, encoding);
+                }
+            }
+        }
+    }
+    diff
+}
+
+/// Reads a file and renders the diff of a planned fix. Files large enough to be fixed in
+/// chunks are not diffed, as the diff holds several copies of the file in memory.
+fn file_diff(result: &FileAnalysis, plan: &PlannedFix) -> io::Result<String> {
+    if result.size >= PARALLEL_THRESHOLD {
+        return Ok(format!("file too large to diff ({} bytes)\n", result.size));
+    }
+
+    let original = fs::read(&result.path)?;
+    let mut fixed = Vec::with_capacity(original.len() + original.len() / 8);
+    apply_fix(original.as_slice(), &mut fixed, plan, result.encoding)?;
+    Ok(unified_diff(
+        &result.path,
+        &original,
+        &fixed,
+        result.encoding,
+    ))
+}
+
+/// Reports the changes the fix options would make to each file without modifying anything,
+/// with a unified diff of each file when `--diff` is given. Returns the number of files that
+/// would change.
+///
+/// # Errors
+///
+/// Returns an error if a file cannot be read for its diff.
+pub fn preview_fixes(config: &ConfigSettings, results: &[FileAnalysis]) -> Result<usize> {
+    println!();
+
+    // Plan and diff files in parallel, then print them in order
+    let previews: Vec<(PlannedFix, Option<io::Result<String>>)> = results
+        .par_iter()
+        .map(|result| {
+            let plan = config
+                .policy_for(&result.path)
+                .map(|policy| plan_fix(result, &policy))
+                .unwrap_or_default();
+            let diff = (config.diff && !plan.is_empty()).then(|| file_diff(result, &plan));
+            (plan, diff)
+        })
+        .collect();
+
+    let mut changed_files = 0usize;
+    let mut errors: Vec<String> = Vec::new();
+
+    for (result, (plan, diff)) in results.iter().zip(&previews) {
+        // Kept BOMs are reported as fixing does, though the file is left unchanged
+        if plan.is_empty() && plan.kept_bom.is_none() {
+            continue;
+        }
+        println!(
+            "\"{}\"\twould {}",
+            result.path.display(),
+            describe_plan(result, plan)
+        );
+        if !plan.is_empty() {
+            changed_files += 1;
+        }
+
+        match diff {
+            Some(Ok(diff)) => print!("{diff}"),
+            Some(Err(e)) => errors.push(format!(
+                "Failed to read {} for its diff: {e}",
+                result.path.display()
+            )),
+            None => {}
+        }
+    }
+
+    println!(
+        "Dry run: would change {changed_files} file(s), {} unchanged",
+        results.len() - changed_files
+    );
+
+    if errors.is_empty() {
+        Ok(changed_files)
+    } else {
+        Err(anyhow::anyhow!("{}", errors.join("\n")))
+    }
+}
@@ -1,122 +1,39 @@
 use anyhow::Result;
 use rayon::prelude::*;
 use std::fs::File;
-use std::io::{self, BufRead, BufReader, Read, Seek, Write};
+use std::io::{self, Read, Seek, SeekFrom, Write};
 use std::path::Path;
 use tempfile::NamedTempFile;
 
+use crate::analysis::{analyze_bytes, bom_from_bytes, sniff_stream};
+use crate::chunks::{
+    CHUNK_SIZE, CHUNKS_IN_FLIGHT, ChunkReader, PARALLEL_THRESHOLD, batch_chunks,
+    line_aligned_chunks,
+};
 use crate::types::{
-    BomRemovalResult, BomType, ConfigSettings, FileAnalysis, LineEnding, LineEndingTarget,
-    RewriteResult,
+    BomType, ConfigSettings, FileAnalysis, FilePolicy, FixResult, LineEnding, PlannedFix,
+    TextEncoding, WhitespaceFixes,
 };
+use crate::utils::for_each_code_unit;
 
 // Define constants for line ending characters and buffer size
 const BUFFER_SIZE: usize = 4096; // 4KB buffer for more efficient reading
-
-/// Rewrites files with specified line endings based on the configuration settings.
-///
-/// # Errors
-///
-/// Returns an error if no rewrite option is set or if file rewriting fails.
-pub fn rewrite_files(config: &ConfigSettings, results: &[FileAnalysis]) -> Result<()> {
-    // error out if no rewrite option is set
-    if !config.has_rewrite_option() {
-        return Err(anyhow::anyhow!("No line ending rewrite option set"));
-    }
-
-    let ending = match config.line_ending_target {
-        LineEndingTarget::Linux => LineEnding::Lf,
-        LineEndingTarget::Windows => LineEnding::Crlf,
-        LineEndingTarget::None => {
-            return Err(anyhow::anyhow!("No line ending rewrite option set"));
-        }
-    };
-
-    println!();
-
-    // Process files in parallel using rayon
-    let rewrite_results: Vec<RewriteResult> = results
-        .par_iter()
-        .map(|result| process_file_for_rewrite(result, config, ending))
-        .collect();
-
-    // Process results sequentially for consistent output and counting
-    let mut rewritten_files = 0usize;
-    let mut skipped_files = 0usize;
-    let mut errors: Vec<String> = Vec::new();
-
-    for rewrite_result in &rewrite_results {
-        if let Some(error) = &rewrite_result.error {
-            errors.push(format!(
-                "Failed to rewrite file: {}: {}",
-                rewrite_result.path.display(),
-                error
-            ));
-        } else if rewrite_result.rewritten {
-            println!("\"{}\"\trewritten", rewrite_result.path.display());
-            rewritten_files += 1;
-        } else {
-            println!("\"{}\"\trewrite skipped", rewrite_result.path.display());
-            skipped_files += 1;
-        }
-    }
-
-    println!(
-        "Rewritten {} file(s) with {} line endings, skipped {}",
-        rewritten_files,
-        match ending {
-            LineEnding::Lf => "Linux (LF)",
-            LineEnding::Crlf => "Windows (CRLF)",
-        },
-        skipped_files
-    );
-
-    if errors.is_empty() {
-        Ok(())
-    } else {
-        Err(anyhow::anyhow!("{}", errors.join("\n")))
-    }
-}
-
-/// Processes a single file for rewriting based on configuration and line ending analysis
-#[must_use]
-pub fn process_file_for_rewrite(
-    result: &FileAnalysis,
-    config: &ConfigSettings,
-    ending: LineEnding,
-) -> RewriteResult {
-    let mut rebuild = false;
-
-    if result.has_mixed_line_endings() {
-        // mixed line endings, always rebuild
-        rebuild = true;
-    }
-    if (config.line_ending_target == LineEndingTarget::Linux && result.is_crlf_only())
-        || (config.line_ending_target == LineEndingTarget::Windows && result.is_lf_only())
-    {
-        // rebuild if its exclusively the wrong type
-        rebuild = true;
-    }
-
-    if rebuild {
-        match rewrite_file_with_line_ending(&result.path, ending) {
-            Ok(()) => RewriteResult {
-                path: result.path.clone(),
-                rewritten: true,
-                error: None,
-            },
-            Err(e) => RewriteResult {
-                path: result.path.clone(),
-                rewritten: false,
-                error: Some(e.to_string()),
-            },
-        }
+const LF: u32 = 0x0A;
+const CR: u32 = 0x0D;
+const SPACE: u32 = 0x20;
+const TAB: u32 = 0x09;
+const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+
+/// Describes the line endings files are rewritten with, for the summaries
+fn target_description(config: &ConfigSettings) -> &'static str {
+    if config.gitattributes.is_some() || config.editorconfig.is_some() || config.project.is_some() {
+        "per-file"
     } else {
-        // file is already in the correct format, skip it
-        RewriteResult {
-            path: result.path.clone(),
-            rewritten: false,
-            error: None,
+        match config.line_ending_target.line_ending() {
+            Some(LineEnding::Lf) => "Linux (LF)",
+            Some(LineEnding::Crlf) => "Windows (CRLF)",
+            Some(LineEnding::Cr) => "Classic Mac (CR)",
+            None => "unchanged",
         }
     }
 }
@@ -150,122 +67,507 @@ fn get_backup_path(input_path: &Path) -> std::path::PathBuf {
 /// Creates a backup of the original file with .BAK extension (if not already created) and
 /// replaces the original file with the new version.
 ///
+/// The file is processed as a stream of code units in the given encoding, so content that
+/// is not valid UTF-8 (Latin-1, Windows-1252 etc.) is copied through unchanged, and UTF-16/32
+/// files keep their BOM and code unit width. Only line terminators are replaced.
+///
 /// # Errors
 ///
 /// Returns an error if file operations (backup creation, reading, writing, or renaming) fail.
-pub fn rewrite_file_with_line_ending(input_path: &Path, ending: LineEnding) -> io::Result<()> {
-    // Create backup if needed
-    create_backup_if_needed(input_path)?;
+pub fn rewrite_file_with_line_ending(
+    input_path: &Path,
+    ending: LineEnding,
+    encoding: TextEncoding,
+) -> io::Result<()> {
+    let fix = PlannedFix {
+        line_ending: Some(ending),
+        ..PlannedFix::default()
+    };
+    rewrite_file_with_fix(input_path, &fix, encoding)
+}
 
-    // Create temporary file in the same directory as the input file
-    let parent = input_path.parent().unwrap_or_else(|| Path::new(""));
-    let mut temp_file = NamedTempFile::new_in(parent)?;
+/// Copies bytes from `reader` to `writer`, replacing every LF, CRLF and lone CR terminator
+/// with `ending`. All other bytes, including invalid UTF-8 sequences, are copied unchanged.
+/// Returns the number of line terminators written.
+///
+/// # Errors
+///
+/// Returns an error if reading from the reader or writing to the writer fails.
+pub fn convert_line_endings<R: Read, W: Write>(
+    reader: R,
+    writer: W,
+    ending: LineEnding,
+) -> io::Result<usize> {
+    convert_line_endings_with_encoding(reader, writer, ending, TextEncoding::Utf8)
+}
 
-    // Check if file ends with a newline by reading only the last byte
-    let has_trailing_newline = check_trailing_newline(input_path)?;
+/// Copies code units from `reader` to `writer`, replacing every LF, CRLF and lone CR
+/// terminator with `ending` encoded in the same encoding. All other code units, and any
+/// incomplete trailing code unit, are copied unchanged.
+/// Returns the number of line terminators written.
+///
+/// # Errors
+///
+/// Returns an error if reading from the reader or writing to the writer fails.
+pub fn convert_line_endings_with_encoding<R: Read, W: Write>(
+    reader: R,
+    writer: W,
+    ending: LineEnding,
+    encoding: TextEncoding,
+) -> io::Result<usize> {
+    fix_lines(reader, writer, Some(ending), None, encoding)
+}
 
-    // Process file line by line without loading into memory
-    let infile = File::open(input_path)?;
-    let reader = BufReader::with_capacity(BUFFER_SIZE, infile);
+/// Converts content to the form stored in the repository, for a git clean filter: LF line
+/// endings and no UTF-8 BOM. Binary content, and files marked `binary` or `-text` in
+/// `.gitattributes`, are returned unchanged.
+///
+/// # Errors
+///
+/// Returns an error if conversion fails.
+pub fn clean_content(path: &Path, data: &[u8], config: &ConfigSettings) -> io::Result<Vec<u8>> {
+    if config.policy_for(path).is_none() {
+        return Ok(data.to_vec());
+    }
+    let analysis = analyze_bytes(path, data, config);
+    if analysis.is_binary {
+        return Ok(data.to_vec());
+    }
 
-    let line_ending: &[u8] = match ending {
-        LineEnding::Lf => &b"\n"[..],
-        LineEnding::Crlf => &b"\r\n"[..],
+    let body = match analysis.bom_type {
+        Some(BomType::Utf8) => &data[UTF8_BOM.len()..],
+        _ => data,
     };
+    let mut output = Vec::with_capacity(data.len());
+    convert_line_endings_with_encoding(body, &mut output, LineEnding::Lf, analysis.encoding)?;
+    Ok(output)
+}
 
-    let mut lines = reader.lines();
-    let mut last_line: Option<String> = None;
+/// Converts content from the repository to the form checked out, for a git smudge filter:
+/// the line ending of the file's policy, with a BOM added or removed as the policy requires.
+/// Content is returned unchanged where there is no policy or it is binary.
+///
+/// # Errors
+///
+/// Returns an error if conversion fails.
+pub fn smudge_content(path: &Path, data: &[u8], config: &ConfigSettings) -> io::Result<Vec<u8>> {
+    let Some(policy) = config.policy_for(path) else {
+        return Ok(data.to_vec());
+    };
 
-    // Process all lines except the last
-    for line in lines.by_ref() {
-        if let Some(prev_line) = last_line.take() {
-            temp_file.write_all(prev_line.as_bytes())?;
-            temp_file.write_all(line_ending)?;
+    // Leave some room for CRLF endings and a BOM
+    let mut output = Vec::with_capacity(data.len() + data.len() / 8 + UTF8_BOM.len());
+    convert_stream(data, &mut output, &policy)?;
+    Ok(output)
+}
+
+/// Copies `reader` to `writer`, skipping the first `bom_size` bytes
+///
+/// # Errors
+///
+/// Returns an error if the stream is shorter than the BOM, or reading or writing fails.
+pub fn copy_without_bom<R: Read, W: Write>(
+    mut reader: R,
+    mut writer: W,
+    bom_size: usize,
+) -> io::Result<()> {
+    // Skip the BOM
+    let mut bom_buffer = vec![0; bom_size];
+    reader.read_exact(&mut bom_buffer)?;
+
+    // Copy the rest directly
+    let mut copy_buffer = [0; BUFFER_SIZE];
+    loop {
+        let bytes_read = reader.read(&mut copy_buffer)?;
+        if bytes_read == 0 {
+            break;
         }
-        last_line = Some(line?);
+        writer.write_all(&copy_buffer[..bytes_read])?;
+    }
+    writer.flush()
+}
+
+/// Copies a stream, such as stdin, applying the line ending and BOM rules of `policy` without
+/// temporary files or backups. The encoding and any BOM are detected from the start of the
+/// stream, and binary content is copied unchanged.
+///
+/// # Errors
+///
+/// Returns an error if reading or writing fails.
+pub fn convert_stream<R: Read, W: Write>(
+    mut reader: R,
+    mut writer: W,
+    policy: &FilePolicy,
+) -> io::Result<()> {
+    let (head, encoding, is_binary) = sniff_stream(&mut reader)?;
+    if is_binary {
+        return copy_without_bom(head.as_slice().chain(reader), writer, 0);
     }
 
-    // Write the last line, adding line ending only if original had trailing newline
-    if let Some(line) = last_line {
-        temp_file.write_all(line.as_bytes())?;
-        if has_trailing_newline {
-            temp_file.write_all(line_ending)?;
+    let bom_type = bom_from_bytes(&head);
+    let bom_size = match bom_type {
+        Some(bom_type) if policy.forbid_bom && bom_type.is_removable() => bom_type.size(),
+        _ => 0,
+    };
+    if bom_type.is_none() && policy.require_bom && encoding == TextEncoding::Utf8 {
+        writer.write_all(UTF8_BOM)?;
+    }
+
+    // The bytes read to detect the encoding come first, and hold the whole of any BOM
+    match policy.line_ending {
+        Some(ending) => {
+            let stream = head[bom_size..].chain(reader);
+            convert_line_endings_with_encoding(stream, writer, ending, encoding).map(|_| ())
         }
+        None => copy_without_bom(head.as_slice().chain(reader), writer, bom_size),
     }
+}
 
-    // Ensure all data is written before replacing files
-    temp_file.flush()?;
+/// Returns true if a BOM can be added to a file: UTF-8 text checked for a BOM and without one.
+/// UTF-16/32 files are reported by the encoding check instead.
+#[must_use]
+pub fn needs_bom(result: &FileAnalysis) -> bool {
+    !result.is_binary
+        && result.error.is_none()
+        && result.bom_checked
+        && result.bom_type.is_none()
+        && result.encoding == TextEncoding::Utf8
+}
 
-    // Atomically replace the original file with the temp file
-    temp_file.persist(input_path)?;
+/// The whitespace fixes a policy asks for. A final newline added to a file without a line
+/// ending rule uses the file's most common line ending.
+#[must_use]
+pub fn whitespace_fixes(result: &FileAnalysis, policy: &FilePolicy) -> WhitespaceFixes {
+    WhitespaceFixes {
+        trim_trailing_whitespace: policy.trim_trailing_whitespace,
+        final_newline: policy.final_newline,
+        ending: policy
+            .line_ending
+            .unwrap_or_else(|| predominant_line_ending(result)),
+    }
+}
 
-    Ok(())
+/// Returns true if the analysis shows trailing whitespace to trim or a final newline to add
+/// or remove
+#[must_use]
+pub fn needs_whitespace_fixes(result: &FileAnalysis, fixes: WhitespaceFixes) -> bool {
+    let needs_trim = fixes.trim_trailing_whitespace && result.trailing_whitespace_lines > 0;
+    let needs_final_newline = matches!(
+        (fixes.final_newline, result.final_newline),
+        (Some(true), Some(false)) | (Some(false), Some(true))
+    );
+    !result.is_binary && result.error.is_none() && (needs_trim || needs_final_newline)
 }
 
-/// Checks if a file ends with a newline without reading the entire file
-fn check_trailing_newline(path: &Path) -> io::Result<bool> {
-    let mut file = File::open(path)?;
-    let file_size = file.metadata()?.len();
+/// The most common line ending in a file, LF for files without any
+fn predominant_line_ending(result: &FileAnalysis) -> LineEnding {
+    if result.crlf_count > result.lf_count && result.crlf_count >= result.cr_count {
+        LineEnding::Crlf
+    } else if result.cr_count > result.lf_count && result.cr_count > result.crlf_count {
+        LineEnding::Cr
+    } else {
+        LineEnding::Lf
+    }
+}
+
+/// Copies code units from `reader` to `writer`, trimming spaces and tabs before each
+/// terminator and adding or removing the final newline. Line terminators themselves are
+/// copied unchanged, and so is any incomplete trailing code unit.
+///
+/// # Errors
+///
+/// Returns an error if reading from the reader or writing to the writer fails.
+pub fn fix_whitespace<R: Read, W: Write>(
+    reader: R,
+    writer: W,
+    fixes: WhitespaceFixes,
+    encoding: TextEncoding,
+) -> io::Result<()> {
+    fix_lines(reader, writer, None, Some(fixes), encoding).map(|_| ())
+}
+
+/// Copies code units from `reader` to `writer` in a single pass, replacing every LF, CRLF
+/// and lone CR terminator with `ending` when one is given, and applying `whitespace` fixes
+/// when they are given. All other code units, and any incomplete trailing code unit, are
+/// copied unchanged. Returns the number of line terminators read.
+///
+/// # Errors
+///
+/// Returns an error if reading from the reader or writing to the writer fails.
+pub fn fix_lines<R: Read, W: Write>(
+    mut reader: R,
+    mut writer: W,
+    ending: Option<LineEnding>,
+    whitespace: Option<WhitespaceFixes>,
+    encoding: TextEncoding,
+) -> io::Result<usize> {
+    let mut fixer = LineFixer::new(ending, whitespace, encoding);
+
+    let trailing = for_each_code_unit(
+        &mut reader,
+        encoding.code_unit_width(),
+        BUFFER_SIZE,
+        |unit| {
+            fixer.push(unit);
+            if fixer.output.len() >= BUFFER_SIZE {
+                writer.write_all(&fixer.output)?;
+                fixer.output.clear();
+            }
+            Ok(())
+        },
+    )?;
+
+    let terminators = fixer.finish(&trailing);
+    writer.write_all(&fixer.output)?;
+    writer.flush()?;
+    Ok(terminators)
+}
+
+/// The code units of a line ending
+fn terminator_units(ending: LineEnding) -> &'static [u32] {
+    match ending {
+        LineEnding::Lf => &[LF],
+        LineEnding::Crlf => &[CR, LF],
+        LineEnding::Cr => &[CR],
+    }
+}
+
+/// Line ending conversion and whitespace fixes, fed one code unit at a time
+struct LineFixer {
+    encoding: TextEncoding,
+    /// Terminator every terminator is replaced with, `None` to keep each as it is
+    ending: Option<LineEnding>,
+    /// Whitespace fixes, which change nothing when none were asked for
+    fixes: WhitespaceFixes,
+    output: Vec<u8>,
+    terminators: usize,
+    /// A CR at the end of one read may be the first half of a CRLF split across reads
+    prev_was_cr: bool,
+    /// Spaces and tabs held back until we know whether they end a line
+    pending_blanks: Vec<u8>,
+    /// Terminators held back until we know whether they end the file
+    pending_terminators: Vec<u8>,
+    last_was_terminator: bool,
+    wrote_any: bool,
+}
 
-    if file_size == 0 {
-        return Ok(false);
+impl LineFixer {
+    fn new(
+        ending: Option<LineEnding>,
+        whitespace: Option<WhitespaceFixes>,
+        encoding: TextEncoding,
+    ) -> Self {
+        LineFixer {
+            encoding,
+            ending,
+            fixes: whitespace.unwrap_or(WhitespaceFixes {
+                trim_trailing_whitespace: false,
+                final_newline: None,
+                ending: LineEnding::Lf,
+            }),
+            output: Vec::with_capacity(BUFFER_SIZE * 2),
+            terminators: 0,
+            prev_was_cr: false,
+            pending_blanks: Vec::new(),
+            pending_terminators: Vec::new(),
+            last_was_terminator: false,
+            wrote_any: false,
+        }
     }
 
-    // Seek to the last byte
-    file.seek(io::SeekFrom::End(-1))?;
-    let mut last_byte = [0u8; 1];
-    file.read_exact(&mut last_byte)?;
+    fn push(&mut self, unit: &[u8]) {
+        self.wrote_any = true;
+        match self.encoding.decode_unit(unit) {
+            LF => {
+                let found = if std::mem::take(&mut self.prev_was_cr) {
+                    LineEnding::Crlf
+                } else {
+                    LineEnding::Lf
+                };
+                self.end_line(found);
+            }
+            CR => {
+                if self.prev_was_cr {
+                    // The previous CR was a lone terminator
+                    self.end_line(LineEnding::Cr);
+                }
+                // Whitespace before any CR is trailing, whether or not LF follows
+                self.pending_blanks.clear();
+                self.prev_was_cr = true;
+            }
+            decoded => {
+                if std::mem::take(&mut self.prev_was_cr) {
+                    self.end_line(LineEnding::Cr);
+                }
+                if self.fixes.trim_trailing_whitespace && (decoded == SPACE || decoded == TAB) {
+                    self.pending_blanks.extend_from_slice(unit);
+                } else {
+                    self.output.append(&mut self.pending_terminators);
+                    self.output.append(&mut self.pending_blanks);
+                    self.output.extend_from_slice(unit);
+                    self.last_was_terminator = false;
+                }
+            }
+        }
+    }
 
-    Ok(last_byte[0] == b'\n')
+    /// Writes a terminator, dropping the whitespace before it. Terminators are held back
+    /// while the final newline is being removed, in case they end the file.
+    fn end_line(&mut self, found: LineEnding) {
+        self.pending_blanks.clear();
+        let out = if self.fixes.final_newline == Some(false) {
+            &mut self.pending_terminators
+        } else {
+            &mut self.output
+        };
+        for &unit in terminator_units(self.ending.unwrap_or(found)) {
+            self.encoding.encode_unit(unit, out);
+        }
+        self.terminators += 1;
+        self.last_was_terminator = true;
+    }
+
+    /// Ends the last line and appends any incomplete trailing code unit, returning the
+    /// number of terminators read. Whitespace and terminators still held back end the
+    /// file, so they are dropped.
+    fn finish(&mut self, trailing: &[u8]) -> usize {
+        // A CR as the very last code unit is a lone terminator
+        if std::mem::take(&mut self.prev_was_cr) {
+            self.end_line(LineEnding::Cr);
+        }
+        if self.fixes.final_newline == Some(true) && self.wrote_any && !self.last_was_terminator {
+            for &unit in terminator_units(self.fixes.ending) {
+                self.encoding.encode_unit(unit, &mut self.output);
+            }
+        }
+        self.output.extend_from_slice(trailing);
+        self.terminators
+    }
 }
 
-/// Removes BOMs from files based on the file analysis
+/// Works out from its analysis what fixing a file to match `policy` would change, using the
+/// same checks as the separate rewrite, BOM and whitespace fixes
+#[must_use]
+pub fn plan_fix(result: &FileAnalysis, policy: &FilePolicy) -> PlannedFix {
+    if result.is_binary || result.error.is_some() {
+        return PlannedFix::default();
+    }
+
+    let line_ending = policy
+        .line_ending
+        .filter(|&ending| result.has_line_endings_other_than(ending));
+    let terminators = match line_ending {
+        Some(LineEnding::Lf) => result.crlf_count + result.cr_count,
+        Some(LineEnding::Crlf) => result.lf_count + result.cr_count,
+        Some(LineEnding::Cr) => result.lf_count + result.crlf_count,
+        None => 0,
+    };
+    let fixes = whitespace_fixes(result, policy);
+    let forbidden_bom = result.bom_type.filter(|_| policy.forbid_bom);
+
+    PlannedFix {
+        line_ending,
+        terminators,
+        remove_bom: forbidden_bom.filter(|bom_type| bom_type.is_removable()),
+        kept_bom: forbidden_bom.filter(|bom_type| !bom_type.is_removable()),
+        add_bom: policy.require_bom && needs_bom(result),
+        whitespace: needs_whitespace_fixes(result, fixes).then_some(fixes),
+    }
+}
+
+/// Fixes files to match their policies, applying every fix a file needs in one pass so each
+/// file is read, rewritten and persisted once
 ///
 /// # Errors
 ///
-/// Returns an error if BOM detection is not enabled or if BOM removal fails.
-pub fn remove_bom_from_files(config: &ConfigSettings, results: &[FileAnalysis]) -> Result<()> {
-    // Make sure we're only processing files that have been checked for BOMs
-    if !config.check_bom {
-        return Err(anyhow::anyhow!(
-            "BOM detection must be enabled (--bom) to remove BOMs"
-        ));
+/// Returns an error naming every file that could not be fixed.
+pub fn fix_files(config: &ConfigSettings, results: &[FileAnalysis]) -> Result<()> {
+    // The rules in .line-endings.toml are part of every policy, but only fixed when asked
+    if !config.has_fix_option() {
+        return Ok(());
     }
 
     println!();
 
-    // Process files in parallel using rayon
-    let removal_results: Vec<BomRemovalResult> = results
+    // Plan and fix files in parallel, with no plan for files marked binary or -text
+    // in .gitattributes
+    let fix_results: Vec<FixResult> = results
         .par_iter()
-        .map(process_file_for_bom_removal)
+        .map(|result| {
+            let fix = config
+                .policy_for(&result.path)
+                .map(|policy| plan_fix(result, &policy))
+                .unwrap_or_default();
+            process_file_for_fix(result, fix)
+        })
         .collect();
 
     // Process results sequentially for consistent output and counting
+    let mut rewritten_files = 0usize;
     let mut bom_removed = 0usize;
-    let mut files_skipped = 0usize;
+    let mut bom_added = 0usize;
+    let mut whitespace_fixed = 0usize;
     let mut errors: Vec<String> = Vec::new();
 
-    for removal_result in &removal_results {
-        if let Some(error) = &removal_result.error {
-            errors.push(format!(
-                "Failed to remove BOM from {}: {}",
-                removal_result.path.display(),
-                error
-            ));
-        } else if removal_result.removed {
-            if let Some(bom_type) = removal_result.bom_type {
-                println!(
-                    "\"{}\"\tBOM removed: {bom_type}",
-                    removal_result.path.display()
-                );
-            }
+    for fix_result in &fix_results {
+        let path = fix_result.path.display();
+        let fix = &fix_result.fix;
+        if let Some(error) = &fix_result.error {
+            errors.push(format!("Failed to fix file: {path}: {error}"));
+            continue;
+        }
+
+        let mut changes = Vec::new();
+        if fix.line_ending.is_some() {
+            changes.push("rewritten".to_string());
+            rewritten_files += 1;
+        } else if config.has_rewrite_option() {
+            changes.push("rewrite skipped".to_string());
+        }
+        if let Some(bom_type) = fix.remove_bom {
+            changes.push(format!("BOM removed: {bom_type}"));
             bom_removed += 1;
-        } else {
-            files_skipped += 1;
+        }
+        if let Some(bom_type) = fix.kept_bom {
+            changes.push(format!("BOM kept: {bom_type} text needs its BOM"));
+        }
+        if fix.add_bom {
+            changes.push("BOM added: UTF-8".to_string());
+            bom_added += 1;
+        }
+        if fix.whitespace.is_some() {
+            changes.push("whitespace fixed".to_string());
+            whitespace_fixed += 1;
+        }
+        if !changes.is_empty() {
+            println!("\"{path}\"\t{}", changes.join(", "));
         }
     }
 
-    println!("BOM removed from {bom_removed} file(s), skipped {files_skipped}");
+    let total = fix_results.len();
+    if config.has_rewrite_option() {
+        println!(
+            "Rewritten {rewritten_files} file(s) with {} line endings, skipped {}",
+            target_description(config),
+            total - rewritten_files
+        );
+    }
+    if config.remove_bom || config.has_per_file_fixes() {
+        println!(
+            "BOM removed from {bom_removed} file(s), skipped {}",
+            total - bom_removed
+        );
+    }
+    if config.has_per_file_fixes() {
+        println!(
+            "BOM added to {bom_added} file(s), skipped {}",
+            total - bom_added
+        );
+        println!(
+            "Fixed whitespace in {whitespace_fixed} file(s), skipped {}",
+            total - whitespace_fixed
+        );
+    }
 
     if errors.is_empty() {
         Ok(())
@@ -274,90 +576,137 @@ pub fn remove_bom_from_files(config: &ConfigSettings, results: &[FileAnalysis])
     }
 }
 
-/// Processes a single file for BOM removal
+/// Fixes a single file, leaving it alone if nothing is planned
 #[must_use]
-pub fn process_file_for_bom_removal(result: &FileAnalysis) -> BomRemovalResult {
-    // Skip binary files, files without BOMs, or files with errors
-    if result.is_binary || result.error.is_some() || !result.has_bom() {
-        return BomRemovalResult {
+pub fn process_file_for_fix(result: &FileAnalysis, fix: PlannedFix) -> FixResult {
+    if fix.is_empty() {
+        return FixResult {
             path: result.path.clone(),
-            removed: false,
-            bom_type: None,
+            fix,
             error: None,
         };
     }
 
-    // Get the BOM type safely using if-let
-    let Some(bom_type) = result.bom_type else {
-        return BomRemovalResult {
-            path: result.path.clone(),
-            removed: false,
-            bom_type: None,
-            error: None,
-        };
-    };
-
-    // Get the size of the BOM to skip
-    let bom_size = match bom_type {
-        BomType::Utf8 => 3,
-        BomType::Utf16Le | BomType::Utf16Be => 2,
-        BomType::Utf32Le | BomType::Utf32Be => 4,
-    };
-
-    // Process the file to remove the BOM
-    match remove_bom_from_file(&result.path, bom_size) {
-        Ok(()) => BomRemovalResult {
-            path: result.path.clone(),
-            removed: true,
-            bom_type: Some(bom_type),
-            error: None,
-        },
-        Err(e) => BomRemovalResult {
-            path: result.path.clone(),
-            removed: false,
-            bom_type: Some(bom_type),
-            error: Some(e.to_string()),
-        },
+    let error = rewrite_file_with_fix(&result.path, &fix, result.encoding)
+        .err()
+        .map(|e| e.to_string());
+    FixResult {
+        path: result.path.clone(),
+        fix,
+        error,
     }
 }
 
-/// Removes a BOM from a file while preserving its content and line endings
+/// Rewrites a file with every planned fix in one pass. Creates a backup of the original file
+/// with a .bak extension (if not already created) and atomically replaces the original with
+/// the new version.
 ///
 /// # Errors
 ///
 /// Returns an error if file operations (backup creation, reading, writing, or renaming) fail.
-pub fn remove_bom_from_file(path: &Path, bom_size: usize) -> io::Result<()> {
-    // Create backup if needed
-    create_backup_if_needed(path)?;
+pub fn rewrite_file_with_fix(
+    input_path: &Path,
+    fix: &PlannedFix,
+    encoding: TextEncoding,
+) -> io::Result<()> {
+    create_backup_if_needed(input_path)?;
 
-    // Create temporary file in the same directory as the input file
-    let parent = path.parent().unwrap_or_else(|| Path::new(""));
+    let parent = input_path.parent().unwrap_or_else(|| Path::new(""));
     let mut temp_file = NamedTempFile::new_in(parent)?;
 
-    // Open the original file for reading
-    let mut input_file = File::open(path)?;
+    let infile = File::open(input_path)?;
+    if infile.metadata()?.len() >= PARALLEL_THRESHOLD {
+        apply_fix_in_chunks(&infile, &mut temp_file, fix, encoding, CHUNK_SIZE)?;
+    } else {
+        apply_fix(infile, &mut temp_file, fix, encoding)?;
+    }
+
+    temp_file.flush()?;
+    temp_file.persist(input_path)?;
 
-    // Skip the BOM
-    let mut bom_buffer = vec![0; bom_size];
-    input_file.read_exact(&mut bom_buffer)?;
+    Ok(())
+}
 
-    // Copy the rest of the file directly (preserving line endings)
-    let mut copy_buffer = [0; BUFFER_SIZE];
-    loop {
-        let bytes_read = input_file.read(&mut copy_buffer)?;
-        if bytes_read == 0 {
-            break;
-        }
-        temp_file.write_all(&copy_buffer[..bytes_read])?;
+/// Copies `reader` to `writer` through a planned fix in a single pass: the BOM is removed
+/// or added, and line terminators are converted and whitespace fixed together by
+/// `fix_lines`.
+///
+/// # Errors
+///
+/// Returns an error if the stream is shorter than the BOM being removed, or reading or
+/// writing fails.
+pub fn apply_fix<R: Read, W: Write>(
+    mut reader: R,
+    mut writer: W,
+    fix: &PlannedFix,
+    encoding: TextEncoding,
+) -> io::Result<()> {
+    if let Some(bom_type) = fix.remove_bom {
+        let mut bom = vec![0; bom_type.size()];
+        reader.read_exact(&mut bom)?;
+    }
+    if fix.add_bom {
+        writer.write_all(UTF8_BOM)?;
     }
 
-    // Ensure all data is written before replacing files
-    temp_file.flush()?;
+    if fix.line_ending.is_none() && fix.whitespace.is_none() {
+        io::copy(&mut reader, &mut writer)?;
+        return writer.flush();
+    }
+    fix_lines(reader, writer, fix.line_ending, fix.whitespace, encoding).map(|_| ())
+}
 
-    // Atomically replace the original file with the temp file
-    temp_file.persist(path)?;
+/// Writes an open file from its start through a planned fix as `apply_fix` does,
+/// converting chunks of about `chunk_size` bytes on separate threads and writing them in
+/// order. Only line ending conversion of single-byte text is split up, since whitespace
+/// fixes depend on the end of the file, so other fixes are streamed by `apply_fix`.
+///
+/// # Errors
+///
+/// Returns an error if the file is shorter than the BOM being removed, or reading or
+/// writing fails.
+pub fn apply_fix_in_chunks<W: Write>(
+    mut file: &File,
+    mut writer: W,
+    fix: &PlannedFix,
+    encoding: TextEncoding,
+    chunk_size: u64,
+) -> io::Result<()> {
+    let (Some(ending), None, TextEncoding::Utf8) = (fix.line_ending, fix.whitespace, encoding)
+    else {
+        file.seek(SeekFrom::Start(0))?;
+        return apply_fix(file, writer, fix, encoding);
+    };
 
-    Ok(())
+    let size = file.metadata()?.len();
+    let start = fix.remove_bom.map_or(0, |bom_type| bom_type.size() as u64);
+    if start > size {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    if fix.add_bom {
+        writer.write_all(UTF8_BOM)?;
+    }
+
+    // Chunks start at the start of a line, so each converts on its own. However many
+    // threads there are, only a batch of `CHUNKS_IN_FLIGHT` chunks is held in memory.
+    let chunks = line_aligned_chunks(&mut file, start, size, chunk_size)?;
+    let max_batch_bytes = chunk_size.max(1).saturating_mul(CHUNKS_IN_FLIGHT);
+    for batch in batch_chunks(&chunks, max_batch_bytes) {
+        let converted = batch
+            .par_iter()
+            .map(|range| {
+                let len = usize::try_from(range.end - range.start).unwrap_or(0);
+                let mut output = Vec::with_capacity(len + len / 8);
+                let chunk = ChunkReader::new(file, range);
+                convert_line_endings_with_encoding(chunk, &mut output, ending, encoding)?;
+                Ok(output)
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        for output in converted {
+            writer.write_all(&output)?;
+        }
+    }
+    writer.flush()
 }
 
 /// Deletes backup files for the given file analyses
@@ -430,4 +779,153 @@ mod tests {
         let backup = get_backup_path(path);
         assert_eq!(backup, std::path::Path::new(".gitignore.bak"));
     }
+
+    fn convert(input: &[u8], ending: LineEnding) -> Vec<u8> {
+        let mut output = Vec::new();
+        convert_line_endings(input, &mut output, ending).expect("conversion should succeed");
+        output
+    }
+
+    #[test]
+    fn test_convert_replaces_only_terminators() {
+        assert_eq!(convert(b"a\nb\r\nc", LineEnding::Crlf), b"a\r\nb\r\nc");
+        assert_eq!(convert(b"a\nb\r\nc\r\n", LineEnding::Lf), b"a\nb\nc\n");
+    }
+
+    #[test]
+    fn test_convert_normalizes_lone_cr() {
+        assert_eq!(convert(b"a\rb\r\r\nc\r", LineEnding::Lf), b"a\nb\n\nc\n");
+        assert_eq!(
+            convert(b"a\rb\r\r\nc\r", LineEnding::Crlf),
+            b"a\r\nb\r\n\r\nc\r\n"
+        );
+    }
+
+    #[test]
+    fn test_convert_to_cr() {
+        assert_eq!(convert(b"a\nb\r\nc\rd", LineEnding::Cr), b"a\rb\rc\rd");
+        assert_eq!(convert(b"a\r\r\n", LineEnding::Cr), b"a\r\r");
+    }
+
+    #[test]
+    fn test_convert_returns_terminator_count() {
+        let mut output = Vec::new();
+        let count = convert_line_endings(&b"a\nb\r\nc\rd"[..], &mut output, LineEnding::Lf)
+            .expect("conversion should succeed");
+        assert_eq!(count, 3);
+    }
+
+    #[test]
+    fn test_convert_handles_crlf_split_across_buffers() {
+        // Place the CR as the last byte of the first read buffer
+        let mut input = vec![b'x'; BUFFER_SIZE - 1];
+        input.extend_from_slice(b"\r\ny");
+        let output = convert(&input, LineEnding::Lf);
+        assert_eq!(output.len(), input.len() - 1);
+        assert!(output.ends_with(b"x\ny"));
+    }
+
+    #[test]
+    fn test_convert_passes_invalid_utf8_through() {
+        let input = b"caf\xe9\n\xff\xfe\x80\r\n\x93quoted\x94";
+        let output = convert(input, LineEnding::Crlf);
+        assert_eq!(output, b"caf\xe9\r\n\xff\xfe\x80\r\n\x93quoted\x94");
+        assert_eq!(
+            convert(&output, LineEnding::Lf),
+            b"caf\xe9\n\xff\xfe\x80\n\x93quoted\x94"
+        );
+    }
+
+    fn fix(input: &[u8], fixes: WhitespaceFixes) -> Vec<u8> {
+        let mut output = Vec::new();
+        fix_whitespace(input, &mut output, fixes, TextEncoding::Utf8)
+            .expect("whitespace fixes should succeed");
+        output
+    }
+
+    const TRIM_ONLY: WhitespaceFixes = WhitespaceFixes {
+        trim_trailing_whitespace: true,
+        final_newline: None,
+        ending: LineEnding::Lf,
+    };
+
+    #[test]
+    fn test_fix_whitespace_trims_before_every_terminator() {
+        assert_eq!(fix(b"a  \nb\t\r\nc \rd", TRIM_ONLY), b"a\nb\r\nc\rd");
+        assert_eq!(fix(b"keep  inner\n  \n", TRIM_ONLY), b"keep  inner\n\n");
+        assert_eq!(fix(b"last  ", TRIM_ONLY), b"last");
+    }
+
+    #[test]
+    fn test_fix_whitespace_adds_final_newline() {
+        let fixes = WhitespaceFixes {
+            trim_trailing_whitespace: false,
+            final_newline: Some(true),
+            ending: LineEnding::Crlf,
+        };
+        assert_eq!(fix(b"a\r\nb", fixes), b"a\r\nb\r\n");
+        assert_eq!(fix(b"a\r\n", fixes), b"a\r\n");
+        assert_eq!(fix(b"", fixes), b"", "Empty files stay empty");
+    }
+
+    #[test]
+    fn test_fix_whitespace_removes_final_newlines() {
+        let fixes = WhitespaceFixes {
+            trim_trailing_whitespace: true,
+            final_newline: Some(false),
+            ending: LineEnding::Lf,
+        };
+        assert_eq!(fix(b"a\nb\n \n\r\n", fixes), b"a\nb");
+        assert_eq!(fix(b"a\nb", fixes), b"a\nb");
+    }
+
+    #[test]
+    fn test_fix_lines_converts_and_fixes_whitespace_together() {
+        let fix_lines_to = |input: &[u8], ending: LineEnding, final_newline: Option<bool>| {
+            let fixes = WhitespaceFixes {
+                trim_trailing_whitespace: true,
+                final_newline,
+                ending,
+            };
+            let mut output = Vec::new();
+            fix_lines(
+                input,
+                &mut output,
+                Some(ending),
+                Some(fixes),
+                TextEncoding::Utf8,
+            )
+            .expect("fixes should succeed");
+            output
+        };
+        assert_eq!(
+            fix_lines_to(b"one \ntwo\t\r\nthree \rfour", LineEnding::Crlf, Some(true)),
+            b"one\r\ntwo\r\nthree\r\nfour\r\n"
+        );
+        assert_eq!(
+            fix_lines_to(b"a \r\n\n \r", LineEnding::Lf, Some(false)),
+            b"a"
+        );
+        assert_eq!(fix_lines_to(b"a\r\r\nb  ", LineEnding::Cr, None), b"a\r\rb");
+    }
+
+    #[test]
+    fn test_fix_whitespace_in_utf16() {
+        let encode =
+            |text: &str| -> Vec<u8> { text.encode_utf16().flat_map(u16::to_le_bytes).collect() };
+        let fixes = WhitespaceFixes {
+            trim_trailing_whitespace: true,
+            final_newline: Some(true),
+            ending: LineEnding::Lf,
+        };
+        let mut output = Vec::new();
+        fix_whitespace(
+            encode("a \nb\t").as_slice(),
+            &mut output,
+            fixes,
+            TextEncoding::Utf16Le,
+        )
+        .expect("whitespace fixes should succeed");
+        assert_eq!(output, encode("a\nb\n"));
+    }
 }
@@ -0,0 +1,253 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+use crate::editorconfig::{Glob, relative_with_slashes};
+use crate::types::LineEnding;
+use crate::utils::normalize_path;
+
+/// Name of the project configuration file, searched for from the folder upwards
+pub const PROJECT_CONFIG: &str = ".line-endings.toml";
+
+/// Whether files matching a rule must or must not start with a BOM
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BomRule {
+    Required,
+    Forbidden,
+}
+
+/// The rules resolved for one file. `None` means not set.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct ProjectRules {
+    pub line_ending: Option<LineEnding>,
+    pub bom: Option<BomRule>,
+    pub final_newline: Option<bool>,
+    /// Largest allowed file size in bytes
+    pub max_size: Option<u64>,
+}
+
+/// `line_ending` as written in the file
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum EndingSetting {
+    Lf,
+    Crlf,
+    Cr,
+}
+
+/// `max_size` as written in the file, either bytes or a string such as `"512KB"`
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SizeSetting {
+    Bytes(u64),
+    Text(String),
+}
+
+/// One `[[rules]]` table as written in the file
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawRule {
+    glob: String,
+    line_ending: Option<EndingSetting>,
+    bom: Option<BomRule>,
+    final_newline: Option<bool>,
+    max_size: Option<SizeSetting>,
+}
+
+/// The whole file as written
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawProjectConfig {
+    #[serde(default)]
+    include: Vec<String>,
+    #[serde(default)]
+    exclude: Vec<String>,
+    #[serde(default)]
+    rules: Vec<RawRule>,
+}
+
+struct Rule {
+    glob: Glob,
+    rules: ProjectRules,
+}
+
+/// A parsed `.line-endings.toml`. Globs use the `.editorconfig` syntax and are relative to
+/// the directory holding the file, and a glob that matches a directory covers everything in it.
+pub struct ProjectConfig {
+    path: PathBuf,
+    root: PathBuf,
+    include: Vec<Glob>,
+    exclude: Vec<Glob>,
+    rules: Vec<Rule>,
+}
+
+impl ProjectConfig {
+    /// Finds the nearest `.line-endings.toml` in `start` or one of its parents and loads it.
+    /// Returns `None` if there is no such file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file found cannot be read or is not valid.
+    pub fn discover(start: impl AsRef<Path>) -> Result<Option<ProjectConfig>> {
+        let start = normalize_path(&std::path::absolute(start.as_ref())?);
+        for directory in start.ancestors() {
+            let candidate = directory.join(PROJECT_CONFIG);
+            if candidate.is_file() {
+                return ProjectConfig::load(candidate).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    /// Loads a project configuration file
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read, is not valid TOML, has unknown keys or
+    /// has a value this tool does not understand.
+    pub fn load(path: impl AsRef<Path>) -> Result<ProjectConfig> {
+        let path = normalize_path(&std::path::absolute(path.as_ref())?);
+        let contents = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let raw: RawProjectConfig = toml::from_str(&contents)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+
+        let mut rules = Vec::with_capacity(raw.rules.len());
+        for rule in raw.rules {
+            let max_size = match rule.max_size {
+                None => None,
+                Some(SizeSetting::Bytes(bytes)) => Some(bytes),
+                Some(SizeSetting::Text(text)) => Some(
+                    parse_size(&text)
+                        .with_context(|| format!("Invalid max_size in {}", path.display()))?,
+                ),
+            };
+            rules.push(Rule {
+                glob: Glob::new(&rule.glob),
+                rules: ProjectRules {
+                    line_ending: rule.line_ending.map(|ending| match ending {
+                        EndingSetting::Lf => LineEnding::Lf,
+                        EndingSetting::Crlf => LineEnding::Crlf,
+                        EndingSetting::Cr => LineEnding::Cr,
+                    }),
+                    bom: rule.bom,
+                    final_newline: rule.final_newline,
+                    max_size,
+                },
+            });
+        }
+
+        let root = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        Ok(ProjectConfig {
+            path,
+            root,
+            include: raw.include.iter().map(|glob| Glob::new(glob)).collect(),
+            exclude: raw.exclude.iter().map(|glob| Glob::new(glob)).collect(),
+            rules,
+        })
+    }
+
+    /// Path of the file this configuration was loaded from
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns true if the file sets `include` globs, which then replace a missing file pattern
+    #[must_use]
+    pub fn has_include_globs(&self) -> bool {
+        !self.include.is_empty()
+    }
+
+    /// Returns true if any rule requires or forbids a BOM, so BOMs must be detected
+    #[must_use]
+    pub fn has_bom_rules(&self) -> bool {
+        self.rules.iter().any(|rule| rule.rules.bom.is_some())
+    }
+
+    /// Returns true if a path matches the `include` globs (or there are none) and no
+    /// `exclude` glob. Paths outside the directory of the file are always included.
+    #[must_use]
+    pub fn includes(&self, path: impl AsRef<Path>) -> bool {
+        let Some(relative) = self.relative_path(path.as_ref()) else {
+            return true;
+        };
+
+        let included = self.include.is_empty()
+            || self
+                .include
+                .iter()
+                .any(|glob| matches_path_or_parent(glob, &relative));
+        included
+            && !self
+                .exclude
+                .iter()
+                .any(|glob| matches_path_or_parent(glob, &relative))
+    }
+
+    /// Resolves the rules that apply to a path, with later rules overriding earlier ones
+    #[must_use]
+    pub fn rules_for(&self, path: impl AsRef<Path>) -> ProjectRules {
+        let mut resolved = ProjectRules::default();
+        let Some(relative) = self.relative_path(path.as_ref()) else {
+            return resolved;
+        };
+
+        for rule in &self.rules {
+            if !matches_path_or_parent(&rule.glob, &relative) {
+                continue;
+            }
+            let rules = &rule.rules;
+            resolved.line_ending = rules.line_ending.or(resolved.line_ending);
+            resolved.bom = rules.bom.or(resolved.bom);
+            resolved.final_newline = rules.final_newline.or(resolved.final_newline);
+            resolved.max_size = rules.max_size.or(resolved.max_size);
+        }
+        resolved
+    }
+
+    /// Path relative to the directory of the file, or `None` if it is outside it
+    fn relative_path(&self, path: &Path) -> Option<String> {
+        let path = normalize_path(&std::path::absolute(path).ok()?);
+        relative_with_slashes(&path, &self.root)
+    }
+}
+
+/// Matches a glob against a relative path and each of the directories above it
+fn matches_path_or_parent(glob: &Glob, relative: &str) -> bool {
+    glob.matches(relative)
+        || relative
+            .match_indices('/')
+            .any(|(index, _)| glob.matches(&relative[..index]))
+}
+
+/// Parses a size such as `1048576`, `512KB` or `2 MiB`. Units are powers of 1024.
+///
+/// # Errors
+///
+/// Returns an error if the text is not a number with an optional unit, or is too large.
+pub fn parse_size(text: &str) -> Result<u64> {
+    let text = text.trim();
+    let digits = text.chars().take_while(char::is_ascii_digit).count();
+    let (number, unit) = text.split_at(digits);
+    let number: u64 = number
+        .parse()
+        .with_context(|| format!("'{text}' does not start with a number"))?;
+
+    let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" | "kib" => 1 << 10,
+        "m" | "mb" | "mib" => 1 << 20,
+        "g" | "gb" | "gib" => 1 << 30,
+        other => {
+            return Err(anyhow::anyhow!(
+                "Unknown size unit '{other}', expected B, KB, MB or GB"
+            ));
+        }
+    };
+
+    number
+        .checked_mul(multiplier)
+        .ok_or_else(|| anyhow::anyhow!("'{text}' is too large"))
+}
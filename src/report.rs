@@ -0,0 +1,469 @@
+use rayon::prelude::*;
+use serde_json::{Value, json};
+// Writing to a String cannot fail, so the results of write! are ignored
+use std::fmt::Write;
+use std::path::{Component, Path};
+
+use crate::check::{check_file, first_offending_line};
+use crate::types::{
+    AnalysisSummary, BomType, ConfigSettings, FileAnalysis, LineEnding, TextEncoding, Violation,
+};
+
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+const SRCROOT: &str = "%SRCROOT%";
+
+/// Builds the JSON object for one analysed file.
+/// `violations` is only included in check mode, otherwise it is `null`.
+#[must_use]
+pub fn file_analysis_json(result: &FileAnalysis, config: &ConfigSettings) -> Value {
+    let violations = if config.check {
+        let violations: Vec<String> = config
+            .policy_for(&result.path)
+            .map(|policy| check_file(result, &policy))
+            .unwrap_or_default()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        json!(violations)
+    } else {
+        Value::Null
+    };
+
+    json!({
+        "path": result.path.to_string_lossy(),
+        "size": result.size,
+        "lf_count": result.lf_count,
+        "crlf_count": result.crlf_count,
+        "cr_count": result.cr_count,
+        "mixed": result.has_mixed_line_endings(),
+        "first_lines": {
+            "lf": result.first_lines.lf,
+            "crlf": result.first_lines.crlf,
+            "cr": result.first_lines.cr,
+            "trailing_whitespace": result.first_lines.trailing_whitespace,
+        },
+        "trailing_whitespace_lines": result.trailing_whitespace_lines,
+        "final_newline": result.final_newline,
+        "bom_checked": result.bom_checked,
+        "bom_type": result.bom_type.map(|bom| bom.to_string()),
+        "encoding": result.encoding.to_string(),
+        "is_binary": result.is_binary,
+        "error": result.error,
+        "violations": violations,
+    })
+}
+
+/// Builds the JSON object for the summary totals and timings
+#[must_use]
+pub fn summary_json(summary: &AnalysisSummary, config: &ConfigSettings) -> Value {
+    json!({
+        "analyzed_files": summary.analyzed_files,
+        "binary_files": summary.binary_files,
+        "error_files": summary.error_files,
+        "mixed_files": summary.mixed_files,
+        "failed_files": config.check.then_some(summary.failed_files),
+        "total_lf": summary.total_lf,
+        "total_crlf": summary.total_crlf,
+        "total_cr": summary.total_cr,
+        "analysis_seconds": summary.analysis_duration.as_secs_f64(),
+        "total_seconds": summary.total_duration.as_secs_f64(),
+    })
+}
+
+/// Prints every file and the summary as a single JSON document
+pub fn print_json_report(
+    results: &[FileAnalysis],
+    summary: &AnalysisSummary,
+    config: &ConfigSettings,
+) {
+    let files: Vec<Value> = results
+        .iter()
+        .map(|result| file_analysis_json(result, config))
+        .collect();
+
+    let report = json!({
+        "files": files,
+        "summary": summary_json(summary, config),
+    });
+    println!("{report:#}");
+}
+
+/// Prints one file as a single NDJSON line, tagged with `"type": "file"`
+pub fn print_ndjson_file(result: &FileAnalysis, config: &ConfigSettings) {
+    let mut line = file_analysis_json(result, config);
+    line["type"] = json!("file");
+    println!("{line}");
+}
+
+/// Prints the summary as the final NDJSON line, tagged with `"type": "summary"`
+pub fn print_ndjson_summary(summary: &AnalysisSummary, config: &ConfigSettings) {
+    let mut line = summary_json(summary, config);
+    line["type"] = json!("summary");
+    println!("{line}");
+}
+
+/// One example of each violation kind, in the order rules are listed in the SARIF log
+const SARIF_RULE_EXAMPLES: [Violation; 10] = [
+    Violation::MixedLineEndings,
+    Violation::WrongLineEnding(LineEnding::Lf),
+    Violation::UnexpectedBom(BomType::Utf8),
+    Violation::LoneCr,
+    Violation::MissingBom,
+    Violation::WrongEncoding(TextEncoding::Utf8),
+    Violation::MissingFinalNewline,
+    Violation::UnexpectedFinalNewline,
+    Violation::TrailingWhitespace,
+    Violation::FileTooLarge(0),
+];
+
+/// Builds the SARIF reporting descriptor for a rule
+fn sarif_rule(example: Violation) -> Value {
+    let (name, description) = match example {
+        Violation::MixedLineEndings => (
+            "MixedLineEndings",
+            "The file uses more than one kind of line ending.",
+        ),
+        Violation::WrongLineEnding(_) => (
+            "WrongLineEnding",
+            "The file uses line endings other than the required kind.",
+        ),
+        Violation::UnexpectedBom(_) => (
+            "UnexpectedBom",
+            "The file starts with a byte order mark that is not allowed.",
+        ),
+        Violation::LoneCr => (
+            "LoneCr",
+            "The file contains carriage returns that are not followed by a line feed.",
+        ),
+        Violation::MissingBom => (
+            "MissingBom",
+            "The file must start with a byte order mark but has none.",
+        ),
+        Violation::WrongEncoding(_) => {
+            ("WrongEncoding", "The file is not in the required encoding.")
+        }
+        Violation::MissingFinalNewline => (
+            "MissingFinalNewline",
+            "The last line of the file has no line ending.",
+        ),
+        Violation::UnexpectedFinalNewline => (
+            "UnexpectedFinalNewline",
+            "The last line of the file ends with a line ending that is not allowed.",
+        ),
+        Violation::TrailingWhitespace => ("TrailingWhitespace", "Lines end with spaces or tabs."),
+        Violation::FileTooLarge(_) => (
+            "FileTooLarge",
+            "The file is larger than the maximum size allowed for it.",
+        ),
+    };
+
+    json!({
+        "id": example.rule_id(),
+        "name": name,
+        "shortDescription": { "text": description },
+        "defaultConfiguration": { "level": "error" },
+    })
+}
+
+/// Converts a path to a SARIF artifact location. Relative paths are resolved against
+/// `%SRCROOT%`, the directory the tool was run from; absolute paths become `file://` URIs.
+fn sarif_artifact_location(path: &Path) -> Value {
+    let mut segments = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir | Component::RootDir => {}
+            Component::Prefix(prefix) => {
+                segments.push(prefix.as_os_str().to_string_lossy().into_owned());
+            }
+            Component::ParentDir => segments.push(String::from("..")),
+            Component::Normal(part) => segments.push(percent_encode(&part.to_string_lossy())),
+        }
+    }
+    let joined = segments.join("/");
+
+    if path.is_absolute() {
+        json!({ "uri": format!("file:///{joined}") })
+    } else {
+        json!({ "uri": joined, "uriBaseId": SRCROOT })
+    }
+}
+
+/// Percent-encodes everything in a path segment except RFC 3986 unreserved characters
+fn percent_encode(segment: &str) -> String {
+    let mut encoded = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+            encoded.push(char::from(byte));
+        } else {
+            let _ = write!(encoded, "%{byte:02X}");
+        }
+    }
+    encoded
+}
+
+/// Checks one analysed file and pairs each violation with the 1-based line where it
+/// first occurs, from the line numbers recorded during analysis. Violations are only
+/// reported in check mode, as in the JSON report.
+fn locate_violations(
+    result: &FileAnalysis,
+    config: &ConfigSettings,
+) -> Vec<(Violation, Option<usize>)> {
+    if !config.check {
+        return Vec::new();
+    }
+    let Some(policy) = config.policy_for(&result.path) else {
+        return Vec::new();
+    };
+    check_file(result, &policy)
+        .into_iter()
+        .map(|violation| {
+            (
+                violation,
+                first_offending_line(&violation, result, &result.first_lines),
+            )
+        })
+        .collect()
+}
+
+/// Builds the SARIF results for one analysed file, one per violation
+fn sarif_file_results(result: &FileAnalysis, config: &ConfigSettings) -> Vec<Value> {
+    locate_violations(result, config)
+        .into_iter()
+        .map(|(violation, line)| {
+            let rule_index = SARIF_RULE_EXAMPLES
+                .iter()
+                .position(|example| example.rule_id() == violation.rule_id())
+                .unwrap_or_default();
+
+            let mut physical_location = json!({
+                "artifactLocation": sarif_artifact_location(&result.path),
+            });
+            if let Some(line) = line {
+                physical_location["region"] = json!({ "startLine": line });
+            }
+
+            json!({
+                "ruleId": violation.rule_id(),
+                "ruleIndex": rule_index,
+                "level": "error",
+                "message": { "text": format!("{}: {violation}", result.path.display()) },
+                "locations": [{ "physicalLocation": physical_location }],
+            })
+        })
+        .collect()
+}
+
+/// Builds a SARIF 2.1.0 log with a single run. In check mode every violation becomes a
+/// result, and files that could not be analysed are reported as tool execution
+/// notifications.
+#[must_use]
+pub fn sarif_log(results: &[FileAnalysis], config: &ConfigSettings) -> Value {
+    let sarif_results: Vec<Value> = results
+        .par_iter()
+        .flat_map_iter(|result| sarif_file_results(result, config))
+        .collect();
+
+    let notifications: Vec<Value> = results
+        .iter()
+        .filter_map(|result| {
+            result.error.as_ref().map(|error| {
+                json!({
+                    "level": "error",
+                    "message": { "text": format!("{}: {error}", result.path.display()) },
+                    "locations": [{
+                        "physicalLocation": {
+                            "artifactLocation": sarif_artifact_location(&result.path),
+                        },
+                    }],
+                })
+            })
+        })
+        .collect();
+
+    let rules: Vec<Value> = SARIF_RULE_EXAMPLES.into_iter().map(sarif_rule).collect();
+
+    let mut run = json!({
+        "tool": {
+            "driver": {
+                "name": env!("CARGO_PKG_NAME"),
+                "version": env!("CARGO_PKG_VERSION"),
+                "rules": rules,
+            },
+        },
+        "invocations": [{
+            "executionSuccessful": notifications.is_empty(),
+            "toolExecutionNotifications": notifications,
+        }],
+        "results": sarif_results,
+    });
+
+    // Tell viewers where relative paths start, when the working directory is known
+    if let Ok(cwd) = std::env::current_dir() {
+        let mut root = sarif_artifact_location(&cwd)["uri"].clone();
+        if let Some(uri) = root.as_str() {
+            root = json!(format!("{}/", uri.trim_end_matches('/')));
+        }
+        run["originalUriBaseIds"] = json!({ SRCROOT: { "uri": root } });
+    }
+
+    json!({
+        "$schema": SARIF_SCHEMA,
+        "version": "2.1.0",
+        "runs": [run],
+    })
+}
+
+/// Prints the SARIF log as a single JSON document
+pub fn print_sarif_report(results: &[FileAnalysis], config: &ConfigSettings) {
+    println!("{:#}", sarif_log(results, config));
+}
+
+/// Escapes text for use in XML content or a double-quoted attribute.
+/// Characters that XML 1.0 cannot represent at all are replaced with U+FFFD.
+fn xml_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if c < ' ' || c == '\u{FFFE}' || c == '\u{FFFF}' => escaped.push('\u{FFFD}'),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Formats a violation with its line, as `path:line: description`
+fn describe_located(path: &Path, violation: Violation, line: Option<usize>) -> String {
+    match line {
+        Some(line) => format!("{}:{line}: {violation}", path.display()),
+        None => format!("{}: {violation}", path.display()),
+    }
+}
+
+/// Builds a `JUnit` XML report with one testcase per analysed path. In check mode
+/// violations become a failure. Files that could not be analysed are an error, and binary
+/// files are skipped.
+#[must_use]
+pub fn junit_report(
+    results: &[FileAnalysis],
+    summary: &AnalysisSummary,
+    config: &ConfigSettings,
+) -> String {
+    let located: Vec<Vec<(Violation, Option<usize>)>> = results
+        .par_iter()
+        .map(|result| locate_violations(result, config))
+        .collect();
+
+    let failures = located.iter().filter(|v| !v.is_empty()).count();
+    let errors = results.iter().filter(|r| r.error.is_some()).count();
+    let skipped = results.iter().filter(|r| r.is_binary).count();
+    let name = env!("CARGO_PKG_NAME");
+    let seconds = summary.total_duration.as_secs_f64();
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        xml,
+        "<testsuites name=\"{name}\" tests=\"{}\" failures=\"{failures}\" errors=\"{errors}\" skipped=\"{skipped}\" time=\"{seconds:.3}\">",
+        results.len()
+    );
+    let _ = writeln!(
+        xml,
+        "  <testsuite name=\"{name}\" tests=\"{}\" failures=\"{failures}\" errors=\"{errors}\" skipped=\"{skipped}\" time=\"{seconds:.3}\">",
+        results.len()
+    );
+
+    for (result, violations) in results.iter().zip(&located) {
+        let path = xml_escape(&result.path.display().to_string());
+        let _ = write!(
+            xml,
+            "    <testcase name=\"{path}\" classname=\"{name}\" file=\"{path}\""
+        );
+
+        if let Some(error) = &result.error {
+            let _ = writeln!(xml, ">");
+            let _ = writeln!(
+                xml,
+                "      <error message=\"{}\" type=\"analysis-error\"/>",
+                xml_escape(error)
+            );
+            let _ = writeln!(xml, "    </testcase>");
+        } else if result.is_binary {
+            let _ = writeln!(xml, ">");
+            let _ = writeln!(xml, "      <skipped message=\"binary file\"/>");
+            let _ = writeln!(xml, "    </testcase>");
+        } else if let Some((first, _)) = violations.first() {
+            let message: Vec<String> = violations.iter().map(|(v, _)| v.to_string()).collect();
+            let details: Vec<String> = violations
+                .iter()
+                .map(|(v, line)| xml_escape(&describe_located(&result.path, *v, *line)))
+                .collect();
+            let _ = writeln!(xml, ">");
+            let _ = writeln!(
+                xml,
+                "      <failure message=\"{}\" type=\"{}\">{}</failure>",
+                xml_escape(&message.join(", ")),
+                first.rule_id(),
+                details.join("\n")
+            );
+            let _ = writeln!(xml, "    </testcase>");
+        } else {
+            let _ = writeln!(xml, "/>");
+        }
+    }
+
+    xml.push_str("  </testsuite>\n</testsuites>\n");
+    xml
+}
+
+/// Builds a Checkstyle XML report with one `<file>` per analysed path and, in check mode,
+/// one `<error>` per violation. Files that could not be analysed get a single
+/// `analysis-error`.
+#[must_use]
+pub fn checkstyle_report(results: &[FileAnalysis], config: &ConfigSettings) -> String {
+    let located: Vec<Vec<(Violation, Option<usize>)>> = results
+        .par_iter()
+        .map(|result| locate_violations(result, config))
+        .collect();
+    let name = env!("CARGO_PKG_NAME");
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<checkstyle version=\"4.3\">\n");
+
+    for (result, violations) in results.iter().zip(&located) {
+        let path = xml_escape(&result.path.display().to_string());
+
+        if result.error.is_none() && violations.is_empty() {
+            let _ = writeln!(xml, "  <file name=\"{path}\"/>");
+            continue;
+        }
+
+        let _ = writeln!(xml, "  <file name=\"{path}\">");
+        if let Some(error) = &result.error {
+            let _ = writeln!(
+                xml,
+                "    <error severity=\"error\" message=\"{}\" source=\"{name}.analysis-error\"/>",
+                xml_escape(error)
+            );
+        }
+        for (violation, line) in violations {
+            let line = line
+                .map(|line| format!(" line=\"{line}\""))
+                .unwrap_or_default();
+            let _ = writeln!(
+                xml,
+                "    <error{line} severity=\"error\" message=\"{}\" source=\"{name}.{}\"/>",
+                xml_escape(&violation.to_string()),
+                violation.rule_id()
+            );
+        }
+        xml.push_str("  </file>\n");
+    }
+
+    xml.push_str("</checkstyle>\n");
+    xml
+}
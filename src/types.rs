@@ -1,4 +1,10 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::editorconfig::{Charset, EditorConfig};
+use crate::gitattributes::{EolAttribute, GitAttributes};
+use crate::project_config::{BomRule, ProjectConfig};
+use ignore::gitignore::Gitignore;
 
 /// Represents the type of BOM detected in a file.
 /// Note: `Option<BomType>` in `FileAnalysis::bom_type` uses `None` to mean "no BOM found".
@@ -12,6 +18,25 @@ pub enum BomType {
     Utf32Be,
 }
 
+impl BomType {
+    /// Length of the BOM in bytes
+    #[must_use]
+    pub fn size(self) -> usize {
+        match self {
+            BomType::Utf8 => 3,
+            BomType::Utf16Le | BomType::Utf16Be => 2,
+            BomType::Utf32Le | BomType::Utf32Be => 4,
+        }
+    }
+
+    /// Returns true if the BOM can be removed without changing how the text is read.
+    /// UTF-16/32 text needs its BOM, so only a UTF-8 BOM is removed.
+    #[must_use]
+    pub fn is_removable(self) -> bool {
+        self == BomType::Utf8
+    }
+}
+
 impl std::fmt::Display for BomType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -24,11 +49,127 @@ impl std::fmt::Display for BomType {
     }
 }
 
+/// Text encoding used to interpret line terminators.
+/// `Utf8` also covers ASCII and legacy 8-bit encodings, where every terminator is a single byte.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum TextEncoding {
+    #[default]
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Utf32Le,
+    Utf32Be,
+}
+
+impl TextEncoding {
+    /// Size in bytes of a single code unit
+    #[must_use]
+    pub fn code_unit_width(self) -> usize {
+        match self {
+            TextEncoding::Utf8 => 1,
+            TextEncoding::Utf16Le | TextEncoding::Utf16Be => 2,
+            TextEncoding::Utf32Le | TextEncoding::Utf32Be => 4,
+        }
+    }
+
+    /// Decodes one code unit. `bytes` must be exactly `code_unit_width()` long.
+    #[must_use]
+    pub fn decode_unit(self, bytes: &[u8]) -> u32 {
+        match self {
+            TextEncoding::Utf8 => u32::from(bytes[0]),
+            TextEncoding::Utf16Le => u32::from(u16::from_le_bytes([bytes[0], bytes[1]])),
+            TextEncoding::Utf16Be => u32::from(u16::from_be_bytes([bytes[0], bytes[1]])),
+            TextEncoding::Utf32Le => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            TextEncoding::Utf32Be => u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+        }
+    }
+
+    /// Appends the bytes of one code unit to `out`.
+    /// `unit` must fit in the code unit width, which holds for CR and LF in every encoding.
+    pub fn encode_unit(self, unit: u32, out: &mut Vec<u8>) {
+        let bytes = unit.to_le_bytes();
+        match self {
+            TextEncoding::Utf8 => out.push(bytes[0]),
+            TextEncoding::Utf16Le => out.extend_from_slice(&bytes[..2]),
+            TextEncoding::Utf16Be => out.extend_from_slice(&[bytes[1], bytes[0]]),
+            TextEncoding::Utf32Le => out.extend_from_slice(&bytes),
+            TextEncoding::Utf32Be => out.extend_from_slice(&unit.to_be_bytes()),
+        }
+    }
+}
+
+impl From<BomType> for TextEncoding {
+    fn from(bom: BomType) -> Self {
+        match bom {
+            BomType::Utf8 => TextEncoding::Utf8,
+            BomType::Utf16Le => TextEncoding::Utf16Le,
+            BomType::Utf16Be => TextEncoding::Utf16Be,
+            BomType::Utf32Le => TextEncoding::Utf32Le,
+            BomType::Utf32Be => TextEncoding::Utf32Be,
+        }
+    }
+}
+
+impl std::fmt::Display for TextEncoding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TextEncoding::Utf8 => write!(f, "UTF-8"),
+            TextEncoding::Utf16Le => write!(f, "UTF-16 LE"),
+            TextEncoding::Utf16Be => write!(f, "UTF-16 BE"),
+            TextEncoding::Utf32Le => write!(f, "UTF-32 LE"),
+            TextEncoding::Utf32Be => write!(f, "UTF-32 BE"),
+        }
+    }
+}
+
+/// Counts of each kind of line terminator found in a file
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct LineEndingCounts {
+    pub lf: usize,
+    pub crlf: usize,
+    /// Lone CR terminators (classic Mac style), not followed by LF
+    pub cr: usize,
+}
+
+impl LineEndingCounts {
+    /// Counts one terminator of the given kind
+    pub fn add(&mut self, ending: LineEnding) {
+        match ending {
+            LineEnding::Lf => self.lf += 1,
+            LineEnding::Crlf => self.crlf += 1,
+            LineEnding::Cr => self.cr += 1,
+        }
+    }
+}
+
+/// 1-based line number of the first terminator of each kind, `None` if the kind is absent
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct FirstLineEndings {
+    pub lf: Option<usize>,
+    pub crlf: Option<usize>,
+    pub cr: Option<usize>,
+    /// First line that ends with a space or tab
+    pub trailing_whitespace: Option<usize>,
+}
+
+impl FirstLineEndings {
+    /// Line of the first terminator of the given kind
+    #[must_use]
+    pub fn line_of(&self, ending: LineEnding) -> Option<usize> {
+        match ending {
+            LineEnding::Lf => self.lf,
+            LineEnding::Crlf => self.crlf,
+            LineEnding::Cr => self.cr,
+        }
+    }
+}
+
 /// Represents line ending types
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum LineEnding {
     Lf,   // Unix/Linux style (\n)
     Crlf, // Windows style (\r\n)
+    Cr,   // Classic Mac style (\r)
 }
 
 /// Target line ending for file conversion
@@ -37,6 +178,174 @@ pub enum LineEndingTarget {
     None,    // No conversion
     Linux,   // Convert to LF
     Windows, // Convert to CRLF
+    Mac,     // Convert to CR
+}
+
+impl LineEndingTarget {
+    /// Returns the line ending to convert to, or `None` if no conversion is requested
+    #[must_use]
+    pub fn line_ending(self) -> Option<LineEnding> {
+        match self {
+            LineEndingTarget::None => None,
+            LineEndingTarget::Linux => Some(LineEnding::Lf),
+            LineEndingTarget::Windows => Some(LineEnding::Crlf),
+            LineEndingTarget::Mac => Some(LineEnding::Cr),
+        }
+    }
+}
+
+impl std::fmt::Display for LineEnding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LineEnding::Lf => write!(f, "LF"),
+            LineEnding::Crlf => write!(f, "CRLF"),
+            LineEnding::Cr => write!(f, "CR"),
+        }
+    }
+}
+
+/// Expected state of a file, verified in check mode
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct FilePolicy {
+    /// Required line ending, or `None` to accept any single kind
+    pub line_ending: Option<LineEnding>,
+    /// `true` if the file must not start with a BOM
+    pub forbid_bom: bool,
+    /// `true` if the file must start with a BOM
+    pub require_bom: bool,
+    /// Required encoding, or `None` to accept any
+    pub encoding: Option<TextEncoding>,
+    /// `Some(true)` if the last line must end with a terminator, `Some(false)` if it must not
+    pub final_newline: Option<bool>,
+    /// `true` if lines must not end with spaces or tabs
+    pub trim_trailing_whitespace: bool,
+    /// Largest allowed file size in bytes, or `None` for no limit
+    pub max_size: Option<u64>,
+}
+
+/// A way in which a file fails to match its `FilePolicy`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Violation {
+    /// More than one kind of line ending is used
+    MixedLineEndings,
+    /// Line endings other than the required kind are present
+    WrongLineEnding(LineEnding),
+    /// The file starts with a BOM that is not allowed
+    UnexpectedBom(BomType),
+    /// Lone CR terminators are present, and CR is not the required ending
+    LoneCr,
+    /// The file must start with a BOM but has none
+    MissingBom,
+    /// The file is not in the required encoding
+    WrongEncoding(TextEncoding),
+    /// The last line has no terminator
+    MissingFinalNewline,
+    /// The last line ends with a terminator that is not allowed
+    UnexpectedFinalNewline,
+    /// Lines end with spaces or tabs
+    TrailingWhitespace,
+    /// The file is larger than the allowed size in bytes
+    FileTooLarge(u64),
+}
+
+impl Violation {
+    /// Stable identifier of the rule this violation breaks, used by machine-readable reports
+    #[must_use]
+    pub fn rule_id(&self) -> &'static str {
+        match self {
+            Violation::MixedLineEndings => "mixed-line-endings",
+            Violation::WrongLineEnding(_) => "wrong-line-ending",
+            Violation::UnexpectedBom(_) => "unexpected-bom",
+            Violation::LoneCr => "lone-cr",
+            Violation::MissingBom => "missing-bom",
+            Violation::WrongEncoding(_) => "wrong-encoding",
+            Violation::MissingFinalNewline => "missing-final-newline",
+            Violation::UnexpectedFinalNewline => "unexpected-final-newline",
+            Violation::TrailingWhitespace => "trailing-whitespace",
+            Violation::FileTooLarge(_) => "file-too-large",
+        }
+    }
+}
+
+impl std::fmt::Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Violation::MixedLineEndings => write!(f, "mixed line endings"),
+            Violation::WrongLineEnding(expected) => write!(f, "expected {expected} line endings"),
+            Violation::UnexpectedBom(bom) => write!(f, "unexpected {bom} BOM"),
+            Violation::LoneCr => write!(f, "lone CR line endings"),
+            Violation::MissingBom => write!(f, "missing BOM"),
+            Violation::WrongEncoding(expected) => write!(f, "expected {expected} encoding"),
+            Violation::MissingFinalNewline => write!(f, "no newline at end of file"),
+            Violation::UnexpectedFinalNewline => write!(f, "unexpected newline at end of file"),
+            Violation::TrailingWhitespace => write!(f, "trailing whitespace"),
+            Violation::FileTooLarge(limit) => write!(f, "larger than {limit} bytes"),
+        }
+    }
+}
+
+/// Format used to print analysis results
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    #[default]
+    Text, // Human readable, tab separated
+    Json,       // A single JSON document, printed once analysis completes
+    Ndjson,     // One JSON object per line, printed as each file is analysed
+    Sarif,      // A SARIF 2.1.0 log of violations, for code scanning tools
+    Junit,      // JUnit XML, one testcase per file
+    Checkstyle, // Checkstyle XML, one <file> per path with an <error> per violation
+}
+
+impl std::fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OutputFormat::Text => write!(f, "text"),
+            OutputFormat::Json => write!(f, "json"),
+            OutputFormat::Ndjson => write!(f, "ndjson"),
+            OutputFormat::Sarif => write!(f, "sarif"),
+            OutputFormat::Junit => write!(f, "junit"),
+            OutputFormat::Checkstyle => write!(f, "checkstyle"),
+        }
+    }
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            "ndjson" => Ok(OutputFormat::Ndjson),
+            "sarif" => Ok(OutputFormat::Sarif),
+            "junit" => Ok(OutputFormat::Junit),
+            "checkstyle" => Ok(OutputFormat::Checkstyle),
+            _ => Err(format!(
+                "Unknown output format '{s}', expected text, json, ndjson, sarif, junit or checkstyle"
+            )),
+        }
+    }
+}
+
+/// Where the files to process come from
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum FileSource {
+    /// Glob patterns from the command line, expanded by walking the folder
+    #[default]
+    Patterns,
+    /// A list of paths from `--files-from`, read from stdin when the path is `-`
+    List { path: String, null_separated: bool },
+    /// Files tracked by git, from `--git-tracked`
+    GitTracked,
+    /// The staged content of files changed in the git index, from `--git-staged`
+    GitStaged,
+    /// Files changed since the working tree branched from a git ref, from `--git-changed`
+    GitChanged(String),
+    /// File names given to `hook run`, taken literally rather than as glob patterns
+    Files(Vec<String>),
+    /// Content read from stdin and converted to stdout by `--stdin`, with the rules for
+    /// `path` when `--stdin-path` is given
+    Stdin { path: Option<String> },
 }
 
 /// Configuration settings parsed from command line arguments
@@ -48,51 +357,223 @@ pub struct ConfigSettings {
     pub remove_bom: bool,
     pub recursive: bool,
     pub no_trash: bool,
+    /// Report files that don't match the requested fixes instead of modifying them
+    pub check: bool,
+    pub format: OutputFormat,
+    /// Per-file line endings from `.gitattributes`, overriding `line_ending_target`
+    pub gitattributes: Option<GitAttributes>,
+    /// Per-file line endings, charset and whitespace rules from `.editorconfig`,
+    /// overriding both `line_ending_target` and `.gitattributes`
+    pub editorconfig: Option<EditorConfig>,
+    /// Include and exclude globs and per-glob rules from `.line-endings.toml`, overriding
+    /// every other source of rules
+    pub project: Option<ProjectConfig>,
+    /// Fix files to match the rules in `.gitattributes`, `.editorconfig` and
+    /// `.line-endings.toml`, without any other fix option
+    pub fix: bool,
+    /// Report the changes the fix options would make instead of making them
+    pub dry_run: bool,
+    /// Show the changes of a dry run as a unified diff
+    pub diff: bool,
+    /// `--exclude` globs, in `.gitignore` syntax relative to the folder
+    pub exclude: Gitignore,
+    /// Skip the `.git` directory and files ignored by `.gitignore`, `.ignore` and the global
+    /// git excludes file when searching for files
+    pub use_ignore_files: bool,
+    /// Where the files to process come from
+    pub file_source: FileSource,
+    /// Bytes read at a time when scanning the lines of a file
+    pub scan_buffer_size: usize,
     pub supplied_paths: Vec<String>,
     pub folder: Option<String>,
 }
 
 impl ConfigSettings {
-    /// Returns true if any line ending rewrite option is set
+    /// Returns true if any option asks for files to be fixed. Loading `.gitattributes`,
+    /// `.editorconfig` or `.line-endings.toml` does not on its own.
+    #[must_use]
+    pub fn has_fix_option(&self) -> bool {
+        self.line_ending_target != LineEndingTarget::None || self.remove_bom || self.fix
+    }
+
+    /// Returns true if any line ending rewrite option is set. The rules in `.gitattributes`,
+    /// `.editorconfig` and `.line-endings.toml` only rewrite files when a fix is asked for.
     #[must_use]
     pub fn has_rewrite_option(&self) -> bool {
         self.line_ending_target != LineEndingTarget::None
+            || (self.has_rule_files() && self.has_fix_option())
+    }
+
+    /// Returns true if per-file BOM, final newline and whitespace rules should be fixed,
+    /// from `.editorconfig` or `.line-endings.toml` when a fix is asked for
+    #[must_use]
+    pub fn has_per_file_fixes(&self) -> bool {
+        (self.editorconfig.is_some() || self.project.is_some()) && self.has_fix_option()
+    }
+
+    /// Returns true if any file with per-file rules is in use
+    fn has_rule_files(&self) -> bool {
+        self.gitattributes.is_some() || self.editorconfig.is_some() || self.project.is_some()
+    }
+
+    /// Returns true if a path is part of the project, by the include and exclude globs
+    /// in `.line-endings.toml`. Every path is when there is no such file.
+    #[must_use]
+    pub fn is_included(&self, path: &Path) -> bool {
+        self.project
+            .as_ref()
+            .is_none_or(|project| project.includes(path))
+    }
+
+    /// Returns the policy that files are checked against, built from the fix options
+    #[must_use]
+    pub fn policy(&self) -> FilePolicy {
+        FilePolicy {
+            line_ending: self.line_ending_target.line_ending(),
+            forbid_bom: self.remove_bom,
+            ..FilePolicy::default()
+        }
+    }
+
+    /// Returns the policy for one file, overlaying `.gitattributes`, `.editorconfig` and then
+    /// `.line-endings.toml` on the fix options when they are in use. Returns `None` for files
+    /// marked `binary` or `-text` in `.gitattributes`, which must be left alone.
+    #[must_use]
+    pub fn policy_for(&self, path: &Path) -> Option<FilePolicy> {
+        let mut policy = self.policy();
+
+        if let Some(attributes) = &self.gitattributes {
+            match attributes.eol_for(path) {
+                EolAttribute::NotText => return None,
+                EolAttribute::Ending(ending) => policy.line_ending = Some(ending),
+                EolAttribute::Unspecified => {}
+            }
+        }
+
+        if let Some(editorconfig) = &self.editorconfig {
+            let properties = editorconfig.properties_for(path);
+            if let Some(ending) = properties.end_of_line {
+                policy.line_ending = Some(ending);
+            }
+            if let Some(charset) = properties.charset {
+                policy.encoding = Some(charset.encoding());
+                policy.require_bom = charset == Charset::Utf8Bom;
+                policy.forbid_bom = matches!(charset, Charset::Utf8 | Charset::Latin1);
+            }
+            if properties.insert_final_newline.is_some() {
+                policy.final_newline = properties.insert_final_newline;
+            }
+            if let Some(trim) = properties.trim_trailing_whitespace {
+                policy.trim_trailing_whitespace = trim;
+            }
+        }
+
+        if let Some(project) = &self.project {
+            let rules = project.rules_for(path);
+            if let Some(ending) = rules.line_ending {
+                policy.line_ending = Some(ending);
+            }
+            if let Some(bom) = rules.bom {
+                policy.require_bom = bom == BomRule::Required;
+                policy.forbid_bom = bom == BomRule::Forbidden;
+            }
+            if rules.final_newline.is_some() {
+                policy.final_newline = rules.final_newline;
+            }
+            policy.max_size = rules.max_size;
+        }
+
+        Some(policy)
     }
 }
 
+/// Settings for `hook run`, which checks files before a commit
+pub struct HookSettings {
+    pub config: ConfigSettings,
+    /// Fix the files that fail the check, in the working tree
+    pub autofix: bool,
+    /// Add the fixed files back to the index so the commit goes ahead with them
+    pub restage: bool,
+}
+
+/// Whitespace changes to make while rewriting a file
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct WhitespaceFixes {
+    /// Remove spaces and tabs at the end of every line
+    pub trim_trailing_whitespace: bool,
+    /// `Some(true)` to end the last line with `ending`, `Some(false)` to remove every
+    /// terminator at the end of the file
+    pub final_newline: Option<bool>,
+    /// Terminator appended when a final newline is added
+    pub ending: LineEnding,
+}
+
 /// Stores the results of line ending analysis for a file
 #[derive(Debug, Clone)]
 pub struct FileAnalysis {
     pub path: PathBuf,
+    /// Size of the file in bytes
+    pub size: u64,
     pub lf_count: usize,
     pub crlf_count: usize,
+    pub cr_count: usize,
+    /// Lines ending with a space or tab before their terminator (or the end of the file)
+    pub trailing_whitespace_lines: usize,
+    /// `true` if the last line ends with a terminator, `None` for an empty file
+    pub final_newline: Option<bool>,
+    /// Where each kind of terminator and trailing whitespace first occur, for reports
+    pub first_lines: FirstLineEndings,
     /// `true` if the BOM check was requested (--bom or --remove-bom flags).
     /// Distinguish "no BOM found" (`bom_checked = true, bom_type = None`) from
     /// "check not requested" (`bom_checked = false`).
     pub bom_checked: bool,
     /// The BOM type found, or `None` if no BOM was found (only valid when `bom_checked = true`).
     pub bom_type: Option<BomType>,
+    /// Encoding the line endings were counted in, detected from the BOM or content
+    pub encoding: TextEncoding,
     pub is_binary: bool,
     pub error: Option<String>,
 }
 
 impl FileAnalysis {
-    /// Returns true if the file has mixed line endings
+    /// Returns true if the file has more than one kind of line ending (LF, CRLF or lone CR)
     #[must_use]
     pub fn has_mixed_line_endings(&self) -> bool {
-        self.lf_count > 0 && self.crlf_count > 0
+        [self.lf_count, self.crlf_count, self.cr_count]
+            .iter()
+            .filter(|&&count| count > 0)
+            .count()
+            > 1
     }
 
     /// Returns true if the file has only LF line endings
     #[must_use]
     pub fn is_lf_only(&self) -> bool {
-        self.lf_count > 0 && self.crlf_count == 0
+        self.lf_count > 0 && self.crlf_count == 0 && self.cr_count == 0
     }
 
     /// Returns true if the file has only CRLF line endings
     #[must_use]
     pub fn is_crlf_only(&self) -> bool {
-        self.lf_count == 0 && self.crlf_count > 0
+        self.lf_count == 0 && self.crlf_count > 0 && self.cr_count == 0
+    }
+
+    /// Returns true if the file has only lone CR (classic Mac) line endings
+    #[must_use]
+    pub fn is_cr_only(&self) -> bool {
+        self.lf_count == 0 && self.crlf_count == 0 && self.cr_count > 0
+    }
+
+    /// Returns true if the file contains any line ending other than `ending`
+    #[must_use]
+    pub fn has_line_endings_other_than(&self, ending: LineEnding) -> bool {
+        let has_any = self.lf_count > 0 || self.crlf_count > 0 || self.cr_count > 0;
+        let only_target = match ending {
+            LineEnding::Lf => self.is_lf_only(),
+            LineEnding::Crlf => self.is_crlf_only(),
+            LineEnding::Cr => self.is_cr_only(),
+        };
+        has_any && !only_target
     }
 
     /// Returns true if the BOM check ran and a BOM was found
@@ -102,19 +583,90 @@ impl FileAnalysis {
     }
 }
 
-/// Stores the result of a file rewrite operation
-#[derive(Debug, Clone)]
-pub struct RewriteResult {
-    pub path: PathBuf,
-    pub rewritten: bool,
-    pub error: Option<String>,
+/// A file found by the search, with the index of the file pattern that matched it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatchedPath {
+    pub pattern_index: usize,
+    pub path: String,
+    /// Object id of the staged content to analyse instead of the file, for `--git-staged`
+    pub index_blob: Option<String>,
+}
+
+impl MatchedPath {
+    /// Orders paths by the pattern that found them, then case-insensitively by path
+    #[must_use]
+    pub fn sort_key(&self) -> (usize, String, String) {
+        (
+            self.pattern_index,
+            self.path.to_lowercase(),
+            self.path.clone(),
+        )
+    }
+}
+
+/// Totals accumulated across all analysed files, used for the summary output
+#[derive(Debug, Clone, Default)]
+pub struct AnalysisSummary {
+    pub analyzed_files: usize,
+    pub binary_files: usize,
+    pub error_files: usize,
+    pub mixed_files: usize,
+    pub total_lf: usize,
+    pub total_crlf: usize,
+    pub total_cr: usize,
+    /// Files with at least one violation, only counted in check mode
+    pub failed_files: usize,
+    pub analysis_duration: Duration,
+    pub total_duration: Duration,
 }
 
-/// Stores the result of a BOM removal operation
+impl AnalysisSummary {
+    /// Adds the line ending totals of a successfully analysed file
+    pub fn add_analyzed(&mut self, result: &FileAnalysis) {
+        self.analyzed_files += 1;
+        self.total_lf += result.lf_count;
+        self.total_crlf += result.crlf_count;
+        self.total_cr += result.cr_count;
+        if result.has_mixed_line_endings() {
+            self.mixed_files += 1;
+        }
+    }
+}
+
+/// Changes that fixing a file makes, worked out from its analysis so that they can be applied
+/// in one pass or reported by `--dry-run`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PlannedFix {
+    /// Line ending to rewrite the file with, when it has terminators of another kind
+    pub line_ending: Option<LineEnding>,
+    /// Number of line terminators replaced
+    pub terminators: usize,
+    /// BOM to remove
+    pub remove_bom: Option<BomType>,
+    /// BOM left in place although the policy forbids BOMs, as the encoding needs it
+    pub kept_bom: Option<BomType>,
+    /// `true` to add a UTF-8 BOM
+    pub add_bom: bool,
+    /// Trailing whitespace and final newline fixes, when the file needs any
+    pub whitespace: Option<WhitespaceFixes>,
+}
+
+impl PlannedFix {
+    /// Returns true if there is nothing to fix
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.line_ending.is_none()
+            && self.remove_bom.is_none()
+            && !self.add_bom
+            && self.whitespace.is_none()
+    }
+}
+
+/// Stores the result of fixing a file with every planned fix in one pass
 #[derive(Debug, Clone)]
-pub struct BomRemovalResult {
+pub struct FixResult {
     pub path: PathBuf,
-    pub removed: bool,
-    pub bom_type: Option<BomType>,
+    /// The fixes made, which are empty when the file was skipped
+    pub fix: PlannedFix,
     pub error: Option<String>,
 }
@@ -1,6 +1,6 @@
 #[cfg(test)]
 mod tests {
-    use crate::types::{BomType, FileAnalysis};
+    use crate::types::{BomType, FileAnalysis, FirstLineEndings, TextEncoding};
     use std::path::PathBuf;
 
     #[test]
@@ -8,9 +8,15 @@ mod tests {
         // bom_checked = false means the check was not requested
         let not_checked = FileAnalysis {
             path: PathBuf::from("test.txt"),
+            size: 0,
             lf_count: 5,
             crlf_count: 0,
+            cr_count: 0,
+            trailing_whitespace_lines: 0,
+            final_newline: Some(true),
+            first_lines: FirstLineEndings::default(),
             bom_type: None,
+            encoding: TextEncoding::Utf8,
             bom_checked: false,
             is_binary: false,
             error: None,
@@ -27,9 +33,15 @@ mod tests {
         // bom_checked = true, bom_type = None means checked, no BOM found
         let checked_no_bom = FileAnalysis {
             path: PathBuf::from("test.txt"),
+            size: 0,
             lf_count: 5,
             crlf_count: 0,
+            cr_count: 0,
+            trailing_whitespace_lines: 0,
+            final_newline: Some(true),
+            first_lines: FirstLineEndings::default(),
             bom_type: None,
+            encoding: TextEncoding::Utf8,
             bom_checked: true,
             is_binary: false,
             error: None,
@@ -46,9 +58,15 @@ mod tests {
         // bom_checked = true, bom_type = Some means BOM found
         let checked_with_bom = FileAnalysis {
             path: PathBuf::from("test.txt"),
+            size: 0,
             lf_count: 5,
             crlf_count: 0,
+            cr_count: 0,
+            trailing_whitespace_lines: 0,
+            final_newline: Some(true),
+            first_lines: FirstLineEndings::default(),
             bom_type: Some(BomType::Utf8),
+            encoding: TextEncoding::Utf8,
             bom_checked: true,
             is_binary: false,
             error: None,
@@ -67,10 +85,16 @@ mod tests {
     fn test_binary_file_analysis_has_is_binary_flag() {
         let binary = FileAnalysis {
             path: PathBuf::from("image.png"),
+            size: 0,
             lf_count: 0,
             crlf_count: 0,
+            cr_count: 0,
+            trailing_whitespace_lines: 0,
+            final_newline: Some(true),
+            first_lines: FirstLineEndings::default(),
             bom_checked: false,
             bom_type: None,
+            encoding: TextEncoding::Utf8,
             is_binary: true,
             error: None,
         };
@@ -78,10 +102,16 @@ mod tests {
 
         let text = FileAnalysis {
             path: PathBuf::from("readme.txt"),
+            size: 0,
             lf_count: 10,
             crlf_count: 0,
+            cr_count: 0,
+            trailing_whitespace_lines: 0,
+            final_newline: Some(true),
+            first_lines: FirstLineEndings::default(),
             bom_checked: false,
             bom_type: None,
+            encoding: TextEncoding::Utf8,
             is_binary: false,
             error: None,
         };
@@ -91,15 +121,21 @@ mod tests {
     /// Test the `has_bom()` method with different `BomType` variants
     /// This test ensures the bug fix for unsafe unwrap operation works correctly
     #[test]
-    #[allow(clippy::similar_names)] // BOM variant names are intentionally similar
+    #[allow(clippy::similar_names, clippy::too_many_lines)] // BOM variant names are intentionally similar
     fn test_has_bom_method_with_different_bom_types() {
         // No BOM found (check ran, nothing found) — bom_type: None
         let no_bom = FileAnalysis {
             path: PathBuf::from("test.txt"),
+            size: 0,
             lf_count: 0,
             crlf_count: 0,
+            cr_count: 0,
+            trailing_whitespace_lines: 0,
+            final_newline: Some(true),
+            first_lines: FirstLineEndings::default(),
             bom_checked: true,
             bom_type: None,
+            encoding: TextEncoding::Utf8,
             is_binary: false,
             error: None,
         };
@@ -108,10 +144,16 @@ mod tests {
         // BOM check not requested — bom_type: None, bom_checked: false
         let not_checked = FileAnalysis {
             path: PathBuf::from("test.txt"),
+            size: 0,
             lf_count: 0,
             crlf_count: 0,
+            cr_count: 0,
+            trailing_whitespace_lines: 0,
+            final_newline: Some(true),
+            first_lines: FirstLineEndings::default(),
             bom_checked: false,
             bom_type: None,
+            encoding: TextEncoding::Utf8,
             is_binary: false,
             error: None,
         };
@@ -120,10 +162,16 @@ mod tests {
         // UTF-8 BOM
         let analysis_utf8 = FileAnalysis {
             path: PathBuf::from("test.txt"),
+            size: 0,
             lf_count: 0,
             crlf_count: 0,
+            cr_count: 0,
+            trailing_whitespace_lines: 0,
+            final_newline: Some(true),
+            first_lines: FirstLineEndings::default(),
             bom_checked: true,
             bom_type: Some(BomType::Utf8),
+            encoding: TextEncoding::Utf8,
             is_binary: false,
             error: None,
         };
@@ -132,10 +180,16 @@ mod tests {
         // UTF-16 LE BOM
         let analysis_utf16_le = FileAnalysis {
             path: PathBuf::from("test.txt"),
+            size: 0,
             lf_count: 0,
             crlf_count: 0,
+            cr_count: 0,
+            trailing_whitespace_lines: 0,
+            final_newline: Some(true),
+            first_lines: FirstLineEndings::default(),
             bom_checked: true,
             bom_type: Some(BomType::Utf16Le),
+            encoding: TextEncoding::Utf8,
             is_binary: false,
             error: None,
         };
@@ -147,10 +201,16 @@ mod tests {
         // UTF-16 BE BOM
         let analysis_utf16_be = FileAnalysis {
             path: PathBuf::from("test.txt"),
+            size: 0,
             lf_count: 0,
             crlf_count: 0,
+            cr_count: 0,
+            trailing_whitespace_lines: 0,
+            final_newline: Some(true),
+            first_lines: FirstLineEndings::default(),
             bom_checked: true,
             bom_type: Some(BomType::Utf16Be),
+            encoding: TextEncoding::Utf8,
             is_binary: false,
             error: None,
         };
@@ -162,10 +222,16 @@ mod tests {
         // UTF-32 LE BOM
         let analysis_utf32_le = FileAnalysis {
             path: PathBuf::from("test.txt"),
+            size: 0,
             lf_count: 0,
             crlf_count: 0,
+            cr_count: 0,
+            trailing_whitespace_lines: 0,
+            final_newline: Some(true),
+            first_lines: FirstLineEndings::default(),
             bom_checked: true,
             bom_type: Some(BomType::Utf32Le),
+            encoding: TextEncoding::Utf8,
             is_binary: false,
             error: None,
         };
@@ -177,10 +243,16 @@ mod tests {
         // UTF-32 BE BOM
         let analysis_utf32_be = FileAnalysis {
             path: PathBuf::from("test.txt"),
+            size: 0,
             lf_count: 0,
             crlf_count: 0,
+            cr_count: 0,
+            trailing_whitespace_lines: 0,
+            final_newline: Some(true),
+            first_lines: FirstLineEndings::default(),
             bom_checked: true,
             bom_type: Some(BomType::Utf32Be),
+            encoding: TextEncoding::Utf8,
             is_binary: false,
             error: None,
         };
@@ -205,10 +277,16 @@ mod tests {
     fn test_has_bom_is_idempotent() {
         let with_bom = FileAnalysis {
             path: PathBuf::from("test.txt"),
+            size: 0,
             lf_count: 0,
             crlf_count: 0,
+            cr_count: 0,
+            trailing_whitespace_lines: 0,
+            final_newline: Some(true),
+            first_lines: FirstLineEndings::default(),
             bom_checked: true,
             bom_type: Some(BomType::Utf8),
+            encoding: TextEncoding::Utf8,
             is_binary: false,
             error: None,
         };
@@ -217,14 +295,48 @@ mod tests {
 
         let without_bom = FileAnalysis {
             path: PathBuf::from("test.txt"),
+            size: 0,
             lf_count: 0,
             crlf_count: 0,
+            cr_count: 0,
+            trailing_whitespace_lines: 0,
+            final_newline: Some(true),
+            first_lines: FirstLineEndings::default(),
             bom_checked: true,
             bom_type: None,
+            encoding: TextEncoding::Utf8,
             is_binary: false,
             error: None,
         };
         assert!(!without_bom.has_bom());
         assert!(!without_bom.has_bom());
     }
+
+    /// Lone CR terminators count as a separate kind for mixed detection
+    #[test]
+    fn test_mixed_detection_includes_lone_cr() {
+        let make = |lf_count, crlf_count, cr_count| FileAnalysis {
+            path: PathBuf::from("test.txt"),
+            size: 0,
+            lf_count,
+            crlf_count,
+            cr_count,
+            trailing_whitespace_lines: 0,
+            final_newline: Some(true),
+            first_lines: FirstLineEndings::default(),
+            bom_checked: false,
+            bom_type: None,
+            encoding: TextEncoding::Utf8,
+            is_binary: false,
+            error: None,
+        };
+
+        assert!(!make(0, 0, 0).has_mixed_line_endings());
+        assert!(!make(0, 0, 4).has_mixed_line_endings());
+        assert!(make(3, 0, 1).has_mixed_line_endings());
+        assert!(make(0, 3, 1).has_mixed_line_endings());
+        assert!(make(3, 3, 0).has_mixed_line_endings());
+        assert!(!make(3, 0, 1).is_lf_only());
+        assert!(!make(0, 3, 1).is_crlf_only());
+    }
 }
@@ -1,7 +1,159 @@
-use anyhow::Result;
-use std::path::Path;
+use anyhow::{Context, Result};
+use ignore::gitignore::Gitignore;
+use ignore::{WalkBuilder, WalkState};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Sender};
 
-use crate::types::ConfigSettings;
+use crate::git::{self, GitFile};
+use crate::types::{ConfigSettings, FileSource, MatchedPath};
+
+/// Finds the files to process, from the glob patterns, the `--files-from` list or git, and
+/// sends them as they are found. Returns once every path has been sent.
+///
+/// # Errors
+///
+/// Returns an error if a glob is not valid, the file list cannot be read or git fails.
+pub fn stream_input_paths(config: &ConfigSettings, sender: Sender<MatchedPath>) -> Result<()> {
+    // git runs in the folder, so the paths it lists are relative to it
+    let git_dir = config.folder.as_deref().unwrap_or(".");
+
+    match &config.file_source {
+        FileSource::Patterns => stream_paths_matching_glob(config, sender),
+        FileSource::List {
+            path,
+            null_separated,
+        } => {
+            if path == "-" {
+                stream_listed_paths(config, io::stdin().lock(), *null_separated, &sender)
+            } else {
+                let file = File::open(path).with_context(|| format!("Failed to open {path}"))?;
+                stream_listed_paths(config, BufReader::new(file), *null_separated, &sender)
+            }
+        }
+        FileSource::Files(files) => {
+            for (pattern_index, file) in files.iter().enumerate() {
+                if !send_listed_path(config, pattern_index, file, &sender) {
+                    break;
+                }
+            }
+            Ok(())
+        }
+        // Converted straight from stdin to stdout, so there are no files to find
+        FileSource::Stdin { .. } => Err(anyhow::anyhow!("--stdin does not search for files")),
+        FileSource::GitTracked => {
+            stream_git_files(config, &git::tracked_files(git_dir)?, &sender);
+            Ok(())
+        }
+        FileSource::GitStaged => {
+            stream_git_files(config, &git::staged_files(git_dir)?, &sender);
+            Ok(())
+        }
+        FileSource::GitChanged(reference) => {
+            stream_git_files(config, &git::changed_files(git_dir, reference)?, &sender);
+            Ok(())
+        }
+    }
+}
+
+/// Sends the files selected from git that are not excluded. Working tree files that no
+/// longer exist are skipped, but staged content is sent whether or not the file exists.
+fn stream_git_files(config: &ConfigSettings, files: &[GitFile], sender: &Sender<MatchedPath>) {
+    for file in files {
+        let path = path_in_folder(config, &file.path);
+        if (file.blob.is_some() || file_exists(&path))
+            && !is_excluded(&config.exclude, &path)
+            && config.is_included(Path::new(&path))
+        {
+            let matched = MatchedPath {
+                pattern_index: 0,
+                path,
+                index_blob: file.blob.clone(),
+            };
+            // Stop if the caller has stopped listening
+            if sender.send(matched).is_err() {
+                break;
+            }
+        }
+    }
+}
+
+/// Resolves a relative path against the folder, leaving absolute paths alone
+fn path_in_folder(config: &ConfigSettings, path: &str) -> String {
+    match config.folder.as_deref() {
+        Some(folder) if folder != "." && Path::new(path).is_relative() => {
+            format!("{}/{}", folder.trim_end_matches('/'), path)
+        }
+        _ => path.to_string(),
+    }
+}
+
+/// Sends each path in a list, one per line or NUL separated, as it is read. The pattern
+/// index is the position in the list, so sorting keeps the list's order. Relative paths are
+/// relative to the folder. Paths that don't exist are still sent, so analysing them reports
+/// the error. Directories, and entries that are matched by `--exclude` or are not included
+/// by `.line-endings.toml`, are skipped; ignore files are not consulted.
+///
+/// # Errors
+///
+/// Returns an error if reading fails or a path is not valid UTF-8.
+pub fn stream_listed_paths(
+    config: &ConfigSettings,
+    reader: impl BufRead,
+    null_separated: bool,
+    sender: &Sender<MatchedPath>,
+) -> Result<()> {
+    let separator = if null_separated { b'\0' } else { b'\n' };
+
+    for (pattern_index, entry) in reader.split(separator).enumerate() {
+        let entry = String::from_utf8(entry?)
+            .map_err(|e| anyhow::anyhow!("File list entry is not valid UTF-8: {e}"))?;
+        // Lines may come from a tool writing CRLF
+        let entry = if null_separated {
+            entry.as_str()
+        } else {
+            entry.trim_end_matches('\r')
+        };
+        if entry.is_empty() {
+            continue;
+        }
+
+        // Stop reading if the caller has stopped listening
+        if !send_listed_path(config, pattern_index, entry, sender) {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Sends one listed path unless it is a directory or is excluded. A path that doesn't exist
+/// is sent, so that it shows up as an error rather than being silently dropped. Returns
+/// false if the caller has stopped listening.
+fn send_listed_path(
+    config: &ConfigSettings,
+    pattern_index: usize,
+    entry: &str,
+    sender: &Sender<MatchedPath>,
+) -> bool {
+    let path = path_in_folder(config, entry);
+    let missing = !Path::new(&path).exists();
+    if !(missing || file_exists(&path))
+        || is_excluded(&config.exclude, &path)
+        || !config.is_included(Path::new(&path))
+    {
+        return true;
+    }
+
+    let matched = MatchedPath {
+        pattern_index,
+        path,
+        index_blob: None,
+    };
+    sender.send(matched).is_ok()
+}
 
 /// function to take a glob and return a vector of path strings
 ///
@@ -13,64 +165,203 @@ pub fn get_paths_matching_glob(config: &ConfigSettings) -> Result<Vec<String>> {
     // eg given z*.txt a*.txt it will return:
     // ["z1.txt", "z2.txt", "a1.txt", "a2.txt"]
 
-    let glob_settings = glob::MatchOptions {
-        case_sensitive: config.case_sensitive,
-        require_literal_separator: false,
-        require_literal_leading_dot: false,
-    };
+    let (sender, receiver) = mpsc::channel();
+    stream_paths_matching_glob(config, sender)?;
 
-    // create a vector to hold the results, initial capacity is set to the number of supplied paths
-    let mut result = Vec::with_capacity(config.supplied_paths.len());
+    let mut matched: Vec<MatchedPath> = receiver.into_iter().collect();
+    matched.sort_by_cached_key(MatchedPath::sort_key);
+    Ok(matched.into_iter().map(|m| m.path).collect())
+}
 
-    for pattern in &config.supplied_paths {
-        // Build the full search pattern with folder prefix if specified
-        let full_pattern = if let Some(folder) = &config.folder {
-            // Don't add folder prefix if it's just "." (current directory)
-            if folder == "." {
-                pattern.clone()
+/// Walks for the files matching each glob and sends them as they are found, so they can
+/// be analysed while the walk continues. Each directory tree is walked on several threads,
+/// so paths arrive in no particular order; `MatchedPath::sort_key` restores the order of
+/// `get_paths_matching_glob`. Returns once every path has been sent.
+///
+/// # Errors
+///
+/// Returns an error if a glob is not valid. Nothing is sent in that case.
+pub fn stream_paths_matching_glob(
+    config: &ConfigSettings,
+    sender: Sender<MatchedPath>,
+) -> Result<()> {
+    // Check every glob before walking, so a bad one doesn't leave a partial list
+    let search_patterns = config
+        .supplied_paths
+        .iter()
+        .map(|pattern| {
+            let search_pattern = search_pattern_for(config, pattern);
+            let compiled = if search_pattern.contains(GLOB_CHARS) {
+                Some(glob::Pattern::new(&search_pattern)?)
             } else {
-                format!("{}/{}", folder.trim_end_matches('/'), pattern)
-            }
+                None
+            };
+            Ok((search_pattern, compiled))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    for (pattern_index, (search_pattern, compiled)) in search_patterns.into_iter().enumerate() {
+        // A pattern without wildcards names a single file, as does a glob that matches
+        // nothing but is itself a file name, such as `report[1].txt`
+        let found = compiled.is_some_and(|compiled| {
+            walk_matching_files(&search_pattern, &compiled, pattern_index, config, &sender)
+        });
+        if !found
+            && file_exists(&search_pattern)
+            && !is_excluded(&config.exclude, &search_pattern)
+            && config.is_included(Path::new(&search_pattern))
+        {
+            // The receiver only goes away if the caller has stopped listening
+            let _ = sender.send(MatchedPath {
+                pattern_index,
+                path: search_pattern,
+                index_blob: None,
+            });
+        }
+    }
+
+    // The walkers' clones are gone, so this ends the receiver's iteration
+    drop(sender);
+    Ok(())
+}
+
+/// Builds the pattern to search for, with the folder prefix and, if recursive, `**/`
+fn search_pattern_for(config: &ConfigSettings, pattern: &str) -> String {
+    // Build the full search pattern with folder prefix if specified
+    let full_pattern = if let Some(folder) = &config.folder {
+        // Don't add folder prefix if it's just "." (current directory)
+        if folder == "." {
+            pattern.to_string()
         } else {
-            pattern.clone()
-        };
+            format!("{}/{}", folder.trim_end_matches('/'), pattern)
+        }
+    } else {
+        pattern.to_string()
+    };
 
-        // If recursive is enabled, modify the pattern to search subdirectories
-        let search_pattern = if config.recursive && !full_pattern.contains("**/") {
-            if let Some(folder) = &config.folder {
-                // Don't add folder prefix if it's just "." (current directory)
-                if folder == "." {
-                    format!("**/{pattern}")
-                } else {
-                    format!("{}/**/{}", folder.trim_end_matches('/'), pattern)
-                }
-            } else {
+    // If recursive is enabled, modify the pattern to search subdirectories
+    if config.recursive && !full_pattern.contains("**/") {
+        if let Some(folder) = &config.folder {
+            // Don't add folder prefix if it's just "." (current directory)
+            if folder == "." {
                 format!("**/{pattern}")
+            } else {
+                format!("{}/**/{}", folder.trim_end_matches('/'), pattern)
             }
         } else {
-            full_pattern
-        };
-
-        // Try to match the pattern as a glob
-        let mut glob_matches: Vec<_> = glob::glob_with(&search_pattern, glob_settings)?
-            .filter_map(|entry| match entry {
-                Ok(path) if path.is_file() => Some(path.to_string_lossy().into_owned()),
-                _ => None,
-            })
-            .collect();
-
-        // If the glob matched nothing, check if the pattern itself is a valid file
-        if glob_matches.is_empty() && file_exists(&search_pattern) {
-            result.push(search_pattern);
-        } else {
-            // If glob matches were found, sort them and extend the result vector
-            // glob_matches.sort(); // Sorts in lexicographical order
-            glob_matches.sort_by_key(|x| x.to_lowercase()); // Sorts in case-insensitive order
-            result.extend(glob_matches);
+            format!("**/{pattern}")
         }
+    } else {
+        full_pattern
     }
+}
+
+/// Characters that make a pattern a glob rather than a file name
+const GLOB_CHARS: [char; 3] = ['*', '?', '['];
+
+/// Walks the directory a glob starts in on several threads, sending each file that matches
+/// it and is included by `.line-endings.toml`. The walk skips paths matched by `--exclude`
+/// and, unless `--no-ignore` is given, the `.git` directory and paths ignored by
+/// `.gitignore`, `.ignore` and the global git excludes file. Returns true if any file
+/// matched.
+fn walk_matching_files(
+    search_pattern: &str,
+    pattern: &glob::Pattern,
+    pattern_index: usize,
+    config: &ConfigSettings,
+    sender: &Sender<MatchedPath>,
+) -> bool {
+    let match_options = glob::MatchOptions {
+        case_sensitive: config.case_sensitive,
+        // Each `*` matches within one directory, as when the glob is expanded level by level
+        require_literal_separator: true,
+        require_literal_leading_dot: false,
+    };
+
+    // Start from the directories at the front of the pattern that have no wildcards
+    let components: Vec<&str> = search_pattern.split('/').collect();
+    let literal_count = components[..components.len() - 1]
+        .iter()
+        .take_while(|component| !component.contains(GLOB_CHARS))
+        .count();
+    let base = components[..literal_count].join("/");
+    let root = match (literal_count, base.is_empty()) {
+        (0, _) => ".",
+        (_, true) => "/",
+        (_, false) => base.as_str(),
+    };
+
+    // `**` can match any depth, otherwise each remaining component is one level
+    let remaining = &components[literal_count..];
+    let max_depth = if remaining.iter().any(|component| component.contains("**")) {
+        None
+    } else {
+        Some(remaining.len())
+    };
+
+    let mut builder = WalkBuilder::new(root);
+    builder
+        .standard_filters(config.use_ignore_files)
+        .hidden(false)
+        .follow_links(true)
+        .max_depth(max_depth);
+
+    let exclude = config.exclude.clone();
+    let skip_git_directory = config.use_ignore_files;
+    builder.filter_entry(move |entry| {
+        let is_dir = entry
+            .file_type()
+            .is_some_and(|file_type| file_type.is_dir());
+        let is_git_directory = skip_git_directory && is_dir && entry.file_name() == ".git";
+        !is_git_directory && !exclude.matched(entry.path(), is_dir).is_ignore()
+    });
 
-    Ok(result)
+    let found = AtomicBool::new(false);
+    builder.build_parallel().run(|| {
+        let sender = sender.clone();
+        let found = &found;
+        Box::new(move |entry| {
+            // Unreadable directories are skipped, as glob expansion does
+            let Ok(entry) = entry else {
+                return WalkState::Continue;
+            };
+
+            // Paths walked from the current directory are matched without the leading `./`
+            let path = if literal_count == 0 {
+                entry.path().strip_prefix(".").unwrap_or(entry.path())
+            } else {
+                entry.path()
+            };
+
+            if path.is_file()
+                && pattern.matches_path_with(path, match_options)
+                && config.is_included(path)
+            {
+                let matched = MatchedPath {
+                    pattern_index,
+                    path: path.to_string_lossy().into_owned(),
+                    index_blob: None,
+                };
+                found.store(true, Ordering::Relaxed);
+                // Stop walking if the caller has stopped listening
+                if sender.send(matched).is_err() {
+                    return WalkState::Quit;
+                }
+            }
+            WalkState::Continue
+        })
+    });
+    found.into_inner()
+}
+
+/// Returns true if `--exclude` matches a path or any directory above it
+fn is_excluded(exclude: &Gitignore, path: impl AsRef<Path>) -> bool {
+    let path = path.as_ref();
+    exclude.matched(path, false).is_ignore()
+        || path
+            .ancestors()
+            .skip(1)
+            .any(|parent| exclude.matched(parent, true).is_ignore())
 }
 
 /// check if file exists
@@ -78,3 +369,70 @@ pub fn file_exists(path: impl AsRef<Path>) -> bool {
     let path_ref = path.as_ref();
     path_ref.exists() && path_ref.is_file()
 }
+
+/// Removes `.` and resolves `..` components without touching the file system
+#[must_use]
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+/// Reads `reader` to the end in `buffer_size` chunks and calls `f` with each complete
+/// code unit of `width` bytes, including units whose bytes are split across two reads.
+/// Returns the bytes of an incomplete trailing code unit, if any.
+///
+/// # Errors
+///
+/// Returns an error if reading fails or if `f` returns an error.
+pub fn for_each_code_unit<R: Read, F: FnMut(&[u8]) -> io::Result<()>>(
+    reader: &mut R,
+    width: usize,
+    buffer_size: usize,
+    mut f: F,
+) -> io::Result<Vec<u8>> {
+    let mut buffer = vec![0u8; buffer_size];
+
+    // Bytes of a code unit split across two reads
+    let mut pending = [0u8; 4];
+    let mut pending_len = 0;
+
+    loop {
+        let n = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        let mut data = &buffer[..n];
+
+        if pending_len > 0 {
+            let take = (width - pending_len).min(data.len());
+            pending[pending_len..pending_len + take].copy_from_slice(&data[..take]);
+            pending_len += take;
+            data = &data[take..];
+            if pending_len == width {
+                f(&pending[..width])?;
+                pending_len = 0;
+            }
+        }
+
+        let mut units = data.chunks_exact(width);
+        for unit in &mut units {
+            f(unit)?;
+        }
+        let remainder = units.remainder();
+        pending[pending_len..pending_len + remainder.len()].copy_from_slice(remainder);
+        pending_len += remainder.len();
+    }
+
+    Ok(pending[..pending_len].to_vec())
+}
@@ -1,11 +1,17 @@
+use ignore::gitignore::Gitignore;
 use std::fs;
 use std::io::Write;
 use std::path::Path;
 use tempfile::TempDir;
 
-use line_endings::analysis::{analyze_file, count_line_endings_in_file, detect_bom};
-use line_endings::processing::{remove_bom_from_files, rewrite_files};
-use line_endings::types::{BomType, ConfigSettings, LineEndingTarget};
+use line_endings::analysis::{
+    DEFAULT_SCAN_BUFFER_SIZE, analyze_bytes, analyze_file, count_line_endings_buffered,
+    count_line_endings_in_file, count_line_endings_scalar, detect_bom,
+};
+use line_endings::processing::{fix_files, rewrite_file_with_line_ending};
+use line_endings::types::{
+    BomType, ConfigSettings, FileSource, LineEnding, LineEndingTarget, OutputFormat, TextEncoding,
+};
 
 /// Helper function to create a temporary directory and copy test files into it
 fn setup_test_environment() -> TempDir {
@@ -48,6 +54,18 @@ fn create_test_config() -> ConfigSettings {
         remove_bom: false,
         recursive: true,
         no_trash: false,
+        check: false,
+        format: OutputFormat::Text,
+        gitattributes: None,
+        editorconfig: None,
+        project: None,
+        fix: false,
+        dry_run: false,
+        diff: false,
+        exclude: Gitignore::empty(),
+        use_ignore_files: true,
+        file_source: FileSource::Patterns,
+        scan_buffer_size: DEFAULT_SCAN_BUFFER_SIZE,
         supplied_paths: vec![],
         folder: None,
     }
@@ -183,7 +201,7 @@ fn test_line_ending_conversion_to_windows() {
     );
 
     let file_list = vec![original_analysis];
-    let result = rewrite_files(&config, &file_list);
+    let result = fix_files(&config, &file_list);
     assert!(result.is_ok(), "File rewrite should succeed");
 
     // Verify conversion
@@ -213,7 +231,7 @@ fn test_line_ending_conversion_to_linux() {
     );
 
     let file_list = vec![original_analysis];
-    let result = rewrite_files(&config, &file_list);
+    let result = fix_files(&config, &file_list);
     assert!(result.is_ok(), "File rewrite should succeed");
 
     // Verify conversion
@@ -240,7 +258,7 @@ fn test_bom_removal() {
     assert!(original_analysis.has_bom(), "Original file should have BOM");
 
     let file_list = vec![original_analysis];
-    let result = remove_bom_from_files(&config, &file_list);
+    let result = fix_files(&config, &file_list);
     assert!(result.is_ok(), "BOM removal should succeed");
 
     // Verify BOM removal
@@ -267,17 +285,10 @@ fn test_combined_bom_removal_and_line_ending_conversion() {
         "Original file should have CRLF only"
     );
 
+    // Both fixes are applied in one pass
     let file_list = vec![original_analysis];
-
-    // First convert line endings
-    let result = rewrite_files(&config, &file_list);
-    assert!(result.is_ok(), "Line ending conversion should succeed");
-
-    // Then remove BOM
-    let intermediate_analysis = analyze_file(&has_bom_path, &config);
-    let bom_files = vec![intermediate_analysis];
-    let result = remove_bom_from_files(&config, &bom_files);
-    assert!(result.is_ok(), "BOM removal should succeed");
+    let result = fix_files(&config, &file_list);
+    assert!(result.is_ok(), "Fixing should succeed");
 
     // Verify both operations
     let final_analysis = analyze_file(&has_bom_path, &config);
@@ -288,6 +299,77 @@ fn test_combined_bom_removal_and_line_ending_conversion() {
     );
 }
 
+#[test]
+fn test_fix_files_converts_and_removes_bom_in_one_pass() {
+    let temp_dir = setup_test_environment();
+    let mut config = create_test_config();
+    config.remove_bom = true;
+    config.line_ending_target = LineEndingTarget::Linux;
+
+    let has_bom_path = temp_dir.path().join("has_bom.txt");
+    let original = fs::read(&has_bom_path).expect("Failed to read file");
+    let analysis = analyze_file(&has_bom_path, &config);
+    assert!(analysis.has_bom() && analysis.is_crlf_only());
+
+    let result = fix_files(&config, &[analysis]);
+    assert!(result.is_ok(), "Fixing should succeed");
+
+    // Both fixes come from the one analysis, and the backup is of the original file
+    let final_analysis = analyze_file(&has_bom_path, &config);
+    assert!(!final_analysis.has_bom(), "BOM should be removed");
+    assert!(final_analysis.is_lf_only(), "File should have LF only");
+    let backup = temp_dir.path().join("has_bom.txt.bak");
+    assert_eq!(fs::read(backup).expect("Backup should exist"), original);
+}
+
+#[test]
+fn test_apply_fix_streams_every_stage() {
+    use line_endings::processing::apply_fix;
+    use line_endings::types::{BomType, LineEnding, PlannedFix, WhitespaceFixes};
+
+    // Large enough to span many read buffers
+    let line = "trailing \r\n";
+    let mut input = b"\xef\xbb\xbf".to_vec();
+    input.extend(line.repeat(50_000).bytes());
+    input.extend_from_slice(b"last\t");
+
+    let fix = PlannedFix {
+        line_ending: Some(LineEnding::Lf),
+        terminators: 50_000,
+        remove_bom: Some(BomType::Utf8),
+        kept_bom: None,
+        add_bom: false,
+        whitespace: Some(WhitespaceFixes {
+            trim_trailing_whitespace: true,
+            final_newline: Some(true),
+            ending: LineEnding::Lf,
+        }),
+    };
+    let mut output = Vec::new();
+    apply_fix(input.as_slice(), &mut output, &fix, TextEncoding::Utf8).unwrap();
+
+    let mut expected = "trailing\n".repeat(50_000).into_bytes();
+    expected.extend_from_slice(b"last\n");
+    assert_eq!(output, expected);
+
+    // A UTF-16 BOM is removed whole, and the stages keep the code unit width
+    let fix = PlannedFix {
+        line_ending: Some(LineEnding::Crlf),
+        terminators: 1,
+        remove_bom: Some(BomType::Utf16Le),
+        ..PlannedFix::default()
+    };
+    let mut output = Vec::new();
+    apply_fix(
+        encode_wide("a\nb", TextEncoding::Utf16Le, true).as_slice(),
+        &mut output,
+        &fix,
+        TextEncoding::Utf16Le,
+    )
+    .unwrap();
+    assert_eq!(output, encode_wide("a\r\nb", TextEncoding::Utf16Le, false));
+}
+
 #[test]
 fn test_original_test_folder_unchanged() {
     // This test ensures the original test_folder files are not modified
@@ -323,16 +405,16 @@ fn test_count_line_endings_directly() {
 
     // Test direct line ending counting without config
     let windows_file = temp_dir.path().join("test_windows.txt");
-    let (lf_count, crlf_count) =
-        count_line_endings_in_file(&windows_file).expect("Should count line endings");
-    assert_eq!(lf_count, 0, "Windows file should have no LF");
-    assert!(crlf_count > 0, "Windows file should have CRLF");
+    let counts = count_line_endings_in_file(&windows_file).expect("Should count line endings");
+    assert_eq!(counts.lf, 0, "Windows file should have no LF");
+    assert!(counts.crlf > 0, "Windows file should have CRLF");
+    assert_eq!(counts.cr, 0, "Windows file should have no lone CR");
 
     let linux_file = temp_dir.path().join("test_linux.txt");
-    let (lf_count, crlf_count) =
-        count_line_endings_in_file(&linux_file).expect("Should count line endings");
-    assert!(lf_count > 0, "Linux file should have LF");
-    assert_eq!(crlf_count, 0, "Linux file should have no CRLF");
+    let counts = count_line_endings_in_file(&linux_file).expect("Should count line endings");
+    assert!(counts.lf > 0, "Linux file should have LF");
+    assert_eq!(counts.crlf, 0, "Linux file should have no CRLF");
+    assert_eq!(counts.cr, 0, "Linux file should have no lone CR");
 }
 
 #[test]
@@ -517,6 +599,83 @@ fn test_file_with_only_cr() {
     assert!(analysis.error.is_none(), "CR file should not error");
     assert_eq!(analysis.lf_count, 0, "Should have no LF");
     assert_eq!(analysis.crlf_count, 0, "Should have no CRLF");
+    assert_eq!(analysis.cr_count, 3, "Should have 3 lone CR");
+    assert!(
+        !analysis.has_mixed_line_endings(),
+        "CR-only file is not mixed"
+    );
+}
+
+#[test]
+fn test_lone_cr_counts_toward_mixed() {
+    let temp_dir = TempDir::new().expect("Failed to create temporary directory");
+    let config = create_test_config();
+
+    let lf_and_cr = temp_dir.path().join("lf_and_cr.txt");
+    fs::write(&lf_and_cr, b"Line 1\nLine 2\rLine 3\n").expect("Failed to write file");
+    let analysis = analyze_file(&lf_and_cr, &config);
+    assert_eq!(analysis.lf_count, 2, "Should have 2 LF");
+    assert_eq!(analysis.cr_count, 1, "Should have 1 lone CR");
+    assert!(
+        analysis.has_mixed_line_endings(),
+        "LF plus lone CR should be mixed"
+    );
+    assert!(!analysis.is_lf_only(), "File with lone CR is not LF only");
+
+    let crlf_and_cr = temp_dir.path().join("crlf_and_cr.txt");
+    fs::write(&crlf_and_cr, b"Line 1\r\r\nLine 2\r\n").expect("Failed to write file");
+    let analysis = analyze_file(&crlf_and_cr, &config);
+    assert_eq!(analysis.crlf_count, 2, "Should have 2 CRLF");
+    assert_eq!(analysis.cr_count, 1, "CR before CRLF is a lone CR");
+    assert!(
+        analysis.has_mixed_line_endings(),
+        "CRLF plus lone CR should be mixed"
+    );
+}
+
+#[test]
+fn test_lone_cr_split_across_buffer_boundary() {
+    let temp_dir = TempDir::new().expect("Failed to create temporary directory");
+    let boundary = temp_dir.path().join("boundary.txt");
+
+    // CR is the last byte of the first buffer read, followed by a non-LF byte
+    let mut content = vec![b'x'; DEFAULT_SCAN_BUFFER_SIZE - 1];
+    content.extend_from_slice(b"\rnext\r\n");
+    fs::write(&boundary, &content).expect("Failed to write file");
+
+    let counts = count_line_endings_in_file(&boundary).expect("Should count line endings");
+    assert_eq!(counts.cr, 1, "Should have 1 lone CR");
+    assert_eq!(counts.crlf, 1, "Should have 1 CRLF");
+    assert_eq!(counts.lf, 0, "Should have no LF");
+}
+
+#[test]
+fn test_analysis_continues_after_sniffed_bytes() {
+    let temp_dir = TempDir::new().expect("Failed to create temporary directory");
+    let boundary = temp_dir.path().join("sniff_boundary.txt");
+
+    // The CRLF is split between the 8KB read for the binary check and the rest of the file
+    let mut content = b"\xef\xbb\xbf".to_vec();
+    content.resize(8191, b'x');
+    content.extend_from_slice(b"\r\nnext \n");
+    fs::write(&boundary, &content).expect("Failed to write file");
+
+    let config = create_test_config();
+    for analysis in [
+        analyze_file(&boundary, &config),
+        analyze_bytes(&boundary, &content, &config),
+    ] {
+        assert!(analysis.error.is_none(), "Analysis should not have errors");
+        assert_eq!(analysis.bom_type, Some(BomType::Utf8));
+        assert_eq!(analysis.size, content.len() as u64);
+        assert_eq!(
+            (analysis.lf_count, analysis.crlf_count, analysis.cr_count),
+            (1, 1, 0)
+        );
+        assert_eq!(analysis.first_lines.crlf, Some(1));
+        assert_eq!(analysis.trailing_whitespace_lines, 1);
+        assert_eq!(analysis.final_newline, Some(true));
+    }
 }
 
 #[test]
@@ -534,6 +693,7 @@ fn test_file_ending_with_cr_no_lf() {
     assert!(analysis.error.is_none(), "Should not error");
     assert_eq!(analysis.lf_count, 1, "Should have 1 LF");
     assert_eq!(analysis.crlf_count, 0, "Should have no CRLF");
+    assert_eq!(analysis.cr_count, 1, "Trailing CR should count as lone CR");
 }
 
 #[test]
@@ -558,6 +718,424 @@ fn test_large_file() {
     assert_eq!(analysis.lf_count, 1000, "Should have 1000 LF endings");
 }
 
+// ============================================================================
+// Lone CR (Classic Mac) Conversion Tests
+// ============================================================================
+
+#[test]
+fn test_lone_cr_file_converted_to_linux() {
+    let temp_dir = TempDir::new().expect("Failed to create temporary directory");
+    let cr_file = temp_dir.path().join("cr_only.txt");
+    fs::write(&cr_file, b"Line 1\rLine 2\rLine 3\r").expect("Failed to write file");
+
+    let mut config = create_test_config();
+    config.line_ending_target = LineEndingTarget::Linux;
+
+    let analysis = analyze_file(&cr_file, &config);
+    let result = fix_files(&config, &[analysis]);
+    assert!(result.is_ok(), "Rewrite should succeed");
+
+    let content = fs::read(&cr_file).expect("Should read file");
+    assert_eq!(content, b"Line 1\nLine 2\nLine 3\n");
+}
+
+#[test]
+fn test_stray_cr_normalized_when_converting_to_windows() {
+    let temp_dir = TempDir::new().expect("Failed to create temporary directory");
+    let crlf_file = temp_dir.path().join("stray_cr.txt");
+    fs::write(&crlf_file, b"Line 1\r\nLine 2\rLine 3\r\n").expect("Failed to write file");
+
+    let mut config = create_test_config();
+    config.line_ending_target = LineEndingTarget::Windows;
+
+    let analysis = analyze_file(&crlf_file, &config);
+    assert_eq!(analysis.cr_count, 1, "Should detect the stray CR");
+    let result = fix_files(&config, &[analysis]);
+    assert!(result.is_ok(), "Rewrite should succeed");
+
+    let converted = analyze_file(&crlf_file, &config);
+    assert!(converted.is_crlf_only(), "Stray CR should become CRLF");
+    assert_eq!(converted.crlf_count, 3);
+}
+
+#[test]
+fn test_conversion_to_mac() {
+    let temp_dir = setup_test_environment();
+    let mut config = create_test_config();
+    config.line_ending_target = LineEndingTarget::Mac;
+
+    let mixed_file = temp_dir.path().join("test_lines.txt");
+    let original = analyze_file(&mixed_file, &config);
+    let total = original.lf_count + original.crlf_count + original.cr_count;
+
+    let result = fix_files(&config, &[original]);
+    assert!(result.is_ok(), "Rewrite should succeed");
+
+    let converted = analyze_file(&mixed_file, &config);
+    assert!(converted.is_cr_only(), "File should have only CR endings");
+    assert_eq!(converted.cr_count, total, "Line count should be preserved");
+}
+
+#[test]
+fn test_cr_only_file_skipped_for_mac_target() {
+    let temp_dir = TempDir::new().expect("Failed to create temporary directory");
+    let cr_file = temp_dir.path().join("cr_only.txt");
+    fs::write(&cr_file, b"Line 1\rLine 2\r").expect("Failed to write file");
+
+    let mut config = create_test_config();
+    config.line_ending_target = LineEndingTarget::Mac;
+
+    let analysis = analyze_file(&cr_file, &config);
+    let result = fix_files(&config, &[analysis]);
+    assert!(result.is_ok(), "Fixing should succeed");
+    assert_eq!(fs::read(&cr_file).unwrap(), b"Line 1\rLine 2\r");
+    assert!(
+        !cr_file.with_extension("txt.bak").exists(),
+        "No backup should be created for a skipped file"
+    );
+}
+
+// ============================================================================
+// UTF-16 / UTF-32 Analysis Tests
+// ============================================================================
+
+/// Encodes text as UTF-16/32 code units, optionally prefixed with a BOM
+fn encode_wide(text: &str, encoding: TextEncoding, with_bom: bool) -> Vec<u8> {
+    let bom = if with_bom { "\u{FEFF}" } else { "" };
+    let text = format!("{bom}{text}");
+
+    let mut bytes = Vec::new();
+    match encoding {
+        TextEncoding::Utf16Le => text
+            .encode_utf16()
+            .for_each(|u| bytes.extend_from_slice(&u.to_le_bytes())),
+        TextEncoding::Utf16Be => text
+            .encode_utf16()
+            .for_each(|u| bytes.extend_from_slice(&u.to_be_bytes())),
+        TextEncoding::Utf32Le => text
+            .chars()
+            .for_each(|c| bytes.extend_from_slice(&u32::from(c).to_le_bytes())),
+        TextEncoding::Utf32Be => text
+            .chars()
+            .for_each(|c| bytes.extend_from_slice(&u32::from(c).to_be_bytes())),
+        TextEncoding::Utf8 => unreachable!("only wide encodings are supported"),
+    }
+    bytes
+}
+
+#[test]
+fn test_utf16_le_with_bom_is_analyzed() {
+    let temp_dir = TempDir::new().expect("Failed to create temporary directory");
+    let rc_file = temp_dir.path().join("resource.rc");
+    let content = encode_wide(
+        "// resource\r\nSTRINGTABLE\r\nBEGIN\r\nEND\r\n",
+        TextEncoding::Utf16Le,
+        true,
+    );
+    fs::write(&rc_file, content).expect("Failed to write file");
+
+    let config = create_test_config();
+    let analysis = analyze_file(&rc_file, &config);
+    assert!(analysis.error.is_none(), "Analysis should not have errors");
+    assert!(!analysis.is_binary, "UTF-16 file should not be binary");
+    assert_eq!(analysis.encoding, TextEncoding::Utf16Le);
+    assert_eq!(analysis.bom_type, Some(BomType::Utf16Le));
+    assert_eq!(analysis.crlf_count, 4, "0D 00 0A 00 should count as CRLF");
+    assert_eq!(analysis.lf_count, 0);
+    assert_eq!(analysis.cr_count, 0);
+}
+
+#[test]
+fn test_utf16_be_with_bom_is_analyzed() {
+    let temp_dir = TempDir::new().expect("Failed to create temporary directory");
+    let file = temp_dir.path().join("be.txt");
+    let content = encode_wide("one\ntwo\r\nthree\rfour", TextEncoding::Utf16Be, true);
+    fs::write(&file, content).expect("Failed to write file");
+
+    let config = create_test_config();
+    let analysis = analyze_file(&file, &config);
+    assert_eq!(analysis.encoding, TextEncoding::Utf16Be);
+    assert_eq!(analysis.bom_type, Some(BomType::Utf16Be));
+    assert_eq!(analysis.lf_count, 1);
+    assert_eq!(analysis.crlf_count, 1);
+    assert_eq!(analysis.cr_count, 1);
+    assert!(analysis.has_mixed_line_endings());
+}
+
+#[test]
+fn test_utf16_without_bom_detected_heuristically() {
+    let temp_dir = TempDir::new().expect("Failed to create temporary directory");
+    let config = create_test_config();
+
+    let le_file = temp_dir.path().join("le.ps1");
+    let text = "Write-Host 'hello'\r\nWrite-Host 'world'\r\n";
+    fs::write(&le_file, encode_wide(text, TextEncoding::Utf16Le, false))
+        .expect("Failed to write file");
+    let analysis = analyze_file(&le_file, &config);
+    assert!(!analysis.is_binary, "BOM-less UTF-16 should not be binary");
+    assert_eq!(analysis.encoding, TextEncoding::Utf16Le);
+    assert_eq!(analysis.bom_type, None, "There is no BOM to report");
+    assert_eq!(analysis.crlf_count, 2);
+
+    let be_file = temp_dir.path().join("be.ps1");
+    fs::write(&be_file, encode_wide(text, TextEncoding::Utf16Be, false))
+        .expect("Failed to write file");
+    let analysis = analyze_file(&be_file, &config);
+    assert_eq!(analysis.encoding, TextEncoding::Utf16Be);
+    assert_eq!(analysis.crlf_count, 2);
+}
+
+#[test]
+fn test_utf32_files_are_analyzed() {
+    let temp_dir = TempDir::new().expect("Failed to create temporary directory");
+    let config = create_test_config();
+
+    for (encoding, bom) in [
+        (TextEncoding::Utf32Le, BomType::Utf32Le),
+        (TextEncoding::Utf32Be, BomType::Utf32Be),
+    ] {
+        let file = temp_dir.path().join(format!("{encoding}.txt"));
+        fs::write(&file, encode_wide("a\nb\nc\r\n", encoding, true)).expect("Failed to write file");
+
+        let analysis = analyze_file(&file, &config);
+        assert!(!analysis.is_binary, "{encoding} file should not be binary");
+        assert_eq!(analysis.encoding, encoding);
+        assert_eq!(analysis.bom_type, Some(bom));
+        assert_eq!(analysis.lf_count, 2, "{encoding} LF count");
+        assert_eq!(analysis.crlf_count, 1, "{encoding} CRLF count");
+    }
+
+    let no_bom = temp_dir.path().join("no_bom32.txt");
+    fs::write(
+        &no_bom,
+        encode_wide("line\nline\n", TextEncoding::Utf32Le, false),
+    )
+    .expect("Failed to write file");
+    let analysis = analyze_file(&no_bom, &config);
+    assert_eq!(analysis.encoding, TextEncoding::Utf32Le);
+    assert_eq!(analysis.lf_count, 2);
+}
+
+/// Reader that returns at most `step` bytes per read, to split code units across reads
+struct TrickleReader<'a> {
+    data: &'a [u8],
+    step: usize,
+}
+
+impl std::io::Read for TrickleReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.step.min(buf.len()).min(self.data.len());
+        buf[..n].copy_from_slice(&self.data[..n]);
+        self.data = &self.data[n..];
+        Ok(n)
+    }
+}
+
+#[test]
+fn test_wide_code_units_split_across_reads() {
+    use line_endings::analysis::count_line_endings_with_encoding;
+    use std::io::BufReader;
+
+    for encoding in [
+        TextEncoding::Utf16Le,
+        TextEncoding::Utf16Be,
+        TextEncoding::Utf32Le,
+        TextEncoding::Utf32Be,
+    ] {
+        let content = encode_wide("a\r\nb\nc\rd\r\n", encoding, true);
+        for step in 1..=5 {
+            let reader = TrickleReader {
+                data: &content,
+                step,
+            };
+            // A tiny capacity makes the BufReader pass short reads straight through
+            let counts =
+                count_line_endings_with_encoding(BufReader::with_capacity(1, reader), encoding)
+                    .expect("Should count line endings");
+            assert_eq!(
+                (counts.lf, counts.crlf, counts.cr),
+                (1, 2, 1),
+                "{encoding} with {step}-byte reads"
+            );
+        }
+    }
+}
+
+#[test]
+fn test_binary_with_nulls_still_detected() {
+    let temp_dir = TempDir::new().expect("Failed to create temporary directory");
+    let binary = temp_dir.path().join("image.bin");
+    let content: Vec<u8> = (0..4096u32).map(|i| (i * 7).to_le_bytes()[0]).collect();
+    fs::write(&binary, content).expect("Failed to write file");
+
+    let config = create_test_config();
+    let analysis = analyze_file(&binary, &config);
+    assert!(
+        analysis.is_binary,
+        "Random bytes with nulls should be binary"
+    );
+}
+
+#[test]
+fn test_utf16_file_converted_preserving_encoding() {
+    let temp_dir = TempDir::new().expect("Failed to create temporary directory");
+    let file = temp_dir.path().join("resource.rc");
+    fs::write(&file, encode_wide("a\nb\rc\n", TextEncoding::Utf16Le, true))
+        .expect("Failed to write file");
+
+    let mut config = create_test_config();
+    config.line_ending_target = LineEndingTarget::Windows;
+    let analysis = analyze_file(&file, &config);
+    let result = fix_files(&config, &[analysis]);
+    assert!(result.is_ok(), "Rewrite should succeed");
+    assert_eq!(
+        fs::read(&file).unwrap(),
+        encode_wide("a\r\nb\r\nc\r\n", TextEncoding::Utf16Le, true),
+        "CRLF should be written as 0D 00 0A 00 with the BOM kept"
+    );
+
+    let converted = analyze_file(&file, &config);
+    assert_eq!(converted.encoding, TextEncoding::Utf16Le);
+    assert_eq!(converted.bom_type, Some(BomType::Utf16Le));
+    assert!(converted.is_crlf_only());
+}
+
+#[test]
+fn test_wide_encodings_round_trip() {
+    let temp_dir = TempDir::new().expect("Failed to create temporary directory");
+    let text_lf = "caf\u{e9}\n\u{4e2d}\u{6587}\n\u{1F600} emoji\nno newline";
+    let text_crlf = "caf\u{e9}\r\n\u{4e2d}\u{6587}\r\n\u{1F600} emoji\r\nno newline";
+
+    for encoding in [
+        TextEncoding::Utf16Le,
+        TextEncoding::Utf16Be,
+        TextEncoding::Utf32Le,
+        TextEncoding::Utf32Be,
+    ] {
+        let file = temp_dir.path().join(format!("{encoding}.txt"));
+        let original = encode_wide(text_lf, encoding, true);
+        fs::write(&file, &original).expect("Failed to write file");
+
+        rewrite_file_with_line_ending(&file, LineEnding::Crlf, encoding).expect("LF to CRLF");
+        assert_eq!(
+            fs::read(&file).unwrap(),
+            encode_wide(text_crlf, encoding, true),
+            "{encoding} LF to CRLF"
+        );
+
+        rewrite_file_with_line_ending(&file, LineEnding::Lf, encoding).expect("CRLF to LF");
+        assert_eq!(fs::read(&file).unwrap(), original, "{encoding} round trip");
+    }
+}
+
+#[test]
+fn test_utf16_be_without_bom_converted_to_linux() {
+    let temp_dir = TempDir::new().expect("Failed to create temporary directory");
+    let file = temp_dir.path().join("script.ps1");
+    fs::write(
+        &file,
+        encode_wide("Get-Item\r\nGet-Date\r\n", TextEncoding::Utf16Be, false),
+    )
+    .expect("Failed to write file");
+
+    let mut config = create_test_config();
+    config.line_ending_target = LineEndingTarget::Linux;
+    let analysis = analyze_file(&file, &config);
+    assert!(fix_files(&config, &[analysis]).is_ok());
+    assert_eq!(
+        fs::read(&file).unwrap(),
+        encode_wide("Get-Item\nGet-Date\n", TextEncoding::Utf16Be, false)
+    );
+}
+
+#[test]
+fn test_remove_bom_keeps_utf16_bom() {
+    let temp_dir = TempDir::new().expect("Failed to create temporary directory");
+    let file = temp_dir.path().join("notes.txt");
+    fs::write(
+        &file,
+        encode_wide("a\r\nb\r\n", TextEncoding::Utf16Le, true),
+    )
+    .expect("Failed to write file");
+    let path = file.to_str().unwrap();
+
+    let output = run_binary(&["--check", "-m", path]);
+    assert!(
+        output.status.success(),
+        "A UTF-16 BOM is not a violation, got: {}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+
+    let output = run_binary(&["-m", "-l", path]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("BOM kept: UTF-16 LE"));
+    assert_eq!(
+        fs::read(&file).unwrap(),
+        encode_wide("a\nb\n", TextEncoding::Utf16Le, true),
+        "Line endings are fixed and the UTF-16 BOM is kept"
+    );
+}
+
+#[test]
+fn test_incomplete_trailing_code_unit_preserved() {
+    use line_endings::processing::convert_line_endings_with_encoding;
+
+    let mut input = encode_wide("a\nb", TextEncoding::Utf16Le, false);
+    input.push(0x42); // dangling odd byte
+    let mut output = Vec::new();
+    let count = convert_line_endings_with_encoding(
+        &input[..],
+        &mut output,
+        LineEnding::Crlf,
+        TextEncoding::Utf16Le,
+    )
+    .expect("conversion should succeed");
+    assert_eq!(count, 1);
+    let mut expected = encode_wide("a\r\nb", TextEncoding::Utf16Le, false);
+    expected.push(0x42);
+    assert_eq!(output, expected);
+}
+
+#[test]
+fn test_wide_conversion_retries_interrupted_reads() {
+    use line_endings::processing::convert_line_endings_with_encoding;
+
+    /// Fails every other read with `Interrupted`
+    struct InterruptingReader<'a> {
+        data: &'a [u8],
+        interrupt: bool,
+    }
+
+    impl std::io::Read for InterruptingReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.interrupt = !self.interrupt;
+            if self.interrupt {
+                return Err(std::io::ErrorKind::Interrupted.into());
+            }
+            self.data.read(buf)
+        }
+    }
+
+    let input = encode_wide("a\nb\n", TextEncoding::Utf16Le, true);
+    let reader = InterruptingReader {
+        data: &input,
+        interrupt: false,
+    };
+    let mut output = Vec::new();
+    let count = convert_line_endings_with_encoding(
+        reader,
+        &mut output,
+        LineEnding::Crlf,
+        TextEncoding::Utf16Le,
+    )
+    .expect("Interrupted reads should be retried");
+    assert_eq!(count, 2);
+    assert_eq!(
+        output,
+        encode_wide("a\r\nb\r\n", TextEncoding::Utf16Le, true)
+    );
+}
+
 // ============================================================================
 // Backup File Tests
 // ============================================================================
@@ -576,7 +1154,7 @@ fn test_backup_file_created_on_line_ending_conversion() {
 
     let analysis = analyze_file(&linux_file, &config);
     let file_list = vec![analysis];
-    let result = rewrite_files(&config, &file_list);
+    let result = fix_files(&config, &file_list);
     assert!(result.is_ok(), "Rewrite should succeed");
 
     // Verify backup was created
@@ -600,7 +1178,7 @@ fn test_backup_file_created_on_bom_removal() {
 
     let analysis = analyze_file(&has_bom_path, &config);
     let file_list = vec![analysis];
-    let result = remove_bom_from_files(&config, &file_list);
+    let result = fix_files(&config, &file_list);
     assert!(result.is_ok(), "BOM removal should succeed");
 
     // Verify backup was created
@@ -622,7 +1200,7 @@ fn test_backup_not_overwritten_on_multiple_operations() {
     // First conversion
     let analysis = analyze_file(&linux_file, &config);
     let file_list = vec![analysis];
-    let result = rewrite_files(&config, &file_list);
+    let result = fix_files(&config, &file_list);
     assert!(result.is_ok(), "First rewrite should succeed");
 
     // Get backup creation time
@@ -638,7 +1216,7 @@ fn test_backup_not_overwritten_on_multiple_operations() {
     config.line_ending_target = LineEndingTarget::Linux;
     let analysis = analyze_file(&linux_file, &config);
     let file_list = vec![analysis];
-    let result = rewrite_files(&config, &file_list);
+    let result = fix_files(&config, &file_list);
     assert!(result.is_ok(), "Second rewrite should succeed");
 
     // Verify backup was NOT overwritten
@@ -670,7 +1248,7 @@ fn test_trailing_newline_preserved_on_conversion() {
 
     let analysis = analyze_file(&file_with_trailing, &config);
     let file_list = vec![analysis];
-    let result = rewrite_files(&config, &file_list);
+    let result = fix_files(&config, &file_list);
     assert!(result.is_ok(), "Conversion should succeed");
 
     // Verify trailing newline is preserved
@@ -694,7 +1272,7 @@ fn test_no_trailing_newline_preserved_on_conversion() {
 
     let analysis = analyze_file(&file_no_trailing, &config);
     let file_list = vec![analysis];
-    let result = rewrite_files(&config, &file_list);
+    let result = fix_files(&config, &file_list);
     assert!(result.is_ok(), "Conversion should succeed");
 
     // Verify no trailing newline is added
@@ -709,6 +1287,76 @@ fn test_no_trailing_newline_preserved_on_conversion() {
     );
 }
 
+// ============================================================================
+// Non-UTF-8 Content Tests
+// ============================================================================
+
+/// Latin-1 / Windows-1252 bytes that are invalid as UTF-8
+const LEGACY_CONTENT_LF: &[u8] =
+    b"caf\xe9 cr\xe8me\n\x93smart quotes\x94 \x80 euro\n\xff\xfe\xfd tail\n";
+const LEGACY_CONTENT_CRLF: &[u8] =
+    b"caf\xe9 cr\xe8me\r\n\x93smart quotes\x94 \x80 euro\r\n\xff\xfe\xfd tail\r\n";
+
+#[test]
+fn test_non_utf8_file_converted_to_windows() {
+    let temp_dir = TempDir::new().expect("Failed to create temporary directory");
+    let legacy_file = temp_dir.path().join("legacy.txt");
+    fs::write(&legacy_file, LEGACY_CONTENT_LF).expect("Failed to write file");
+
+    let mut config = create_test_config();
+    config.line_ending_target = LineEndingTarget::Windows;
+
+    let analysis = analyze_file(&legacy_file, &config);
+    assert!(!analysis.is_binary, "Latin-1 text should not be binary");
+    let result = fix_files(&config, &[analysis]);
+    assert!(result.is_ok(), "Rewrite of non-UTF-8 file should succeed");
+
+    let content = fs::read(&legacy_file).expect("Should read file");
+    assert_eq!(content, LEGACY_CONTENT_CRLF);
+}
+
+#[test]
+fn test_non_utf8_content_survives_round_trip() {
+    let temp_dir = TempDir::new().expect("Failed to create temporary directory");
+    let legacy_file = temp_dir.path().join("legacy.txt");
+    fs::write(&legacy_file, LEGACY_CONTENT_LF).expect("Failed to write file");
+
+    rewrite_file_with_line_ending(&legacy_file, LineEnding::Crlf, TextEncoding::Utf8)
+        .expect("LF to CRLF");
+    assert_eq!(fs::read(&legacy_file).unwrap(), LEGACY_CONTENT_CRLF);
+
+    rewrite_file_with_line_ending(&legacy_file, LineEnding::Lf, TextEncoding::Utf8)
+        .expect("CRLF to LF");
+    assert_eq!(
+        fs::read(&legacy_file).unwrap(),
+        LEGACY_CONTENT_LF,
+        "Content should be byte-identical after LF->CRLF->LF"
+    );
+}
+
+#[test]
+fn test_all_byte_values_survive_round_trip() {
+    let temp_dir = TempDir::new().expect("Failed to create temporary directory");
+    let all_bytes = temp_dir.path().join("all_bytes.txt");
+
+    // Every byte value except CR and LF, one per line
+    let mut original = Vec::new();
+    for b in (0u8..=255).filter(|&b| b != b'\r' && b != b'\n') {
+        original.push(b);
+        original.push(b'\n');
+    }
+    fs::write(&all_bytes, &original).expect("Failed to write file");
+
+    rewrite_file_with_line_ending(&all_bytes, LineEnding::Crlf, TextEncoding::Utf8)
+        .expect("LF to CRLF");
+    let converted = fs::read(&all_bytes).unwrap();
+    assert_eq!(converted.len(), original.len() + 254, "Each LF gains a CR");
+
+    rewrite_file_with_line_ending(&all_bytes, LineEnding::Lf, TextEncoding::Utf8)
+        .expect("CRLF to LF");
+    assert_eq!(fs::read(&all_bytes).unwrap(), original);
+}
+
 // ============================================================================
 // CLI/Config Tests
 // ============================================================================
@@ -739,6 +1387,30 @@ fn test_conflicting_line_ending_flags() {
     );
 }
 
+#[test]
+fn test_mac_line_ending_flag() {
+    use line_endings::config::parse_args;
+    use pico_args::Arguments;
+    use std::ffi::OsString;
+
+    let args: Vec<OsString> = vec![
+        "program".into(),
+        "--mac-line-endings".into(),
+        "test.txt".into(),
+    ];
+    let config = parse_args(Arguments::from_vec(args)).expect("Should parse successfully");
+    assert_eq!(config.line_ending_target, LineEndingTarget::Mac);
+
+    let args: Vec<OsString> = vec![
+        "program".into(),
+        "-l".into(),
+        "--mac-line-endings".into(),
+        "test.txt".into(),
+    ];
+    let result = parse_args(Arguments::from_vec(args));
+    assert!(result.is_err(), "Should error when -l and Mac are combined");
+}
+
 #[test]
 fn test_unrecognized_switch() {
     use line_endings::config::parse_args;
@@ -752,6 +1424,27 @@ fn test_unrecognized_switch() {
     assert!(result.is_err(), "Should error on unrecognized switch");
 }
 
+#[test]
+fn test_check_flag_parsed() {
+    use line_endings::config::parse_args;
+    use pico_args::Arguments;
+    use std::ffi::OsString;
+
+    let args: Vec<OsString> = vec![
+        "program".into(),
+        "--check".into(),
+        "-w".into(),
+        "a.txt".into(),
+    ];
+    let config = parse_args(Arguments::from_vec(args)).expect("Should parse successfully");
+    assert!(config.check, "check should be set");
+    assert_eq!(
+        config.policy().line_ending,
+        Some(LineEnding::Crlf),
+        "-w should be the expected ending in check mode"
+    );
+}
+
 #[test]
 fn test_remove_bom_enables_check_bom() {
     use line_endings::config::parse_args;
@@ -860,12 +1553,92 @@ fn test_non_matching_glob_pattern() {
     );
 }
 
-// ============================================================================
+#[test]
+fn test_glob_characters_in_file_name_match_literally() {
+    use line_endings::utils::get_paths_matching_glob;
+
+    let temp_dir = TempDir::new().expect("Failed to create temporary directory");
+    let report = temp_dir.path().join("report[1].txt");
+    fs::write(&report, b"content").expect("Failed to create file");
+
+    let mut config = create_test_config();
+    config.folder = Some(temp_dir.path().to_string_lossy().to_string());
+    config.supplied_paths = vec!["report[1].txt".to_string()];
+    config.recursive = false;
+
+    // As a glob the name only matches `report1.txt`, so the file itself is used
+    let paths = get_paths_matching_glob(&config).expect("Should match the file name");
+    assert_eq!(paths.len(), 1, "Should find the file, got {paths:?}");
+    assert_eq!(Path::new(&paths[0]), report);
+}
+
+#[test]
+fn test_glob_order_is_per_pattern() {
+    use line_endings::utils::get_paths_matching_glob;
+
+    let temp_dir = TempDir::new().expect("Failed to create temporary directory");
+    for name in ["a2.txt", "A1.txt", "z2.txt", "z1.txt", "sub/a3.txt"] {
+        let path = temp_dir.path().join(name);
+        fs::create_dir_all(path.parent().unwrap()).expect("Failed to create dirs");
+        fs::write(path, b"content").expect("Failed to create file");
+    }
+
+    let mut config = create_test_config();
+    config.folder = Some(temp_dir.path().to_string_lossy().to_string());
+    config.supplied_paths = vec!["z*.txt".to_string(), "a*.txt".to_string()];
+    config.recursive = true;
+
+    let paths = get_paths_matching_glob(&config).expect("Should match glob pattern");
+    let names: Vec<String> = paths
+        .iter()
+        .map(|p| {
+            Path::new(p)
+                .strip_prefix(temp_dir.path())
+                .unwrap()
+                .to_string_lossy()
+                .replace('\\', "/")
+        })
+        .collect();
+
+    // Sorted case-insensitively within each pattern, but patterns keep their order
+    assert_eq!(
+        names,
+        ["z1.txt", "z2.txt", "A1.txt", "a2.txt", "sub/a3.txt"]
+    );
+}
+
+#[test]
+fn test_stream_paths_reports_pattern_index() {
+    use line_endings::utils::stream_paths_matching_glob;
+
+    let temp_dir = setup_test_environment();
+    let mut config = create_test_config();
+    config.folder = Some(temp_dir.path().to_string_lossy().to_string());
+    config.supplied_paths = vec!["test_l*.txt".to_string(), "has_bom.txt".to_string()];
+    config.recursive = false;
+
+    let (sender, receiver) = std::sync::mpsc::channel();
+    stream_paths_matching_glob(&config, sender).expect("Should walk");
+    let mut matched: Vec<_> = receiver.into_iter().collect();
+    matched.sort_by_key(line_endings::types::MatchedPath::sort_key);
+
+    let indexes: Vec<usize> = matched.iter().map(|m| m.pattern_index).collect();
+    assert_eq!(indexes, [0, 0, 1]);
+    assert!(matched[2].path.ends_with("has_bom.txt"));
+
+    // A bad glob fails before anything is sent
+    config.supplied_paths = vec!["*.txt".to_string(), "[".to_string(), "x[".to_string()];
+    let (sender, receiver) = std::sync::mpsc::channel();
+    assert!(stream_paths_matching_glob(&config, sender).is_err());
+    assert_eq!(receiver.into_iter().count(), 0);
+}
+
+// ============================================================================
 // Error Propagation Tests
 // ============================================================================
 
 #[test]
-fn test_rewrite_files_error_message_names_failing_file() {
+fn test_fix_files_error_message_names_failing_file() {
     let temp_dir = TempDir::new().expect("Failed to create temporary directory");
     let mut config = create_test_config();
     config.line_ending_target = LineEndingTarget::Windows;
@@ -883,10 +1656,10 @@ fn test_rewrite_files_error_message_names_failing_file() {
         analyze_file(&writable_file, &config),
     ];
 
-    // Delete the first file after analysis so rewrite_file_with_line_ending fails
+    // Delete the first file after analysis so rewrite_file_with_fix fails
     fs::remove_file(&missing_file).expect("Should delete file");
 
-    let result = rewrite_files(&config, &file_list);
+    let result = fix_files(&config, &file_list);
     assert!(result.is_err(), "Should return error for missing file");
     let error_msg = result.unwrap_err().to_string();
     assert!(
@@ -897,7 +1670,7 @@ fn test_rewrite_files_error_message_names_failing_file() {
 
 /// All errors should be collected and reported together, not just the first one
 #[test]
-fn test_rewrite_files_collects_all_errors() {
+fn test_fix_files_collects_all_errors() {
     let temp_dir = TempDir::new().expect("Failed to create temporary directory");
     let mut config = create_test_config();
     config.line_ending_target = LineEndingTarget::Windows;
@@ -913,7 +1686,7 @@ fn test_rewrite_files_collects_all_errors() {
     fs::remove_file(&file1).expect("Should delete file1");
     fs::remove_file(&file2).expect("Should delete file2");
 
-    let result = rewrite_files(&config, &file_list);
+    let result = fix_files(&config, &file_list);
     assert!(result.is_err(), "Should return error");
     let error_msg = result.unwrap_err().to_string();
     assert!(
@@ -943,7 +1716,7 @@ fn test_multiple_files_processed_correctly() {
         analyze_file(&mixed_file, &config),
     ];
 
-    let result = rewrite_files(&config, &file_list);
+    let result = fix_files(&config, &file_list);
     assert!(result.is_ok(), "Should process multiple files successfully");
 
     // Verify all files now have LF only
@@ -961,3 +1734,2385 @@ fn test_multiple_files_processed_correctly() {
         "Mixed file should be converted to LF"
     );
 }
+
+// ============================================================================
+// Check Mode Tests
+// ============================================================================
+
+/// Runs the compiled binary with the given arguments
+fn run_binary(args: &[&str]) -> std::process::Output {
+    std::process::Command::new(env!("CARGO_BIN_EXE_line_endings"))
+        .args(args)
+        .output()
+        .expect("Failed to run binary")
+}
+
+/// Runs the binary with `input` piped to its stdin
+fn run_binary_with_stdin(args: &[&str], input: &[u8]) -> std::process::Output {
+    let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_line_endings"))
+        .args(args)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .expect("Failed to run binary");
+    child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(input)
+        .expect("Failed to write stdin");
+    child.wait_with_output().expect("Failed to wait for binary")
+}
+
+#[test]
+fn test_check_file_reports_violations() {
+    use line_endings::check::check_file;
+    use line_endings::types::{FilePolicy, Violation};
+
+    let temp_dir = setup_test_environment();
+    let config = create_test_config();
+
+    let lf_policy = FilePolicy {
+        line_ending: Some(LineEnding::Lf),
+        forbid_bom: false,
+        ..FilePolicy::default()
+    };
+    let mixed = analyze_file(temp_dir.path().join("test_lines.txt"), &config);
+    assert_eq!(
+        check_file(&mixed, &lf_policy),
+        vec![
+            Violation::MixedLineEndings,
+            Violation::WrongLineEnding(LineEnding::Lf)
+        ]
+    );
+
+    let windows = analyze_file(temp_dir.path().join("test_windows.txt"), &config);
+    assert_eq!(
+        check_file(&windows, &lf_policy),
+        vec![Violation::WrongLineEnding(LineEnding::Lf)]
+    );
+    assert!(
+        check_file(&windows, &FilePolicy::default()).is_empty(),
+        "Consistent CRLF passes without a required ending"
+    );
+
+    let bom = analyze_file(temp_dir.path().join("has_bom.txt"), &config);
+    let no_bom_policy = FilePolicy {
+        line_ending: Some(LineEnding::Crlf),
+        forbid_bom: true,
+        ..FilePolicy::default()
+    };
+    assert_eq!(
+        check_file(&bom, &no_bom_policy),
+        vec![Violation::UnexpectedBom(BomType::Utf8)]
+    );
+
+    let linux = analyze_file(temp_dir.path().join("test_linux.txt"), &config);
+    assert!(check_file(&linux, &lf_policy).is_empty());
+}
+
+#[test]
+fn test_check_mode_exits_non_zero_on_violations() {
+    let temp_dir = setup_test_environment();
+    let windows_file = temp_dir.path().join("test_windows.txt");
+    let original = fs::read(&windows_file).expect("Should read file");
+
+    let output = run_binary(&[
+        "--check",
+        "--linux-line-endings",
+        windows_file.to_str().unwrap(),
+    ]);
+    assert_eq!(output.status.code(), Some(1), "Violations should exit 1");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("test_windows.txt\"\tcheck failed: expected LF line endings"),
+        "Should list the failing file, got: {stdout}"
+    );
+    assert_eq!(
+        fs::read(&windows_file).unwrap(),
+        original,
+        "Check mode must not modify files"
+    );
+    assert!(
+        !windows_file.with_extension("txt.bak").exists(),
+        "Check mode must not create backups"
+    );
+}
+
+#[test]
+fn test_check_mode_exits_zero_when_clean() {
+    let temp_dir = setup_test_environment();
+    let linux_file = temp_dir.path().join("test_linux.txt");
+
+    let output = run_binary(&["--check", "-l", "-m", linux_file.to_str().unwrap()]);
+    assert!(output.status.success(), "Clean file should pass the check");
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Check passed for 1 file(s)"));
+}
+
+#[test]
+fn test_check_mode_ignores_line_endings_without_target() {
+    let temp_dir = setup_test_environment();
+    let folder = temp_dir.path().to_str().unwrap();
+    fs::write(temp_dir.path().join("mac.txt"), b"one\rtwo\r").expect("Failed to write file");
+
+    // Nothing asks about line endings, so mixed and lone CR files pass
+    let output = run_binary(&["--check", "--folder", folder, "test_lines.txt", "mac.txt"]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Check passed for 2 file(s)"));
+
+    let output = run_binary(&["--check", "-l", "--folder", folder, "mac.txt"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("lone CR"));
+}
+
+#[test]
+fn test_check_mode_flags_mixed_and_bom() {
+    let temp_dir = setup_test_environment();
+    let folder = temp_dir.path().to_str().unwrap();
+
+    let output = run_binary(&["--check", "-w", "--remove-bom", "--folder", folder, "*.txt"]);
+    assert_eq!(output.status.code(), Some(1));
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("test_lines.txt\"\tcheck failed: mixed line endings"));
+    assert!(stdout.contains("has_bom.txt\"\tcheck failed: unexpected UTF-8 BOM"));
+    assert!(
+        temp_dir.path().join("has_bom.txt").exists()
+            && !temp_dir.path().join("has_bom.txt.bak").exists(),
+        "BOM must not be removed in check mode"
+    );
+}
+
+// ============================================================================
+// JSON Output Tests
+// ============================================================================
+
+#[test]
+fn test_json_output_contains_all_fields() {
+    let temp_dir = setup_test_environment();
+    let folder = temp_dir.path().to_str().unwrap();
+    fs::write(temp_dir.path().join("binary.txt"), [0u8, 1, 2, 3]).expect("Failed to write file");
+
+    let output = run_binary(&["--format", "json", "--bom", "--folder", folder, "*.txt"]);
+    assert!(output.status.success(), "Analysis should succeed");
+
+    let report: serde_json::Value =
+        serde_json::from_slice(&output.stdout).expect("stdout should be a single JSON document");
+    let files = report["files"].as_array().expect("files array");
+    assert_eq!(files.len(), 5);
+
+    let mixed = files
+        .iter()
+        .find(|f| f["path"].as_str().unwrap().ends_with("test_lines.txt"))
+        .expect("mixed file present");
+    let mut fields: Vec<&str> = mixed
+        .as_object()
+        .expect("file object")
+        .keys()
+        .map(String::as_str)
+        .collect();
+    fields.sort_unstable();
+    assert_eq!(
+        fields,
+        [
+            "bom_checked",
+            "bom_type",
+            "cr_count",
+            "crlf_count",
+            "encoding",
+            "error",
+            "final_newline",
+            "first_lines",
+            "is_binary",
+            "lf_count",
+            "mixed",
+            "path",
+            "size",
+            "trailing_whitespace_lines",
+            "violations",
+        ],
+        "Every analysis field should be serialised"
+    );
+    let size = fs::metadata(temp_dir.path().join("test_lines.txt"))
+        .unwrap()
+        .len();
+    assert_eq!(mixed["size"], size);
+    assert!(mixed["first_lines"]["lf"].is_u64());
+    assert!(mixed["first_lines"]["crlf"].is_u64());
+    assert_eq!(mixed["first_lines"]["cr"], serde_json::Value::Null);
+    assert!(
+        mixed["first_lines"]
+            .as_object()
+            .unwrap()
+            .contains_key("trailing_whitespace")
+    );
+    assert_eq!(mixed["mixed"], true);
+    assert_eq!(mixed["lf_count"], 10);
+    assert_eq!(mixed["crlf_count"], 10);
+    assert_eq!(mixed["cr_count"], 0);
+    assert_eq!(mixed["bom_checked"], true);
+    assert_eq!(mixed["bom_type"], serde_json::Value::Null);
+    assert_eq!(mixed["encoding"], "UTF-8");
+    assert_eq!(mixed["is_binary"], false);
+    assert_eq!(mixed["error"], serde_json::Value::Null);
+
+    let bom = files
+        .iter()
+        .find(|f| f["path"].as_str().unwrap().ends_with("has_bom.txt"))
+        .expect("BOM file present");
+    assert_eq!(bom["bom_type"], "UTF-8");
+
+    let binary = files
+        .iter()
+        .find(|f| f["path"].as_str().unwrap().ends_with("binary.txt"))
+        .expect("binary file present");
+    assert_eq!(binary["is_binary"], true);
+
+    let summary = &report["summary"];
+    assert_eq!(summary["analyzed_files"], 4);
+    assert_eq!(summary["binary_files"], 1);
+    assert_eq!(summary["mixed_files"], 1);
+    assert!(summary["analysis_seconds"].is_f64());
+    assert!(summary["total_seconds"].is_f64());
+}
+
+#[test]
+fn test_ndjson_output_streams_one_object_per_line() {
+    let temp_dir = setup_test_environment();
+    let folder = temp_dir.path().to_str().unwrap();
+
+    let output = run_binary(&["--format", "ndjson", "--folder", folder, "*.txt"]);
+    assert!(output.status.success(), "Analysis should succeed");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<serde_json::Value> = stdout
+        .lines()
+        .map(|line| serde_json::from_str(line).expect("each line should be JSON"))
+        .collect();
+    assert_eq!(lines.len(), 5, "4 files plus a summary line");
+    assert!(lines[..4].iter().all(|line| line["type"] == "file"));
+    assert_eq!(lines[4]["type"], "summary");
+    assert_eq!(lines[4]["total_crlf"], 47);
+}
+
+#[test]
+fn test_json_check_mode_includes_violations() {
+    let temp_dir = setup_test_environment();
+    let windows_file = temp_dir.path().join("test_windows.txt");
+
+    let output = run_binary(&[
+        "--format",
+        "json",
+        "--check",
+        "-l",
+        windows_file.to_str().unwrap(),
+    ]);
+    assert_eq!(output.status.code(), Some(1), "Violations should exit 1");
+
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).expect("valid JSON");
+    assert_eq!(
+        report["files"][0]["violations"],
+        serde_json::json!(["expected LF line endings"])
+    );
+    assert_eq!(report["summary"]["failed_files"], 1);
+}
+
+#[test]
+fn test_json_format_rejected_when_fixing() {
+    use line_endings::config::parse_args;
+    use pico_args::Arguments;
+    use std::ffi::OsString;
+
+    let args: Vec<OsString> = vec![
+        "program".into(),
+        "--format".into(),
+        "json".into(),
+        "-l".into(),
+        "test.txt".into(),
+    ];
+    assert!(parse_args(Arguments::from_vec(args)).is_err());
+
+    let args: Vec<OsString> = vec![
+        "program".into(),
+        "--format".into(),
+        "xml".into(),
+        "test.txt".into(),
+    ];
+    let result = parse_args(Arguments::from_vec(args));
+    assert!(result.is_err(), "Unknown format should be rejected");
+}
+
+// ============================================================================
+// SARIF Output Tests
+// ============================================================================
+
+#[test]
+fn test_lone_cr_violation_unless_cr_required() {
+    use line_endings::check::check_file;
+    use line_endings::types::{FilePolicy, Violation};
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let path = temp_dir.path().join("mac.txt");
+    fs::write(&path, b"one\rtwo\rthree\r").expect("Failed to write file");
+    let result = analyze_file(&path, &create_test_config());
+
+    let lf_policy = FilePolicy {
+        line_ending: Some(LineEnding::Lf),
+        ..FilePolicy::default()
+    };
+    assert_eq!(
+        check_file(&result, &lf_policy),
+        vec![
+            Violation::WrongLineEnding(LineEnding::Lf),
+            Violation::LoneCr
+        ]
+    );
+    let cr_policy = FilePolicy {
+        line_ending: Some(LineEnding::Cr),
+        forbid_bom: false,
+        ..FilePolicy::default()
+    };
+    assert!(check_file(&result, &cr_policy).is_empty());
+}
+
+#[test]
+fn test_first_line_endings_and_offending_lines() {
+    use line_endings::analysis::find_first_line_endings;
+    use line_endings::check::first_offending_line;
+    use line_endings::types::{FirstLineEndings, Violation};
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let path = temp_dir.path().join("mixed.txt");
+    fs::write(&path, b"1\r\n2\r\n3 \n4\r5\n").expect("Failed to write file");
+    let result = analyze_file(&path, &create_test_config());
+
+    let first = find_first_line_endings(&path, TextEncoding::Utf8, DEFAULT_SCAN_BUFFER_SIZE)
+        .expect("Should scan file");
+    assert_eq!(
+        first,
+        FirstLineEndings {
+            lf: Some(3),
+            crlf: Some(1),
+            cr: Some(4),
+            trailing_whitespace: Some(3),
+        }
+    );
+
+    assert_eq!(
+        first_offending_line(&Violation::MixedLineEndings, &result, &first),
+        Some(3)
+    );
+    assert_eq!(
+        first_offending_line(
+            &Violation::WrongLineEnding(LineEnding::Crlf),
+            &result,
+            &first
+        ),
+        Some(3)
+    );
+    assert_eq!(
+        first_offending_line(&Violation::WrongLineEnding(LineEnding::Lf), &result, &first),
+        Some(1)
+    );
+    assert_eq!(
+        first_offending_line(&Violation::LoneCr, &result, &first),
+        Some(4)
+    );
+    assert_eq!(
+        first_offending_line(&Violation::TrailingWhitespace, &result, &first),
+        Some(3)
+    );
+    assert_eq!(
+        first_offending_line(&Violation::UnexpectedFinalNewline, &result, &first),
+        Some(5)
+    );
+}
+
+#[test]
+fn test_first_line_endings_in_utf16() {
+    use line_endings::analysis::find_first_line_endings;
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let path = temp_dir.path().join("wide.txt");
+    let content = encode_wide("a\nb\nc\r\n", TextEncoding::Utf16Le, true);
+    fs::write(&path, content).expect("Failed to write file");
+
+    let first =
+        find_first_line_endings(&path, TextEncoding::Utf16Le, 3).expect("Should scan UTF-16 file");
+    assert_eq!(first.lf, Some(1));
+    assert_eq!(first.crlf, Some(3));
+    assert_eq!(first.cr, None);
+}
+
+#[test]
+fn test_sarif_output_reports_rules_and_locations() {
+    let temp_dir = setup_test_environment();
+    let folder = temp_dir.path().to_str().unwrap();
+
+    let output = run_binary(&[
+        "--format", "sarif", "--check", "-l", "-m", "--folder", folder, "*.txt",
+    ]);
+    assert_eq!(output.status.code(), Some(1), "Violations should exit 1");
+
+    let log: serde_json::Value =
+        serde_json::from_slice(&output.stdout).expect("stdout should be a SARIF document");
+    assert_eq!(log["version"], "2.1.0");
+
+    let run = &log["runs"][0];
+    let rule_ids: Vec<&str> = run["tool"]["driver"]["rules"]
+        .as_array()
+        .expect("rules array")
+        .iter()
+        .map(|rule| rule["id"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        rule_ids,
+        [
+            "mixed-line-endings",
+            "wrong-line-ending",
+            "unexpected-bom",
+            "lone-cr",
+            "missing-bom",
+            "wrong-encoding",
+            "missing-final-newline",
+            "unexpected-final-newline",
+            "trailing-whitespace",
+            "file-too-large"
+        ]
+    );
+
+    let results = run["results"].as_array().expect("results array");
+    let find = |file: &str, rule: &str| {
+        results
+            .iter()
+            .find(|r| {
+                r["ruleId"] == rule
+                    && r["locations"][0]["physicalLocation"]["artifactLocation"]["uri"]
+                        .as_str()
+                        .unwrap()
+                        .ends_with(file)
+            })
+            .unwrap_or_else(|| panic!("missing {rule} result for {file}"))
+    };
+
+    let bom = find("has_bom.txt", "unexpected-bom");
+    assert_eq!(
+        bom["locations"][0]["physicalLocation"]["region"]["startLine"],
+        1
+    );
+    assert_eq!(
+        run["tool"]["driver"]["rules"]
+            [usize::try_from(bom["ruleIndex"].as_u64().unwrap()).unwrap()]["id"],
+        "unexpected-bom"
+    );
+
+    let wrong = find("test_windows.txt", "wrong-line-ending");
+    assert_eq!(
+        wrong["locations"][0]["physicalLocation"]["region"]["startLine"],
+        1
+    );
+    find("test_lines.txt", "mixed-line-endings");
+
+    assert!(
+        !results.iter().any(
+            |r| r["locations"][0]["physicalLocation"]["artifactLocation"]["uri"]
+                .as_str()
+                .unwrap()
+                .ends_with("test_linux.txt")
+        ),
+        "Clean files have no results"
+    );
+    assert_eq!(run["invocations"][0]["executionSuccessful"], true);
+}
+
+// ============================================================================
+// JUnit and Checkstyle Output Tests
+// ============================================================================
+
+#[test]
+fn test_junit_output_has_testcase_per_file() {
+    let temp_dir = setup_test_environment();
+    let folder = temp_dir.path().to_str().unwrap();
+    fs::write(temp_dir.path().join("binary.txt"), [0u8, 1, 2, 3]).expect("Failed to write file");
+
+    let output = run_binary(&[
+        "--format", "junit", "--check", "-l", "-m", "--folder", folder, "*.txt",
+    ]);
+    assert_eq!(output.status.code(), Some(1), "Violations should exit 1");
+
+    let xml = String::from_utf8_lossy(&output.stdout);
+    assert!(xml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>"));
+    assert!(xml.contains("tests=\"5\" failures=\"3\" errors=\"0\" skipped=\"1\""));
+    assert_eq!(xml.matches("<testcase ").count(), 5);
+    assert!(xml.contains("<skipped message=\"binary file\"/>"));
+    assert!(xml.contains(
+        "<failure message=\"expected LF line endings, unexpected UTF-8 BOM\" type=\"wrong-line-ending\">"
+    ));
+    assert!(xml.contains("test_lines.txt:11: mixed line endings"));
+
+    // A passing file is a testcase with no children
+    let linux_case = xml
+        .lines()
+        .find(|line| line.contains("test_linux.txt"))
+        .expect("testcase for the clean file");
+    assert!(linux_case.trim_end().ends_with("/>"));
+}
+
+#[test]
+fn test_checkstyle_output_has_error_per_violation() {
+    let temp_dir = setup_test_environment();
+    let folder = temp_dir.path().to_str().unwrap();
+    fs::write(temp_dir.path().join("a&b <c>.txt"), b"one\ntwo\r\n").expect("Failed to write file");
+
+    let output = run_binary(&[
+        "--format",
+        "checkstyle",
+        "--check",
+        "-w",
+        "--folder",
+        folder,
+        "*.txt",
+    ]);
+    assert_eq!(output.status.code(), Some(1), "Violations should exit 1");
+
+    let xml = String::from_utf8_lossy(&output.stdout);
+    assert!(xml.contains("<checkstyle version=\"4.3\">"));
+    assert_eq!(xml.matches("<file ").count(), 5, "One <file> per path");
+    assert!(
+        xml.contains("a&amp;b &lt;c&gt;.txt\">"),
+        "File names must be escaped, got: {xml}"
+    );
+    assert!(xml.contains(
+        "<error line=\"2\" severity=\"error\" message=\"mixed line endings\" source=\"line_endings.mixed-line-endings\"/>"
+    ));
+    assert_eq!(
+        xml.matches("<error ").count(),
+        5,
+        "Two errors for each mixed file and one for the LF file"
+    );
+}
+
+#[test]
+fn test_reports_list_violations_only_in_check_mode() {
+    let temp_dir = setup_test_environment();
+    let folder = temp_dir.path().to_str().unwrap();
+    // Rules from the project file apply without any fix options
+    fs::write(
+        temp_dir.path().join(".line-endings.toml"),
+        "[[rules]]\nglob = \"*\"\nline_ending = \"lf\"\n",
+    )
+    .expect("Failed to write .line-endings.toml");
+
+    for format in ["json", "sarif", "junit", "checkstyle"] {
+        let output = run_binary(&["--format", format, "--folder", folder, "*.txt"]);
+        assert!(output.status.success(), "{format} analysis should succeed");
+        let report = String::from_utf8_lossy(&output.stdout);
+        for violation in ["mixed line endings", "expected LF line endings"] {
+            assert!(
+                !report.contains(violation),
+                "{format} should not list violations without --check: {report}"
+            );
+        }
+
+        let output = run_binary(&["--format", format, "--check", "--folder", folder, "*.txt"]);
+        assert_eq!(output.status.code(), Some(1));
+        assert!(
+            String::from_utf8_lossy(&output.stdout).contains("expected LF line endings"),
+            "{format} should list violations with --check"
+        );
+    }
+}
+
+// ============================================================================
+// .gitattributes Tests
+// ============================================================================
+
+/// Creates a fake repository with root and nested `.gitattributes`, a macro and
+/// `info/attributes`, returning its directory
+fn setup_gitattributes_repo() -> TempDir {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let root = temp_dir.path();
+    fs::create_dir_all(root.join(".git/info")).expect("Failed to create .git");
+    fs::create_dir_all(root.join("docs/nested")).expect("Failed to create docs");
+    fs::create_dir_all(root.join("win")).expect("Failed to create win");
+
+    fs::write(
+        root.join(".gitattributes"),
+        "# Root attributes\n\
+         [attr]windows text eol=crlf\n\
+         * text=auto\n\
+         *.txt text eol=lf\n\
+         *.bat windows\n\
+         *.dat binary\n\
+         /anchored.txt -text\n\
+         \"name with space.txt\" eol=crlf\n",
+    )
+    .expect("Failed to write .gitattributes");
+    fs::write(
+        root.join("docs/.gitattributes"),
+        "*.txt eol=crlf\nnested/** -text\n",
+    )
+    .expect("Failed to write nested .gitattributes");
+    fs::write(root.join(".git/info/attributes"), "override.txt eol=crlf\n")
+        .expect("Failed to write info/attributes");
+
+    temp_dir
+}
+
+#[test]
+fn test_gitattributes_resolves_eol_per_path() {
+    use line_endings::gitattributes::{EolAttribute, GitAttributes};
+
+    let temp_dir = setup_gitattributes_repo();
+    let root = temp_dir.path();
+    // Discovery from a subdirectory still finds the repository root
+    let attributes = GitAttributes::discover(root.join("win")).expect("Should load attributes");
+
+    let eol = |path: &str| attributes.eol_for(root.join(path));
+    assert_eq!(eol("a.txt"), EolAttribute::Ending(LineEnding::Lf));
+    assert_eq!(eol("win/a.txt"), EolAttribute::Ending(LineEnding::Lf));
+    assert_eq!(
+        eol("docs/a.txt"),
+        EolAttribute::Ending(LineEnding::Crlf),
+        "Nested file overrides the root"
+    );
+    assert_eq!(eol("docs/nested/a.txt"), EolAttribute::NotText);
+    assert_eq!(
+        eol("run.bat"),
+        EolAttribute::Ending(LineEnding::Crlf),
+        "Macro expands to eol=crlf"
+    );
+    assert_eq!(
+        eol("image.dat"),
+        EolAttribute::NotText,
+        "binary macro unsets text"
+    );
+    assert_eq!(eol("anchored.txt"), EolAttribute::NotText);
+    assert_eq!(
+        eol("win/anchored.txt"),
+        EolAttribute::Ending(LineEnding::Lf),
+        "Leading slash anchors to the root"
+    );
+    assert_eq!(
+        eol("name with space.txt"),
+        EolAttribute::Ending(LineEnding::Crlf)
+    );
+    assert_eq!(
+        eol("override.txt"),
+        EolAttribute::Ending(LineEnding::Crlf),
+        "info/attributes has the highest precedence"
+    );
+    assert_eq!(eol("readme.md"), EolAttribute::Unspecified);
+}
+
+#[test]
+fn test_gitattributes_check_and_fix() {
+    let temp_dir = setup_gitattributes_repo();
+    let root = temp_dir.path();
+    let folder = root.to_str().unwrap();
+    fs::write(root.join("unix.txt"), b"one\r\ntwo\r\n").expect("Failed to write file");
+    fs::write(root.join("run.bat"), b"echo\necho\n").expect("Failed to write file");
+    fs::write(root.join("anchored.txt"), b"keep\r\nmixed\n").expect("Failed to write file");
+
+    let output = run_binary(&["--gitattributes", "--check", "--folder", folder, "*.*"]);
+    assert_eq!(output.status.code(), Some(1), "Violations should exit 1");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("unix.txt\"\tcheck failed: expected LF line endings"));
+    assert!(stdout.contains("run.bat\"\tcheck failed: expected CRLF line endings"));
+    assert!(!stdout.contains("anchored.txt\"\tcheck failed"));
+    assert!(stdout.contains("Skipped 1 file(s) marked binary or -text in .gitattributes"));
+
+    // Without a fix option the files are only analysed
+    let output = run_binary(&["--gitattributes", "--no-trash", "--folder", folder, "*.*"]);
+    assert!(output.status.success(), "Analysis should succeed");
+    assert_eq!(fs::read(root.join("unix.txt")).unwrap(), b"one\r\ntwo\r\n");
+    assert_eq!(fs::read(root.join("run.bat")).unwrap(), b"echo\necho\n");
+
+    let output = run_binary(&[
+        "--gitattributes",
+        "--fix",
+        "--no-trash",
+        "--folder",
+        folder,
+        "*.*",
+    ]);
+    assert!(output.status.success(), "Fixing should succeed");
+    assert_eq!(fs::read(root.join("unix.txt")).unwrap(), b"one\ntwo\n");
+    assert_eq!(fs::read(root.join("run.bat")).unwrap(), b"echo\r\necho\r\n");
+    assert_eq!(
+        fs::read(root.join("anchored.txt")).unwrap(),
+        b"keep\r\nmixed\n",
+        "-text files must not be rewritten"
+    );
+
+    let output = run_binary(&["--gitattributes", "--check", "--folder", folder, "*.*"]);
+    assert!(output.status.success(), "Fixed files should pass the check");
+}
+
+#[test]
+fn test_gitattributes_falls_back_to_global_target() {
+    let temp_dir = setup_gitattributes_repo();
+    let root = temp_dir.path();
+    fs::write(root.join("notes.md"), b"one\r\ntwo\r\n").expect("Failed to write file");
+
+    let folder = root.to_str().unwrap();
+    let output = run_binary(&[
+        "--gitattributes",
+        "--check",
+        "-l",
+        "--folder",
+        folder,
+        "notes.md",
+    ]);
+    assert_eq!(
+        output.status.code(),
+        Some(1),
+        "Files without eol use the global ending"
+    );
+}
+
+// ============================================================================
+// .editorconfig Tests
+// ============================================================================
+
+#[test]
+fn test_trailing_whitespace_and_final_newline_analysis() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let config = create_test_config();
+
+    let path = temp_dir.path().join("spaces.txt");
+    fs::write(&path, b"a \nb\t\r\nc\n  ").expect("Failed to write file");
+    let result = analyze_file(&path, &config);
+    assert_eq!(result.trailing_whitespace_lines, 3);
+    assert_eq!(result.final_newline, Some(false));
+
+    let path = temp_dir.path().join("clean.txt");
+    fs::write(&path, b"a\nb\n").expect("Failed to write file");
+    let result = analyze_file(&path, &config);
+    assert_eq!(result.trailing_whitespace_lines, 0);
+    assert_eq!(result.final_newline, Some(true));
+
+    let path = temp_dir.path().join("empty.txt");
+    fs::write(&path, b"").expect("Failed to write file");
+    assert_eq!(analyze_file(&path, &config).final_newline, None);
+}
+
+#[test]
+fn test_editorconfig_resolves_properties() {
+    use line_endings::editorconfig::{Charset, EditorConfig};
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let root = temp_dir.path();
+    fs::create_dir_all(root.join("lib/v2")).expect("Failed to create dirs");
+    fs::write(
+        root.join(".editorconfig"),
+        "# Top level\n\
+         root = true\n\
+         \n\
+         [*]\n\
+         end_of_line = lf\n\
+         insert_final_newline = true\n\
+         \n\
+         [*.{bat,cmd}]\n\
+         end_of_line = CRLF\n\
+         \n\
+         [lib/**.cs]\n\
+         charset = utf-8-bom\n\
+         \n\
+         [file{1..3}.txt]\n\
+         trim_trailing_whitespace = true\n\
+         \n\
+         [[!a]?.md]\n\
+         charset = latin1\n",
+    )
+    .expect("Failed to write .editorconfig");
+    fs::write(
+        root.join("lib/.editorconfig"),
+        "[*.bat]\nend_of_line = cr\n[v2/*]\ninsert_final_newline = unset\n",
+    )
+    .expect("Failed to write nested .editorconfig");
+
+    let config = EditorConfig::new();
+    let props = |path: &str| config.properties_for(root.join(path));
+
+    assert_eq!(props("a.txt").end_of_line, Some(LineEnding::Lf));
+    assert_eq!(props("a.txt").insert_final_newline, Some(true));
+    assert_eq!(props("run.cmd").end_of_line, Some(LineEnding::Crlf));
+    assert_eq!(
+        props("lib/run.bat").end_of_line,
+        Some(LineEnding::Cr),
+        "Closer file wins"
+    );
+    assert_eq!(props("lib/v2/x.cs").charset, Some(Charset::Utf8Bom));
+    assert_eq!(props("x.cs").charset, None, "Glob with / is anchored");
+    assert_eq!(props("lib/v2/x.cs").insert_final_newline, None);
+    assert_eq!(props("sub/file2.txt").trim_trailing_whitespace, Some(true));
+    assert_eq!(props("file4.txt").trim_trailing_whitespace, None);
+    assert_eq!(props("bx.md").charset, Some(Charset::Latin1));
+    assert_eq!(props("ax.md").charset, None);
+}
+
+#[test]
+fn test_editorconfig_check_and_fix() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let root = temp_dir.path();
+    let folder = root.to_str().unwrap();
+    fs::write(
+        root.join(".editorconfig"),
+        "root = true\n[*]\nend_of_line = lf\ninsert_final_newline = true\n\
+         trim_trailing_whitespace = true\ncharset = utf-8\n\
+         [*.bat]\nend_of_line = crlf\n[*.cs]\ncharset = utf-8-bom\n",
+    )
+    .expect("Failed to write .editorconfig");
+    fs::write(root.join("a.txt"), b"one  \r\ntwo").expect("Failed to write file");
+    fs::write(root.join("run.bat"), b"echo\n").expect("Failed to write file");
+    fs::write(root.join("A.cs"), b"class A {}\n").expect("Failed to write file");
+    fs::write(root.join("bom.txt"), b"\xef\xbb\xbfbom\n").expect("Failed to write file");
+
+    let output = run_binary(&["--editorconfig", "--check", "--folder", folder, "*.*"]);
+    assert_eq!(output.status.code(), Some(1), "Violations should exit 1");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains(
+        "a.txt\"\tcheck failed: expected LF line endings, no newline at end of file, trailing whitespace"
+    ));
+    assert!(stdout.contains("run.bat\"\tcheck failed: expected CRLF line endings"));
+    assert!(stdout.contains("A.cs\"\tcheck failed: missing BOM"));
+    assert!(stdout.contains("bom.txt\"\tcheck failed: unexpected UTF-8 BOM"));
+
+    // Without a fix option the files are only analysed
+    let output = run_binary(&["--editorconfig", "--no-trash", "--folder", folder, "*.*"]);
+    assert!(output.status.success(), "Analysis should succeed");
+    assert_eq!(fs::read(root.join("a.txt")).unwrap(), b"one  \r\ntwo");
+    assert_eq!(
+        fs::read(root.join("bom.txt")).unwrap(),
+        b"\xef\xbb\xbfbom\n"
+    );
+
+    let output = run_binary(&[
+        "--editorconfig",
+        "--fix",
+        "--no-trash",
+        "--folder",
+        folder,
+        "*.*",
+    ]);
+    assert!(output.status.success(), "Fixing should succeed");
+    assert_eq!(fs::read(root.join("a.txt")).unwrap(), b"one\ntwo\n");
+    assert_eq!(fs::read(root.join("run.bat")).unwrap(), b"echo\r\n");
+    assert_eq!(
+        fs::read(root.join("A.cs")).unwrap(),
+        b"\xef\xbb\xbfclass A {}\n"
+    );
+    assert_eq!(fs::read(root.join("bom.txt")).unwrap(), b"bom\n");
+
+    // Check again, leaving out the .bak backups
+    let output = run_binary(&[
+        "--editorconfig",
+        "--check",
+        "--folder",
+        folder,
+        "*.txt",
+        "*.bat",
+        "*.cs",
+    ]);
+    assert!(
+        output.status.success(),
+        "Fixed files should pass the check: {}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+}
+
+#[test]
+fn test_editorconfig_folder_with_parent_component() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let root = temp_dir.path();
+    fs::create_dir_all(root.join("other")).expect("Failed to create dirs");
+    fs::create_dir_all(root.join("proj")).expect("Failed to create dirs");
+    fs::write(
+        root.join("other/.editorconfig"),
+        "root = true\n[*]\nend_of_line = crlf\n",
+    )
+    .expect("Failed to write .editorconfig");
+    fs::write(root.join("proj/a.txt"), b"one\ntwo\n").expect("Failed to write file");
+
+    // `other/..` is not a parent of the file, so `other/.editorconfig` doesn't apply
+    let folder = root.join("other/../proj");
+    let output = run_binary(&[
+        "--editorconfig",
+        "--check",
+        "--folder",
+        folder.to_str().unwrap(),
+        "*.txt",
+    ]);
+    assert!(
+        output.status.success(),
+        "No .editorconfig should apply: {}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+}
+
+// ============================================================================
+// .line-endings.toml Tests
+// ============================================================================
+
+#[test]
+fn test_project_config_resolves_rules_and_globs() {
+    use line_endings::project_config::{BomRule, ProjectConfig};
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let root = temp_dir.path();
+    fs::create_dir_all(root.join("src/nested")).expect("Failed to create dirs");
+    fs::write(
+        root.join(".line-endings.toml"),
+        "include = [\"src\", \"*.bat\"]\n\
+         exclude = [\"src/generated\"]\n\
+         \n\
+         [[rules]]\n\
+         glob = \"*\"\n\
+         line_ending = \"lf\"\n\
+         bom = \"forbidden\"\n\
+         \n\
+         [[rules]]\n\
+         glob = \"*.bat\"\n\
+         line_ending = \"crlf\"\n\
+         final_newline = true\n\
+         \n\
+         [[rules]]\n\
+         glob = \"src/nested\"\n\
+         bom = \"required\"\n\
+         max_size = \"2 KB\"\n",
+    )
+    .expect("Failed to write .line-endings.toml");
+
+    let config = ProjectConfig::discover(root.join("src/nested"))
+        .expect("Config should load")
+        .expect("Config should be found from a subdirectory");
+    assert_eq!(config.path(), root.join(".line-endings.toml"));
+
+    assert!(config.includes(root.join("src/nested/a.rs")));
+    assert!(config.includes(root.join("run.bat")));
+    assert!(!config.includes(root.join("README.md")), "Not included");
+    assert!(
+        !config.includes(root.join("src/generated/x.rs")),
+        "Excluded"
+    );
+
+    let rules = config.rules_for(root.join("src/main.rs"));
+    assert_eq!(rules.line_ending, Some(LineEnding::Lf));
+    assert_eq!(rules.bom, Some(BomRule::Forbidden));
+    assert_eq!(rules.max_size, None);
+
+    let rules = config.rules_for(root.join("scripts/run.bat"));
+    assert_eq!(rules.line_ending, Some(LineEnding::Crlf), "Later rule wins");
+    assert_eq!(rules.final_newline, Some(true));
+
+    let rules = config.rules_for(root.join("src/nested/deep/a.rs"));
+    assert_eq!(rules.bom, Some(BomRule::Required));
+    assert_eq!(rules.max_size, Some(2048));
+}
+
+#[test]
+fn test_project_config_rejects_invalid_files() {
+    use line_endings::project_config::ProjectConfig;
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let path = temp_dir.path().join(".line-endings.toml");
+
+    for contents in [
+        "[[rules]]\nglob = \"*\"\nline_ending = \"lfcr\"\n",
+        "[[rules]]\nglob = \"*\"\nmax_size = \"10 parsecs\"\n",
+        "[[rules]]\nglob = \"*\"\nfinal_newlines = true\n",
+        "includes = [\"src\"]\n",
+    ] {
+        fs::write(&path, contents).expect("Failed to write .line-endings.toml");
+        assert!(
+            ProjectConfig::load(&path).is_err(),
+            "Should reject: {contents}"
+        );
+    }
+
+    let output = run_binary(&["--fix", "--folder", temp_dir.path().to_str().unwrap()]);
+    assert!(!output.status.success(), "Invalid config should fail");
+}
+
+#[test]
+fn test_project_config_check_and_fix() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let root = temp_dir.path();
+    let folder = root.to_str().unwrap();
+    fs::create_dir_all(root.join("src")).expect("Failed to create dirs");
+    fs::create_dir_all(root.join("vendor")).expect("Failed to create dirs");
+    fs::write(
+        root.join(".line-endings.toml"),
+        "include = [\"src\", \"*.bat\"]\n\
+         exclude = [\"*.bak\"]\n\
+         [[rules]]\nglob = \"*\"\nline_ending = \"lf\"\nfinal_newline = true\nbom = \"forbidden\"\n\
+         [[rules]]\nglob = \"*.bat\"\nline_ending = \"crlf\"\n\
+         [[rules]]\nglob = \"big.txt\"\nmax_size = 4\n",
+    )
+    .expect("Failed to write .line-endings.toml");
+    fs::write(root.join("src/a.rs"), b"one\r\ntwo").expect("Failed to write file");
+    fs::write(root.join("src/b.rs"), b"\xef\xbb\xbfok\n").expect("Failed to write file");
+    fs::write(root.join("src/big.txt"), b"12345\n").expect("Failed to write file");
+    fs::write(root.join("run.bat"), b"echo\n").expect("Failed to write file");
+    fs::write(root.join("vendor/v.rs"), b"v\r\n").expect("Failed to write file");
+
+    // Without a file pattern the include globs choose the files
+    let output = run_binary(&["--check", "--folder", folder]);
+    assert_eq!(output.status.code(), Some(1), "Violations should exit 1");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout
+            .contains("a.rs\"\tcheck failed: expected LF line endings, no newline at end of file")
+    );
+    assert!(stdout.contains("b.rs\"\tcheck failed: unexpected UTF-8 BOM"));
+    assert!(stdout.contains("big.txt\"\tcheck failed: larger than 4 bytes"));
+    assert!(stdout.contains("run.bat\"\tcheck failed: expected CRLF line endings"));
+    assert!(!stdout.contains("v.rs"), "vendor is not included");
+
+    // --no-config ignores the file, so the same run has no files
+    let output = run_binary(&["--check", "--no-config", "--folder", folder]);
+    assert!(
+        !output.status.success(),
+        "No input files without the config"
+    );
+
+    // Analysis alone never modifies files
+    let output = run_binary(&["--folder", folder]);
+    assert!(output.status.success());
+    assert_eq!(fs::read(root.join("src/a.rs")).unwrap(), b"one\r\ntwo");
+
+    let output = run_binary(&["--fix", "--no-trash", "--folder", folder]);
+    assert!(output.status.success(), "Fixing should succeed");
+    assert_eq!(fs::read(root.join("src/a.rs")).unwrap(), b"one\ntwo\n");
+    assert_eq!(fs::read(root.join("src/b.rs")).unwrap(), b"ok\n");
+    assert_eq!(fs::read(root.join("run.bat")).unwrap(), b"echo\r\n");
+    assert_eq!(fs::read(root.join("vendor/v.rs")).unwrap(), b"v\r\n");
+
+    // Only the size limit, which is never fixed, still fails. Backups are excluded.
+    let output = run_binary(&["--check", "--folder", folder]);
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("Check failed for 1 of 4 file(s)"),
+        "{stdout}"
+    );
+}
+
+#[test]
+fn test_project_config_folder_with_parent_component() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let root = temp_dir.path();
+    fs::create_dir_all(root.join("sub")).expect("Failed to create dirs");
+    fs::create_dir_all(root.join("other/src")).expect("Failed to create dirs");
+    fs::write(
+        root.join("sub/.line-endings.toml"),
+        "[[rules]]\nglob = \"*\"\nline_ending = \"crlf\"\n",
+    )
+    .expect("Failed to write .line-endings.toml");
+    fs::write(root.join("other/a.txt"), b"one\ntwo\n").expect("Failed to write file");
+    let folder = root.join("sub/../other");
+    let folder = folder.to_str().unwrap();
+
+    // `sub` is not a parent of `other`, so its .line-endings.toml doesn't apply
+    let output = run_binary(&["--check", "--folder", folder, "a.txt"]);
+    assert!(
+        output.status.success(),
+        "sub/.line-endings.toml should not apply: {}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+
+    // The globs of the file that does apply still match
+    fs::write(
+        root.join("other/.line-endings.toml"),
+        "include = [\"src\"]\n[[rules]]\nglob = \"*.txt\"\nline_ending = \"lf\"\n",
+    )
+    .expect("Failed to write .line-endings.toml");
+    fs::write(root.join("other/src/b.txt"), b"one\r\n").expect("Failed to write file");
+    let output = run_binary(&["--check", "--folder", folder]);
+    assert_eq!(output.status.code(), Some(1), "Violations should exit 1");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("b.txt\"\tcheck failed: expected LF line endings"),
+        "{stdout}"
+    );
+    assert!(
+        stdout.contains("Check failed for 1 of 1 file(s)"),
+        "{stdout}"
+    );
+}
+
+#[test]
+fn test_fix_requires_project_config() {
+    let temp_dir = setup_test_environment();
+    let output = run_binary(&[
+        "--fix",
+        "--folder",
+        temp_dir.path().to_str().unwrap(),
+        "*.txt",
+    ]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains(".line-endings.toml"));
+}
+
+// ============================================================================
+// Exclude and Ignore File Tests
+// ============================================================================
+
+/// Creates a tree with a `.gitignore`, an `.ignore` and some build output
+fn setup_ignore_tree() -> TempDir {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let root = temp_dir.path();
+    for dir in [".git", "src/generated", "target/debug", "scratch", "docs"] {
+        fs::create_dir_all(root.join(dir)).expect("Failed to create dirs");
+    }
+    fs::write(root.join(".gitignore"), "target/\n*.log\n").expect("Failed to write .gitignore");
+    fs::write(root.join(".ignore"), "scratch/\n").expect("Failed to write .ignore");
+    for file in [
+        ".git/HEAD.txt",
+        "src/main.txt",
+        "src/generated/out.txt",
+        "target/debug/build.txt",
+        "scratch/notes.txt",
+        "docs/guide.txt",
+        "docs/run.log",
+    ] {
+        fs::write(root.join(file), b"line\n").expect("Failed to write file");
+    }
+    temp_dir
+}
+
+/// File names from the analysis lines of a text report
+fn analysed_files(output: &std::process::Output) -> Vec<String> {
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|line| line.starts_with('"'))
+        .map(|line| line.split('"').nth(1).unwrap().replace('\\', "/"))
+        .collect()
+}
+
+#[test]
+fn test_walk_skips_ignored_paths_and_git_directory() {
+    let temp_dir = setup_ignore_tree();
+    let folder = temp_dir.path().to_str().unwrap();
+
+    let output = run_binary(&["--folder", folder, "-r", "*"]);
+    assert!(output.status.success());
+    let files = analysed_files(&output);
+    assert!(files.iter().any(|f| f.ends_with("src/main.txt")));
+    assert!(files.iter().any(|f| f.ends_with("src/generated/out.txt")));
+    assert!(files.iter().any(|f| f.ends_with("docs/guide.txt")));
+    for skipped in ["HEAD.txt", "build.txt", "notes.txt", "run.log"] {
+        assert!(
+            !files.iter().any(|f| f.ends_with(skipped)),
+            "{skipped} should be skipped: {files:?}"
+        );
+    }
+
+    let output = run_binary(&["--folder", folder, "--no-ignore", "-r", "*.txt"]);
+    assert!(output.status.success());
+    assert_eq!(
+        analysed_files(&output).len(),
+        6,
+        "--no-ignore walks everything"
+    );
+}
+
+#[test]
+fn test_exclude_option_skips_matching_paths() {
+    let temp_dir = setup_ignore_tree();
+    let folder = temp_dir.path().to_str().unwrap();
+
+    let output = run_binary(&[
+        "--folder",
+        folder,
+        "--exclude",
+        "generated/",
+        "--exclude",
+        "guide.*",
+        "**/*.txt",
+    ]);
+    assert!(output.status.success());
+    let files = analysed_files(&output);
+    assert_eq!(files.len(), 1, "{files:?}");
+    assert!(files[0].ends_with("src/main.txt"));
+
+    // Anchored globs are relative to the folder, and explicit file names are excluded too
+    let output = run_binary(&["--folder", folder, "--exclude", "/src", "src/main.txt"]);
+    assert!(
+        !output.status.success(),
+        "Excluded file leaves no input files"
+    );
+}
+
+// ============================================================================
+// File List Tests
+// ============================================================================
+
+#[test]
+fn test_files_from_stdin_keeps_list_order() {
+    let temp_dir = setup_test_environment();
+    let folder = temp_dir.path().to_str().unwrap();
+
+    // Newline separated, with a CRLF line, a blank line and a directory
+    let output = run_binary_with_stdin(
+        &["--folder", folder, "--files-from", "-"],
+        b"test_windows.txt\r\n\nsub_folder\ntest_linux.txt\n",
+    );
+    assert!(output.status.success());
+    let files = analysed_files(&output);
+    assert_eq!(files.len(), 2, "{files:?}");
+    assert!(files[0].ends_with("test_windows.txt"));
+    assert!(files[1].ends_with("test_linux.txt"));
+
+    // NUL separated, with a name containing a newline
+    fs::write(temp_dir.path().join("odd\nname.txt"), b"a\r\n").expect("Failed to write file");
+    let output = run_binary_with_stdin(
+        &[
+            "--folder",
+            folder,
+            "--files-from",
+            "-",
+            "-0",
+            "--check",
+            "-l",
+        ],
+        b"test_linux.txt\0odd\nname.txt\0",
+    );
+    assert_eq!(output.status.code(), Some(1), "CRLF file fails the check");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("Check failed for 1 of 2 file(s)"),
+        "{stdout}"
+    );
+}
+
+#[test]
+fn test_files_from_reports_missing_files() {
+    let temp_dir = setup_test_environment();
+    let folder = temp_dir.path().to_str().unwrap();
+
+    let output = run_binary_with_stdin(
+        &["--folder", folder, "--files-from", "-"],
+        b"test_linux.txt\nmissing.txt\n",
+    );
+    assert!(!output.status.success(), "A missing file is an error");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("missing.txt\terror: Failed to check file type"),
+        "{stdout}"
+    );
+    assert_eq!(analysed_files(&output).len(), 1);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Files with errors: 1"));
+}
+
+#[test]
+fn test_files_from_file_applies_exclude() {
+    let temp_dir = setup_test_environment();
+    let list = temp_dir.path().join("list.txt");
+    let path = |name: &str| temp_dir.path().join(name).to_string_lossy().into_owned();
+    fs::write(
+        &list,
+        format!(
+            "{}\n{}\n",
+            path("has_bom.txt"),
+            path("sub_folder/test_lines.txt")
+        ),
+    )
+    .expect("Failed to write list");
+
+    let output = run_binary(&[
+        "--files-from",
+        list.to_str().unwrap(),
+        "--exclude",
+        "sub_folder/",
+        "--folder",
+        temp_dir.path().to_str().unwrap(),
+    ]);
+    assert!(output.status.success());
+    let files = analysed_files(&output);
+    assert_eq!(files, [path("has_bom.txt").replace('\\', "/")]);
+}
+
+#[test]
+fn test_files_from_rejects_conflicting_options() {
+    let output = run_binary(&["--files-from", "-", "*.txt"]);
+    assert!(!output.status.success());
+    let output = run_binary(&["-0", "*.txt"]);
+    assert!(!output.status.success());
+}
+
+// ============================================================================
+// Git Selection Tests
+// ============================================================================
+
+/// Runs git in `dir`, panicking if it fails
+fn git(dir: &Path, args: &[&str]) {
+    let output = std::process::Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .expect("Failed to run git");
+    assert!(
+        output.status.success(),
+        "git {args:?} failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+/// Creates a repository with one commit holding LF and CRLF files
+fn setup_git_repo() -> TempDir {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let root = temp_dir.path();
+    git(root, &["init", "-q"]);
+    git(root, &["config", "user.email", "test@example.com"]);
+    git(root, &["config", "user.name", "Test"]);
+    git(root, &["config", "commit.gpgsign", "false"]);
+
+    fs::create_dir(root.join("src")).expect("Failed to create dir");
+    fs::write(root.join("linux.txt"), b"a\nb\n").expect("Failed to write file");
+    fs::write(root.join("windows.txt"), b"a\r\nb\r\n").expect("Failed to write file");
+    fs::write(root.join("src/main.txt"), b"a\nb\n").expect("Failed to write file");
+    git(root, &["add", "."]);
+    git(root, &["commit", "-q", "-m", "initial"]);
+    temp_dir
+}
+
+#[test]
+fn test_git_staged_checks_index_content() {
+    let temp_dir = setup_git_repo();
+    let root = temp_dir.path();
+    let folder = root.to_str().unwrap();
+
+    // Stage CRLF content, then put LF back in the working tree
+    fs::write(root.join("linux.txt"), b"a\r\nb\r\n").expect("Failed to write file");
+    git(root, &["add", "linux.txt"]);
+    fs::write(root.join("linux.txt"), b"a\nb\n").expect("Failed to write file");
+    // A staged rename and deletion, and an untracked file
+    git(root, &["mv", "src/main.txt", "src/renamed.txt"]);
+    git(root, &["rm", "-q", "windows.txt"]);
+    fs::write(root.join("untracked.txt"), b"a\r\n").expect("Failed to write file");
+
+    let output = run_binary(&["--folder", folder, "--git-staged"]);
+    assert!(output.status.success());
+    let files = analysed_files(&output);
+    assert_eq!(files.len(), 2, "{files:?}");
+    assert!(files[0].ends_with("linux.txt"));
+    assert!(files[1].ends_with("src/renamed.txt"));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("CRLF 2"), "{stdout}");
+
+    let output = run_binary(&["--folder", folder, "--git-staged", "--check", "-l"]);
+    assert_eq!(output.status.code(), Some(1), "staged CRLF fails the check");
+
+    // The working tree copy is LF, so the tracked files pass
+    let output = run_binary(&["--folder", folder, "--git-tracked", "--check", "-l"]);
+    assert!(output.status.success());
+    let files = analysed_files(&output);
+    assert_eq!(files.len(), 2, "{files:?}");
+    assert!(!files.iter().any(|f| f.ends_with("untracked.txt")));
+}
+
+#[test]
+fn test_git_changed_lists_files_since_ref() {
+    let temp_dir = setup_git_repo();
+    let root = temp_dir.path();
+    git(root, &["checkout", "-q", "-b", "feature"]);
+    fs::write(root.join("src/main.txt"), b"a\r\nb\n").expect("Failed to write file");
+    fs::write(root.join("src/new.txt"), b"a\n").expect("Failed to write file");
+    git(root, &["add", "src/new.txt"]);
+    git(root, &["commit", "-q", "-m", "add new"]);
+    // An untracked file, and one that is ignored
+    fs::write(root.join("untracked.txt"), b"a\n").expect("Failed to write file");
+    fs::write(root.join(".gitignore"), b"*.log\n").expect("Failed to write file");
+    fs::write(root.join("ignored.log"), b"a\n").expect("Failed to write file");
+
+    // Committed and uncommitted changes and untracked files all count
+    let output = run_binary(&[
+        "--folder",
+        root.to_str().unwrap(),
+        "--git-changed",
+        "HEAD~1",
+        "--exclude",
+        ".gitignore",
+    ]);
+    assert!(output.status.success());
+    let files = analysed_files(&output);
+    assert_eq!(files.len(), 3, "{files:?}");
+    assert!(files[0].ends_with("src/main.txt"));
+    assert!(files[1].ends_with("src/new.txt"));
+    assert!(files[2].ends_with("untracked.txt"));
+
+    // Paths are relative to the folder, and --exclude applies
+    let sub_folder = root.join("src");
+    let output = run_binary(&[
+        "--folder",
+        sub_folder.to_str().unwrap(),
+        "--git-changed",
+        "HEAD~1",
+        "--exclude",
+        "new.txt",
+    ]);
+    assert!(output.status.success());
+    let files = analysed_files(&output);
+    assert_eq!(files.len(), 1, "{files:?}");
+    assert!(files[0].ends_with("src/main.txt"));
+
+    let output = run_binary(&[
+        "--folder",
+        root.to_str().unwrap(),
+        "--git-changed",
+        "no-such-ref",
+    ]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_git_options_reject_conflicting_options() {
+    let temp_dir = setup_git_repo();
+    let folder = temp_dir.path().to_str().unwrap();
+
+    for args in [
+        &["--git-tracked", "--git-staged"][..],
+        &["--git-tracked", "*.txt"],
+        &["--git-changed", "HEAD", "--files-from", "-"],
+        &["--git-staged", "-l"],
+        &["--git-staged", "--remove-bom"],
+    ] {
+        let output = run_binary(&[&["--folder", folder][..], args].concat());
+        assert!(!output.status.success(), "{args:?} should be rejected");
+    }
+
+    // Outside a repository git fails
+    let not_repo = TempDir::new().expect("Failed to create temp dir");
+    let output = run_binary(&[
+        "--folder",
+        not_repo.path().to_str().unwrap(),
+        "--git-tracked",
+    ]);
+    assert!(!output.status.success());
+}
+
+// ============================================================================
+// Pre-Commit Hook Tests
+// ============================================================================
+
+/// Runs the binary in `dir`, as git runs hooks from the top of the work tree
+fn run_binary_in(dir: &Path, args: &[&str]) -> std::process::Output {
+    std::process::Command::new(env!("CARGO_BIN_EXE_line_endings"))
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .expect("Failed to run binary")
+}
+
+/// Reads the staged content of a file
+fn staged_content(dir: &Path, file: &str) -> Vec<u8> {
+    let output = std::process::Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["show", &format!(":{file}")])
+        .output()
+        .expect("Failed to run git");
+    assert!(output.status.success());
+    output.stdout
+}
+
+#[test]
+fn test_hook_install_writes_pre_commit_hook() {
+    use line_endings::hook::{HOOK_MARKER, pre_commit_script};
+
+    let script = pre_commit_script(Path::new("/opt/my tools/line_endings"), &["-l".to_string()]);
+    assert!(script.starts_with("#!/bin/sh\n"));
+    assert!(script.contains(HOOK_MARKER));
+    assert!(script.ends_with("exec '/opt/my tools/line_endings' hook run -l\n"));
+    let script = pre_commit_script(Path::new("le"), &["it's".to_string()]);
+    assert!(
+        script.ends_with("exec le hook run 'it'\\''s'\n"),
+        "{script}"
+    );
+
+    let temp_dir = setup_git_repo();
+    let root = temp_dir.path();
+    let hook_path = root.join(".git/hooks/pre-commit");
+
+    let output = run_binary_in(root, &["hook", "install", "-l", "--restage"]);
+    assert!(output.status.success());
+    let script = fs::read_to_string(&hook_path).expect("Hook should be written");
+    assert!(script.contains("hook run -l --restage"), "{script}");
+
+    // Hooks written by this tool are replaced, others need --force
+    let output = run_binary_in(root, &["hook", "install", "-w"]);
+    assert!(output.status.success());
+    fs::write(&hook_path, "#!/bin/sh\nexit 0\n").expect("Failed to write hook");
+    let output = run_binary_in(root, &["hook", "install"]);
+    assert!(!output.status.success());
+    assert_eq!(
+        fs::read_to_string(&hook_path).unwrap(),
+        "#!/bin/sh\nexit 0\n"
+    );
+    let output = run_binary_in(root, &["hook", "install", "--force"]);
+    assert!(output.status.success());
+
+    // Options are checked when installing
+    for args in [
+        &["hook", "install", "-l", "-w"][..],
+        &["hook", "install", "file.txt"],
+        &["hook", "install", "--format", "json"],
+        &["hook", "uninstall"],
+    ] {
+        let output = run_binary_in(root, args);
+        assert!(!output.status.success(), "{args:?} should be rejected");
+    }
+}
+
+#[test]
+fn test_hook_run_checks_staged_content_and_restages() {
+    let temp_dir = setup_git_repo();
+    let root = temp_dir.path();
+
+    // Nothing staged passes
+    let output = run_binary_in(root, &["hook", "run", "-l"]);
+    assert!(output.status.success());
+
+    fs::write(root.join("linux.txt"), b"a\r\nb\r\n").expect("Failed to write file");
+    fs::write(root.join("src/main.txt"), b"a\r\n").expect("Failed to write file");
+    git(root, &["add", "linux.txt", "src/main.txt"]);
+    // An unstaged change on top of the staged one
+    fs::write(root.join("src/main.txt"), b"a\r\nb\r\n").expect("Failed to write file");
+
+    let output = run_binary_in(root, &["hook", "run", "-l"]);
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("Check failed for 2 of 2 file(s)"),
+        "{stdout}"
+    );
+    assert_eq!(staged_content(root, "linux.txt"), b"a\r\nb\r\n");
+
+    // The file with unstaged changes is left alone, so the hook still fails
+    let output = run_binary_in(root, &["hook", "run", "-l", "-n", "--restage"]);
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("not fixed: has unstaged changes"),
+        "{stdout}"
+    );
+    assert_eq!(staged_content(root, "linux.txt"), b"a\nb\n");
+    assert_eq!(fs::read(root.join("linux.txt")).unwrap(), b"a\nb\n");
+    assert_eq!(staged_content(root, "src/main.txt"), b"a\r\n");
+
+    git(root, &["add", "src/main.txt"]);
+    let output = run_binary_in(root, &["hook", "run", "-l", "-n", "--restage"]);
+    assert!(output.status.success());
+    assert_eq!(staged_content(root, "src/main.txt"), b"a\nb\n");
+}
+
+#[test]
+fn test_hook_run_restages_in_folder_outside_current_directory() {
+    let temp_dir = setup_git_repo();
+    let root = temp_dir.path();
+    let elsewhere = TempDir::new().expect("Failed to create temp dir");
+
+    fs::write(root.join("linux.txt"), b"a\r\nb\r\n").expect("Failed to write file");
+    fs::write(root.join("src/main.txt"), b"a\r\n").expect("Failed to write file");
+    git(root, &["add", "linux.txt", "src/main.txt"]);
+    fs::write(root.join("src/main.txt"), b"a\r\nb\r\n").expect("Failed to write file");
+
+    // Git runs in the folder, not in the directory the hook was started from
+    let folder = root.to_str().unwrap();
+    let args = ["hook", "run", "-l", "-n", "--restage", "-f", folder];
+    let output = run_binary_in(elsewhere.path(), &args);
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("main.txt\"\tnot fixed: has unstaged changes"),
+        "{stdout}"
+    );
+    assert!(stdout.contains("Fixed and re-staged 1 file(s)"), "{stdout}");
+    assert_eq!(staged_content(root, "linux.txt"), b"a\nb\n");
+    assert_eq!(staged_content(root, "src/main.txt"), b"a\r\n");
+}
+
+#[test]
+fn test_hook_run_autofixes_named_files() {
+    let temp_dir = setup_test_environment();
+    let root = temp_dir.path();
+
+    // File names are taken literally, as the pre-commit framework passes them
+    fs::write(root.join("odd[1].txt"), b"a\r\n").expect("Failed to write file");
+    let output = run_binary_in(root, &["hook", "run", "-l", "test_linux.txt", "odd[1].txt"]);
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("Check failed for 1 of 2 file(s)"),
+        "{stdout}"
+    );
+
+    // Fixed files still fail the hook so they can be reviewed
+    let output = run_binary_in(
+        root,
+        &[
+            "hook",
+            "run",
+            "-l",
+            "-n",
+            "--autofix",
+            "test_linux.txt",
+            "odd[1].txt",
+        ],
+    );
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(fs::read(root.join("odd[1].txt")).unwrap(), b"a\n");
+
+    let output = run_binary_in(root, &["hook", "run", "-l", "test_linux.txt", "odd[1].txt"]);
+    assert!(output.status.success());
+}
+
+// ============================================================================
+// Filter Process Tests
+// ============================================================================
+
+/// Encodes data as a pkt-line
+fn pkt(data: &[u8]) -> Vec<u8> {
+    let mut packet = format!("{:04x}", data.len() + 4).into_bytes();
+    packet.extend_from_slice(data);
+    packet
+}
+
+/// Splits pkt-lines into their data, with `None` for flush packets
+fn parse_pkts(mut data: &[u8]) -> Vec<Option<Vec<u8>>> {
+    let mut packets = Vec::new();
+    while !data.is_empty() {
+        let length = usize::from_str_radix(std::str::from_utf8(&data[..4]).unwrap(), 16).unwrap();
+        if length == 0 {
+            packets.push(None);
+            data = &data[4..];
+        } else {
+            packets.push(Some(data[4..length].to_vec()));
+            data = &data[length..];
+        }
+    }
+    packets
+}
+
+#[test]
+fn test_filter_process_protocol() {
+    use line_endings::config::parse_filter_args;
+    use line_endings::filter::run_filter_process;
+
+    let config = parse_filter_args(pico_args::Arguments::from_vec(vec![
+        "-w".into(),
+        "--no-config".into(),
+    ]))
+    .expect("Options should parse");
+
+    // A large file split over several packets, and an unknown command
+    let large: Vec<u8> = b"line\n".repeat(20_000);
+    let mut input = Vec::new();
+    for line in ["git-filter-client\n", "version=2\n"] {
+        input.extend(pkt(line.as_bytes()));
+    }
+    input.extend(b"0000");
+    for line in [
+        "capability=clean\n",
+        "capability=smudge\n",
+        "capability=delay\n",
+    ] {
+        input.extend(pkt(line.as_bytes()));
+    }
+    input.extend(b"0000");
+    for (command, content) in [
+        ("clean", &b"\xEF\xBB\xBFa\r\nb\r\n"[..]),
+        ("smudge", &large[..]),
+        ("resize", b"a\n"),
+    ] {
+        input.extend(pkt(format!("command={command}\n").as_bytes()));
+        input.extend(pkt(b"pathname=dir/file.txt\n"));
+        input.extend(b"0000");
+        for chunk in content.chunks(65516) {
+            input.extend(pkt(chunk));
+        }
+        input.extend(b"0000");
+    }
+
+    let mut output = Vec::new();
+    run_filter_process(&config, input.as_slice(), &mut output).expect("Protocol should complete");
+    let packets = parse_pkts(&output);
+    let text = |index: usize| String::from_utf8(packets[index].clone().unwrap()).unwrap();
+
+    assert_eq!(text(0), "git-filter-server\n");
+    assert_eq!(text(1), "version=2\n");
+    assert_eq!(packets[2], None);
+    // Delay is not supported
+    assert_eq!(text(3), "capability=clean\n");
+    assert_eq!(text(4), "capability=smudge\n");
+    assert_eq!(packets[5], None);
+
+    // Clean: status, content, then an empty status list
+    assert_eq!(text(6), "status=success\n");
+    assert_eq!(packets[7], None);
+    assert_eq!(packets[8].as_deref(), Some(&b"a\nb\n"[..]));
+    assert_eq!(packets[9], None);
+    assert_eq!(packets[10], None);
+
+    // Smudge: the content comes back CRLF over several packets
+    assert_eq!(text(11), "status=success\n");
+    assert_eq!(packets[12], None);
+    let mut index = 13;
+    let mut smudged = Vec::new();
+    while let Some(data) = &packets[index] {
+        assert!(data.len() <= 65516);
+        smudged.extend_from_slice(data);
+        index += 1;
+    }
+    assert_eq!(smudged, b"line\r\n".repeat(20_000));
+    assert_eq!(packets[index + 1], None);
+
+    assert_eq!(text(index + 2), "status=error\n");
+    assert_eq!(packets[index + 3], None);
+    assert_eq!(packets.len(), index + 4);
+
+    // Not git
+    let mut output = Vec::new();
+    assert!(run_filter_process(&config, &b"hello"[..], &mut output).is_err());
+}
+
+#[test]
+fn test_filter_process_with_git() {
+    let temp_dir = setup_git_repo();
+    let root = temp_dir.path();
+    let filter = format!("'{}' filter -w", env!("CARGO_BIN_EXE_line_endings"));
+    git(root, &["config", "filter.line-endings.process", &filter]);
+    git(root, &["config", "filter.line-endings.required", "true"]);
+
+    fs::write(
+        root.join(".gitattributes"),
+        "*.txt filter=line-endings\n*.cs filter=line-endings\n*.dat filter=line-endings binary\n",
+    )
+    .expect("Failed to write .gitattributes");
+    fs::write(
+        root.join(".line-endings.toml"),
+        "[[rules]]\nglob = \"*.cs\"\nbom = \"required\"\n",
+    )
+    .expect("Failed to write .line-endings.toml");
+    fs::write(root.join("bom.txt"), b"\xEF\xBB\xBFa\r\nb\n").expect("Failed to write file");
+    fs::write(root.join("program.cs"), b"x\ny\n").expect("Failed to write file");
+    fs::write(root.join("data.dat"), b"\x00\x01\r\n").expect("Failed to write file");
+    git(root, &["add", "."]);
+    git(root, &["commit", "-q", "-m", "filtered"]);
+
+    // Stored LF without a BOM, except the binary file
+    assert_eq!(staged_content(root, "bom.txt"), b"a\nb\n");
+    assert_eq!(staged_content(root, "program.cs"), b"x\ny\n");
+    assert_eq!(staged_content(root, "data.dat"), b"\x00\x01\r\n");
+
+    // Checked out CRLF, with a BOM where the rules require one
+    for file in ["bom.txt", "program.cs", "data.dat"] {
+        fs::remove_file(root.join(file)).expect("Failed to remove file");
+    }
+    git(root, &["checkout", "--", "."]);
+    assert_eq!(fs::read(root.join("bom.txt")).unwrap(), b"a\r\nb\r\n");
+    assert_eq!(
+        fs::read(root.join("program.cs")).unwrap(),
+        b"\xEF\xBB\xBFx\r\ny\r\n"
+    );
+    assert_eq!(fs::read(root.join("data.dat")).unwrap(), b"\x00\x01\r\n");
+}
+
+// ============================================================================
+// Stdin Conversion Tests
+// ============================================================================
+
+#[test]
+fn test_convert_stream_applies_policy() {
+    use line_endings::processing::convert_stream;
+    use line_endings::types::{FilePolicy, LineEnding};
+
+    let convert = |input: &[u8], policy: &FilePolicy| {
+        let mut output = Vec::new();
+        convert_stream(input, &mut output, policy).expect("Conversion should succeed");
+        output
+    };
+    let lf = FilePolicy {
+        line_ending: Some(LineEnding::Lf),
+        ..FilePolicy::default()
+    };
+
+    // A CRLF split between the sniffed start and the rest of the stream
+    let mut input = vec![b'a'; 8191];
+    input.extend_from_slice(b"\r\nb\r\n");
+    let mut expected = vec![b'a'; 8191];
+    expected.extend_from_slice(b"\nb\n");
+    assert_eq!(convert(&input, &lf), expected);
+
+    // BOMs are removed or added, and kept otherwise; UTF-16 text keeps its BOM
+    let strip = FilePolicy {
+        forbid_bom: true,
+        ..FilePolicy::default()
+    };
+    assert_eq!(convert(b"\xEF\xBB\xBFa\r\n", &strip), b"a\r\n");
+    assert_eq!(
+        convert(b"\xFF\xFEa\x00\n\x00", &strip),
+        b"\xFF\xFEa\x00\n\x00"
+    );
+    let require = FilePolicy {
+        require_bom: true,
+        line_ending: Some(LineEnding::Crlf),
+        ..FilePolicy::default()
+    };
+    assert_eq!(convert(b"a\nb\n", &require), b"\xEF\xBB\xBFa\r\nb\r\n");
+    assert_eq!(convert(b"\xEF\xBB\xBFa\n", &require), b"\xEF\xBB\xBFa\r\n");
+    assert_eq!(convert(b"\xEF\xBB\xBFa\n", &lf), b"\xEF\xBB\xBFa\n");
+
+    // UTF-16 keeps its code unit width, and binary content is copied unchanged
+    assert_eq!(
+        convert(b"\xFF\xFEa\x00\r\x00\n\x00", &lf),
+        b"\xFF\xFEa\x00\n\x00"
+    );
+    assert_eq!(convert(b"\x00\x01\x02\r\n", &lf), b"\x00\x01\x02\r\n");
+    assert_eq!(convert(b"", &require), b"\xEF\xBB\xBF");
+}
+
+#[test]
+fn test_stdin_converts_to_stdout() {
+    let output = run_binary_with_stdin(&["--stdin", "-w", "-m"], b"\xEF\xBB\xBFa\nb\r\nc");
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"a\r\nb\r\nc");
+
+    let output = run_binary_with_stdin(&["--stdin", "--remove-bom"], b"\xEF\xBB\xBFa\nb\r\n");
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"a\nb\r\n");
+
+    // --stdin-path picks the rules for a file from .line-endings.toml
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    fs::write(
+        temp_dir.path().join(".line-endings.toml"),
+        "[[rules]]\nglob = \"*.cs\"\nline_ending = \"crlf\"\nbom = \"required\"\n",
+    )
+    .expect("Failed to write .line-endings.toml");
+    let folder = temp_dir.path().to_str().unwrap();
+    let path = temp_dir.path().join("src").join("Program.cs");
+    let output = run_binary_with_stdin(
+        &[
+            "--folder",
+            folder,
+            "--stdin",
+            "--fix",
+            "--stdin-path",
+            path.to_str().unwrap(),
+        ],
+        b"a\nb\n",
+    );
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"\xEF\xBB\xBFa\r\nb\r\n");
+
+    for args in [
+        &["--stdin"][..],
+        &["--stdin", "--check", "-l"],
+        &["--stdin", "-l", "file.txt"],
+        &["--stdin", "-l", "--git-tracked"],
+        &["--stdin-path", "file.txt", "-l"],
+    ] {
+        let output = run_binary_with_stdin(args, b"a\n");
+        assert!(!output.status.success(), "{args:?} should be rejected");
+    }
+}
+
+// ============================================================================
+// Dry Run Tests
+// ============================================================================
+
+#[test]
+fn test_dry_run_reports_without_modifying() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let root = temp_dir.path();
+    let folder = root.to_str().unwrap();
+    let original: &[u8] = b"\xef\xbb\xbfone\r\ntwo\nthree\r\n";
+    fs::write(root.join("mixed.txt"), original).expect("Failed to write file");
+    fs::write(root.join("lf.txt"), b"ok\n").expect("Failed to write file");
+
+    let output = run_binary(&["-l", "-m", "--dry-run", "--folder", folder, "*.txt"]);
+    assert!(output.status.success(), "Dry run should succeed");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("mixed.txt\"\twould rewrite 2 line ending(s) as LF, remove UTF-8 BOM"));
+    assert!(!stdout.contains("lf.txt\"\twould"));
+    assert!(stdout.contains("Dry run: would change 1 file(s), 1 unchanged"));
+    assert!(!stdout.contains("@@"), "Diffs are only shown with --diff");
+
+    // Nothing is written, not even backups
+    assert_eq!(fs::read(root.join("mixed.txt")).unwrap(), original);
+    assert!(!root.join("mixed.txt.bak").exists());
+
+    let output = run_binary(&["-l", "-m", "--diff", "--folder", folder, "*.txt"]);
+    assert!(output.status.success(), "Diff should succeed");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("@@ -1,3 +1,3 @@\n-<BOM>one\\r\n+one\n two\n-three\\r\n+three\n"));
+    assert_eq!(fs::read(root.join("mixed.txt")).unwrap(), original);
+
+    for args in [
+        &["--dry-run", "*.txt"][..],
+        &["--diff", "--check", "-l", "*.txt"],
+        &["--dry-run", "--stdin", "-l"],
+        &["hook", "run", "--dry-run", "-l"],
+    ] {
+        let output = run_binary(args);
+        assert!(!output.status.success(), "{args:?} should be rejected");
+    }
+}
+
+#[test]
+fn test_dry_run_reports_kept_bom() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let file = temp_dir.path().join("wide.txt");
+    let original = encode_wide("one\ntwo\n", TextEncoding::Utf16Le, true);
+    fs::write(&file, &original).expect("Failed to write file");
+
+    let output = run_binary(&["-m", "--dry-run", file.to_str().unwrap()]);
+    assert!(output.status.success(), "Dry run should succeed");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("wide.txt\"\twould keep UTF-16 LE BOM that UTF-16 LE text needs"),
+        "{stdout}"
+    );
+    assert!(stdout.contains("Dry run: would change 0 file(s), 1 unchanged"));
+    assert_eq!(fs::read(&file).unwrap(), original);
+}
+
+#[test]
+fn test_diff_skips_files_too_large_to_diff() {
+    use line_endings::chunks::PARALLEL_THRESHOLD;
+    use line_endings::preview::preview_fixes;
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let file = temp_dir.path().join("huge.txt");
+    fs::write(&file, b"one\r\ntwo\r\n").expect("Failed to write file");
+
+    let mut config = create_test_config();
+    config.line_ending_target = LineEndingTarget::Linux;
+    config.dry_run = true;
+    config.diff = true;
+    let mut analysis = analyze_file(&file, &config);
+    analysis.size = PARALLEL_THRESHOLD;
+
+    // The file is never read for its diff, so removing it is not an error
+    fs::remove_file(&file).expect("Failed to remove file");
+    assert_eq!(preview_fixes(&config, &[analysis]).unwrap(), 1);
+}
+
+#[test]
+fn test_diff_accepts_redundant_dry_run() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let file = temp_dir.path().join("crlf.txt");
+    let original: &[u8] = b"one\r\ntwo\r\n";
+    fs::write(&file, original).expect("Failed to write file");
+
+    // --diff implies --dry-run, so giving both is allowed
+    let output = run_binary(&["--dry-run", "--diff", "-l", file.to_str().unwrap()]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("@@ -1,2 +1,2 @@\n-one\\r\n-two\\r\n+one\n+two\n"),
+        "{stdout}"
+    );
+    assert_eq!(fs::read(&file).unwrap(), original);
+}
+
+#[test]
+fn test_planned_fix_matches_applied_fix() {
+    use line_endings::preview::{describe_plan, unified_diff};
+    use line_endings::processing::{apply_fix, plan_fix};
+    use line_endings::types::{FilePolicy, LineEnding};
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let root = temp_dir.path();
+    fs::write(
+        root.join(".editorconfig"),
+        "root = true\n[*]\nend_of_line = crlf\ninsert_final_newline = true\n\
+         trim_trailing_whitespace = true\ncharset = utf-8-bom\n",
+    )
+    .expect("Failed to write .editorconfig");
+    let path = root.join("a.txt");
+    let original: &[u8] = b"one  \ntwo\r\nthree\t";
+    fs::write(&path, original).expect("Failed to write file");
+
+    let policy = FilePolicy {
+        line_ending: Some(LineEnding::Crlf),
+        require_bom: true,
+        final_newline: Some(true),
+        trim_trailing_whitespace: true,
+        ..FilePolicy::default()
+    };
+    let analysis = analyze_file(&path, &create_test_config());
+    let plan = plan_fix(&analysis, &policy);
+    assert_eq!(
+        describe_plan(&analysis, &plan),
+        "rewrite 1 line ending(s) as CRLF, add UTF-8 BOM, trim trailing whitespace from 2 line(s), add final newline"
+    );
+    let mut planned = Vec::new();
+    apply_fix(original, &mut planned, &plan, analysis.encoding).unwrap();
+
+    // The preview is exactly what fixing the file writes
+    let folder = root.to_str().unwrap();
+    let output = run_binary(&[
+        "--editorconfig",
+        "--fix",
+        "--no-trash",
+        "--folder",
+        folder,
+        "a.txt",
+    ]);
+    assert!(output.status.success(), "Fixing should succeed");
+    assert_eq!(fs::read(&path).unwrap(), planned);
+    assert_eq!(planned, b"\xef\xbb\xbfone\r\ntwo\r\nthree\r\n");
+
+    // Files that already comply have nothing planned
+    let fixed = analyze_file(&path, &create_test_config());
+    assert!(plan_fix(&fixed, &policy).is_empty());
+
+    // UTF-16 is decoded for display, and a missing final newline is marked
+    let old = encode_wide("a\r\nb", TextEncoding::Utf16Le, true);
+    let new = encode_wide("a\nb", TextEncoding::Utf16Le, true);
+    assert_eq!(
+        unified_diff(Path::new("w.txt"), &old, &new, TextEncoding::Utf16Le),
+        "--- w.txt\n+++ w.txt\n@@ -1,2 +1,2 @@\n-<BOM>a\\r\n+<BOM>a\n b\n\\ No newline at end of file\n"
+    );
+}
+
+// ============================================================================
+// Vectorised Scanner Tests
+// ============================================================================
+
+#[test]
+fn test_vectorised_scan_matches_scalar() {
+    let inputs: [&[u8]; 8] = [
+        b"",
+        b"\r",
+        b"one\ntwo\r\nthree\rfour",
+        b"\r\r\n\n\r\n\r",
+        b"trailing \nspace\t\r\nnone\r\n  \r",
+        b"no terminators at all  ",
+        b"a\r\n\r\n\r\n\rb\r\r",
+        b"x\r",
+    ];
+
+    // Tiny buffers put every terminator, and every CRLF pair, across a read boundary
+    for data in inputs {
+        let scalar = count_line_endings_scalar(data, TextEncoding::Utf8, 4096).unwrap();
+        for buffer_size in (1..=9).chain([4096]) {
+            let vectorised =
+                count_line_endings_buffered(data, TextEncoding::Utf8, buffer_size).unwrap();
+            assert_eq!(
+                vectorised, scalar,
+                "{data:?} with {buffer_size} byte buffer"
+            );
+        }
+    }
+
+    // Whitespace, final newlines and first lines don't depend on the buffer either
+    let mut config = create_test_config();
+    let path = Path::new("log.txt");
+    let data = b"ok\nspace \r\ntab\t\rend\r\nlast ";
+    let expected = analyze_bytes(path, data, &config);
+    assert_eq!(expected.trailing_whitespace_lines, 3);
+    for buffer_size in 1..=7 {
+        config.scan_buffer_size = buffer_size;
+        let result = analyze_bytes(path, data, &config);
+        assert_eq!(
+            (result.lf_count, result.crlf_count, result.cr_count),
+            (expected.lf_count, expected.crlf_count, expected.cr_count)
+        );
+        assert_eq!(
+            result.trailing_whitespace_lines,
+            expected.trailing_whitespace_lines
+        );
+        assert_eq!(result.final_newline, Some(false));
+        assert_eq!(result.first_lines, expected.first_lines);
+    }
+}
+
+#[test]
+fn test_buffer_size_option() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let path = temp_dir.path().join("a.txt");
+    fs::write(&path, b"one\r\ntwo\r\n").expect("Failed to write file");
+    let file = path.to_str().unwrap();
+
+    let output = run_binary(&["--buffer-size", "4KB", "-l", "--no-trash", file]);
+    assert!(
+        output.status.success(),
+        "A valid buffer size should be accepted"
+    );
+    assert_eq!(fs::read(&path).unwrap(), b"one\ntwo\n");
+
+    for size in ["512", "2GB", "lots"] {
+        let output = run_binary(&["--buffer-size", size, file]);
+        assert!(
+            !output.status.success(),
+            "--buffer-size {size} should be rejected"
+        );
+        assert!(String::from_utf8_lossy(&output.stderr).contains("--buffer-size"));
+    }
+}
+
+#[test]
+fn test_file_larger_than_default_buffer() {
+    let temp_dir = TempDir::new().expect("Failed to create temporary directory");
+    let path = temp_dir.path().join("larger.txt");
+
+    // Enough lines to span three default buffers, with a CRLF every tenth line
+    let line = "Line with some extra text to fill the buffer";
+    let lines = 3 * DEFAULT_SCAN_BUFFER_SIZE / line.len();
+    let mut content = String::new();
+    for i in 0..lines {
+        content.push_str(line);
+        content.push_str(if i % 10 == 0 { "\r\n" } else { "\n" });
+    }
+    assert!(content.len() > 2 * DEFAULT_SCAN_BUFFER_SIZE);
+    fs::write(&path, &content).expect("Failed to write file");
+
+    let analysis = analyze_file(&path, &create_test_config());
+    assert!(analysis.error.is_none(), "Large file should not error");
+    assert_eq!(analysis.crlf_count, lines.div_ceil(10));
+    assert_eq!(analysis.lf_count, lines - lines.div_ceil(10));
+    assert_eq!(analysis.first_lines.crlf, Some(1));
+    assert_eq!(analysis.first_lines.lf, Some(2));
+}
+
+#[test]
+fn test_buffer_size_limits() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let path = temp_dir.path().join("a.txt");
+    fs::write(&path, b"one\r\ntwo\r\n").expect("Failed to write file");
+    let file = path.to_str().unwrap();
+
+    for size in ["1KB", "1024", "1GB", "1024MB"] {
+        let output = run_binary(&["--buffer-size", size, file]);
+        assert!(
+            output.status.success(),
+            "--buffer-size {size} should be accepted: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    for size in ["0", "1023", "1025MB", "1KBs", ""] {
+        let output = run_binary(&["--buffer-size", size, file]);
+        assert!(
+            !output.status.success(),
+            "--buffer-size {size} should be rejected"
+        );
+        assert!(String::from_utf8_lossy(&output.stderr).contains("--buffer-size"));
+    }
+}
+
+// ============================================================================
+// Memory-Mapped Analysis Tests
+// ============================================================================
+
+#[cfg(feature = "mmap")]
+#[test]
+fn test_map_large_file_only_maps_above_threshold() {
+    use line_endings::mmap::map_large_file;
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let path = temp_dir.path().join("small.txt");
+    fs::write(&path, b"one\r\ntwo\n").expect("Failed to write file");
+    let file = fs::File::open(&path).unwrap();
+
+    assert!(
+        map_large_file(&file, 1024).is_none(),
+        "Small files are streamed"
+    );
+    let map = map_large_file(&file, 1).expect("File should be mapped");
+    assert_eq!(&map[..], b"one\r\ntwo\n");
+
+    // Empty files cannot be mapped, and are streamed like any other
+    let empty = temp_dir.path().join("empty.txt");
+    fs::write(&empty, b"").expect("Failed to write file");
+    assert!(map_large_file(&fs::File::open(&empty).unwrap(), 0).is_none());
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn test_mapped_analysis_matches_streamed() {
+    use line_endings::analysis::is_binary_file;
+    use line_endings::mmap::MMAP_THRESHOLD;
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let path = temp_dir.path().join("export.csv");
+
+    // Just over the threshold, with terminators at the start, middle and end
+    let mut content = b"id,name \r\n".to_vec();
+    content.resize(usize::try_from(MMAP_THRESHOLD).unwrap() / 2, b'x');
+    content.extend_from_slice(b"\rmiddle\n");
+    content.resize(usize::try_from(MMAP_THRESHOLD).unwrap(), b'y');
+    content.extend_from_slice(b"\r\nlast\t");
+    fs::write(&path, &content).expect("Failed to write file");
+
+    let config = create_test_config();
+    let mapped = analyze_file(&path, &config);
+    let streamed = analyze_bytes(&path, &content, &config);
+    assert!(mapped.error.is_none(), "Mapped analysis should not error");
+    assert_eq!(mapped.size, content.len() as u64);
+    assert_eq!(
+        (mapped.lf_count, mapped.crlf_count, mapped.cr_count),
+        (1, 2, 1)
+    );
+    assert_eq!(
+        (mapped.lf_count, mapped.crlf_count, mapped.cr_count),
+        (streamed.lf_count, streamed.crlf_count, streamed.cr_count)
+    );
+    assert_eq!(mapped.trailing_whitespace_lines, 2);
+    assert_eq!(mapped.final_newline, Some(false));
+    assert_eq!(mapped.first_lines, streamed.first_lines);
+
+    let counts = count_line_endings_in_file(&path).expect("Should count line endings");
+    assert_eq!((counts.lf, counts.crlf, counts.cr), (1, 2, 1));
+    assert!(!is_binary_file(&path).unwrap());
+}
+
+// ============================================================================
+// Chunked Processing Tests
+// ============================================================================
+
+/// Content with every kind of terminator, runs of CRs and LFs, and trailing whitespace
+const CHUNK_TEST_CONTENT: &[u8] =
+    b"first \r\nsecond\nthird\r\r\n\n\rfourth\t\r\nlong line without a break for a while \rlast";
+
+#[test]
+fn test_line_aligned_chunks_keep_crlf_together() {
+    use line_endings::chunks::line_aligned_chunks;
+    use std::io::Cursor;
+
+    let data = CHUNK_TEST_CONTENT;
+    let end = data.len() as u64;
+    for chunk_size in 1..=16 {
+        let chunks = line_aligned_chunks(&mut Cursor::new(data), 3, end, chunk_size).unwrap();
+
+        // The chunks cover the content in order, each ending just after a terminator
+        assert_eq!(chunks.first().unwrap().start, 3);
+        assert_eq!(chunks.last().unwrap().end, end);
+        for pair in chunks.windows(2) {
+            assert_eq!(pair[0].end, pair[1].start);
+            let split = usize::try_from(pair[0].end).unwrap();
+            assert!(
+                matches!(data[split - 1], b'\n' | b'\r'),
+                "Chunk {pair:?} should end a line with a size of {chunk_size}"
+            );
+            assert!(
+                !(data[split - 1] == b'\r' && data[split] == b'\n'),
+                "CRLF at {split} should not be split with a size of {chunk_size}"
+            );
+        }
+    }
+
+    // A line longer than the chunk size is kept whole
+    let long = b"aaaaaaaaaaaaaaaaaaaa\nb";
+    let chunks = line_aligned_chunks(&mut Cursor::new(long), 0, 22, 4).unwrap();
+    assert_eq!(chunks, vec![0..21, 21..22]);
+}
+
+#[test]
+fn test_batch_chunks_caps_bytes_in_flight() {
+    use line_endings::chunks::batch_chunks;
+
+    let chunks = vec![0..4, 4..8, 8..20, 20..22, 22..24, 24..26];
+    let batches = batch_chunks(&chunks, 8);
+    assert_eq!(
+        batches,
+        vec![&chunks[0..2], &chunks[2..3], &chunks[3..6]],
+        "A chunk larger than the cap is a batch of its own"
+    );
+    assert!(batch_chunks(&[], 8).is_empty());
+}
+
+#[test]
+fn test_chunked_analysis_matches_streamed() {
+    use line_endings::analysis::analyze_file_in_chunks;
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let path = temp_dir.path().join("chunks.txt");
+    let mut content = b"\xef\xbb\xbf".to_vec();
+    content.extend_from_slice(CHUNK_TEST_CONTENT);
+    fs::write(&path, &content).expect("Failed to write file");
+
+    let mut config = create_test_config();
+    config.check_bom = true;
+    let expected = analyze_bytes(&path, &content, &config);
+    assert_eq!(
+        (expected.lf_count, expected.crlf_count, expected.cr_count),
+        (2, 3, 3)
+    );
+
+    for chunk_size in 1..=16 {
+        let result = analyze_file_in_chunks(&path, &config, chunk_size);
+        assert!(result.error.is_none(), "Chunked analysis should not error");
+        assert_eq!(
+            (result.lf_count, result.crlf_count, result.cr_count),
+            (expected.lf_count, expected.crlf_count, expected.cr_count),
+            "Counts should match with a chunk size of {chunk_size}"
+        );
+        assert_eq!(result.first_lines, expected.first_lines);
+        assert_eq!(
+            result.trailing_whitespace_lines,
+            expected.trailing_whitespace_lines
+        );
+        assert_eq!(result.final_newline, expected.final_newline);
+        assert_eq!(result.bom_type, Some(BomType::Utf8));
+        assert_eq!(result.size, content.len() as u64);
+    }
+}
+
+#[test]
+fn test_chunked_fix_matches_streamed() {
+    use line_endings::processing::{apply_fix, apply_fix_in_chunks};
+    use line_endings::types::PlannedFix;
+
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let with_bom = temp_dir.path().join("bom.txt");
+    let mut content = b"\xef\xbb\xbf".to_vec();
+    content.extend_from_slice(CHUNK_TEST_CONTENT);
+    fs::write(&with_bom, &content).expect("Failed to write file");
+    let without_bom = temp_dir.path().join("plain.txt");
+    fs::write(&without_bom, CHUNK_TEST_CONTENT).expect("Failed to write file");
+
+    for ending in [LineEnding::Lf, LineEnding::Crlf, LineEnding::Cr] {
+        let fixes = [
+            (&with_bom, &content[..], None),
+            (&with_bom, &content[..], Some(BomType::Utf8)),
+            (&without_bom, CHUNK_TEST_CONTENT, None),
+        ];
+        for (path, original, remove_bom) in fixes {
+            let fix = PlannedFix {
+                line_ending: Some(ending),
+                remove_bom,
+                add_bom: path == &without_bom,
+                ..PlannedFix::default()
+            };
+            let mut expected = Vec::new();
+            apply_fix(original, &mut expected, &fix, TextEncoding::Utf8).unwrap();
+
+            let file = fs::File::open(path).expect("Failed to open file");
+            for chunk_size in 1..=16 {
+                let mut chunked = Vec::new();
+                apply_fix_in_chunks(&file, &mut chunked, &fix, TextEncoding::Utf8, chunk_size)
+                    .unwrap();
+                assert_eq!(
+                    chunked, expected,
+                    "{ending:?} with a chunk size of {chunk_size}"
+                );
+            }
+        }
+    }
+}